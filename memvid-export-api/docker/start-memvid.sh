@@ -31,8 +31,17 @@ fi
 
 echo "[startup] launching memvid-export-api ..."
 set +e
-/usr/local/bin/memvid-export-api
+# Run in the background and forward SIGTERM/SIGINT so the API can drain
+# running exports; `sh` as PID 1 would not pass them on otherwise.
+/usr/local/bin/memvid-export-api &
+child=$!
+trap 'kill -TERM "$child" 2>/dev/null' TERM INT
+wait "$child"
 exit_code=$?
+if kill -0 "$child" 2>/dev/null; then
+  wait "$child"
+  exit_code=$?
+fi
 set -e
 echo "[startup] memvid-export-api exited with code=${exit_code}"
 
@@ -1,12 +1,15 @@
 import json
 import os
 import subprocess
+import tempfile
+import threading
 from typing import Any, Dict
 
 import runpod
 
 
 RUST_EXECUTABLE = os.getenv("RUNPOD_RUST_EXECUTABLE", "/usr/local/bin/memvid-export-api")
+PROGRESS_POLL_SECONDS = float(os.getenv("RUNPOD_PROGRESS_POLL_SECONDS", "1"))
 
 
 def _required(input_data: Dict[str, Any], key: str) -> str:
@@ -16,6 +19,25 @@ def _required(input_data: Dict[str, Any], key: str) -> str:
     return str(value)
 
 
+def _forward_progress(job: Dict[str, Any], progress_path: str, done: threading.Event) -> None:
+    """Sends each new snapshot of the runner's progress file to Runpod, which
+    returns it as the job output of /status while the job is IN_PROGRESS."""
+    last = None
+    while not done.wait(PROGRESS_POLL_SECONDS):
+        try:
+            with open(progress_path, "r", encoding="utf-8") as handle:
+                raw = handle.read()
+        except OSError:
+            continue
+        if raw == last:
+            continue
+        last = raw
+        try:
+            runpod.serverless.progress_update(job, json.loads(raw))
+        except Exception as err:  # progress is best effort
+            print(f"Failed to forward progress update: {err}", flush=True)
+
+
 def handler(job: Dict[str, Any]) -> Dict[str, Any]:
     job_input = job.get("input", {})
     runpod_job_id = str(job.get("id", "unknown"))
@@ -44,18 +66,50 @@ def handler(job: Dict[str, Any]) -> Dict[str, Any]:
         embedding_model,
     ]
 
+    base_capsule_ref = job_input.get("base_capsule_ref")
+    if base_capsule_ref:
+        cmd.extend(["--base-capsule", str(base_capsule_ref)])
+
+    for key, flag in (
+        ("artifact_upload_url", "--artifact-upload-url"),
+        ("sidecar_upload_url", "--sidecar-upload-url"),
+    ):
+        upload_url = job_input.get(key)
+        if upload_url:
+            cmd.extend([flag, str(upload_url)])
+
+    traceparent = job_input.get("traceparent")
+    if traceparent:
+        cmd.extend(["--traceparent", str(traceparent)])
+
+    progress_path = os.path.join(tempfile.gettempdir(), f"memvid-progress-{job_id}.json")
+    cmd.extend(["--progress-file", progress_path])
+
     env = os.environ.copy()
     ollama_host = job_input.get("ollama_host")
     if ollama_host:
         env["OLLAMA_HOST"] = str(ollama_host)
 
-    proc = subprocess.run(
-        cmd,
-        text=True,
-        capture_output=True,
-        env=env,
-        check=False,
+    done = threading.Event()
+    forwarder = threading.Thread(
+        target=_forward_progress, args=(job, progress_path, done), daemon=True
     )
+    forwarder.start()
+    try:
+        proc = subprocess.run(
+            cmd,
+            text=True,
+            capture_output=True,
+            env=env,
+            check=False,
+        )
+    finally:
+        done.set()
+        forwarder.join()
+        try:
+            os.remove(progress_path)
+        except OSError:
+            pass
 
     if proc.returncode != 0:
         return {
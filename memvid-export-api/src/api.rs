@@ -1,27 +1,45 @@
 use axum::{
-    body::Body,
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, Response, StatusCode},
-    response::{sse::Event, sse::KeepAlive, IntoResponse, Sse},
+    body::Bytes,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{sse::Event, sse::KeepAlive, IntoResponse, Redirect, Response, Sse},
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use serde_json::json;
-use std::{convert::Infallible, time::Duration};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::{Path as FsPath, PathBuf},
+    time::Duration,
+};
 use tokio::fs;
 use tokio_stream::wrappers::ReceiverStream;
-use tracing::{info, warn};
+use tracing::{info, warn, Span};
 use uuid::Uuid;
 
 use crate::{
-    artifact_store::delete_file_if_exists,
-    auth::verify_bearer,
+    artifact_store::{bundle_file_name, delete_artifact_set, ensure_bundle, ensure_local_artifact},
+    auth::{authorize, ApiKey, Scope},
     config::ExportBackendMode,
+    download::serve_file,
+    job_store::JobSnapshot,
+    mcp_index::sidecar_path_for_capsule,
+    metrics::metrics,
     models::{
         ExportAcceptedResponse, ExportEventType, ExportEventsResponse, ExportLogEvent,
         ExportRequest, ExportStage, JobBackendMetadata, JobRecord, JobState,
     },
-    queue::append_job_event,
+    queue::{append_job_event, resolve_base_capsule},
+    rate_limit::{
+        insert_rate_headers, rate_limited_response, ExportQuota, ExportUsage, RateLimitHeaders,
+    },
+    signed_url::{
+        client_ip, verify_signed_download, DownloadGrant, DownloadTarget, SignedUrlQuery,
+    },
+    telemetry,
+    transform::planned_frame_count,
+    uploads::decode_json_body,
     AppState,
 };
 
@@ -41,13 +59,53 @@ pub struct EventsQueryParams {
     pub limit: Option<usize>,
 }
 
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadUrlRequest {
+    #[serde(default)]
+    pub target: DownloadTarget,
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+    #[serde(default)]
+    pub bind_ip: Option<IpAddr>,
+    #[serde(default)]
+    pub bind_key: bool,
+}
+
 pub async fn create_export(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<ExportRequest>,
-) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
+    body: Bytes,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+
+    let payload: ExportRequest =
+        match decode_json_body(&headers, body, state.config.max_decoded_body_bytes).await {
+            Ok(payload) => payload,
+            Err(response) => return response,
+        };
+
+    submit_export(&state, payload, &key).await
+}
+
+/// Validates an export request, registers the job and puts it on the queue.
+/// Shared by direct `POST /v1/exports` and committed upload sessions.
+pub async fn submit_export(state: &AppState, payload: ExportRequest, key: &ApiKey) -> Response {
+    if state.shutdown.is_draining() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "5")],
+            Json(json!({
+                "error": {
+                    "code": "SHUTTING_DOWN",
+                    "message": "Server is shutting down; retry against another instance."
+                }
+            })),
+        )
+            .into_response();
     }
 
     if payload.nodes.is_empty() || payload.relationships.is_empty() {
@@ -63,6 +121,46 @@ pub async fn create_export(
             .into_response();
     }
 
+    if let Some(base) = payload.base_export.as_ref() {
+        // Keys may only build on their own exports; raw capsule paths are
+        // admin-only since they bypass job ownership.
+        let denied = match (&base.job_id, &base.capsule_path) {
+            (Some(job_id), _) => {
+                let jobs = state.jobs.read().await;
+                let owned = jobs
+                    .get(job_id)
+                    .is_some_and(|job| key.can_access(job.owner_key_id.as_deref()));
+                (!owned).then(|| format!("Unknown base export job {job_id}"))
+            }
+            (None, Some(_)) if !key.is_admin() => {
+                Some("baseExport.capsulePath requires an admin API key".to_string())
+            }
+            _ => None,
+        };
+        let resolved = match denied {
+            Some(message) => Err(anyhow::anyhow!(message)),
+            None => resolve_base_capsule(state, base).await,
+        };
+        if let Err(err) = resolved {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "error": {
+                        "code": "INVALID_BASE_EXPORT",
+                        "message": err.to_string()
+                    }
+                })),
+            )
+                .into_response();
+        }
+    }
+
+    // Only well-formed requests spend a rate-limit token; quotas come last.
+    let rate = state.export_rate_limiter.check(&key.id).await;
+    if !rate.allowed {
+        return rate_limited_response("RATE_LIMITED", "Export rate limit exceeded", rate.headers);
+    }
+
     let now = Utc::now();
     let job_id = Uuid::new_v4().to_string();
     let session_id = payload.session_id.clone();
@@ -70,6 +168,7 @@ pub async fn create_export(
     let node_count = payload.nodes.len();
     let relation_count = payload.relationships.len();
     let file_count = payload.file_contents.len();
+    let planned_frames = planned_frame_count(&payload);
 
     let record = JobRecord {
         job_id: job_id.clone(),
@@ -78,7 +177,8 @@ pub async fn create_export(
         status: JobState::Queued,
         progress: 0.0,
         message: Some("Queued for export".to_string()),
-        request: Some(payload),
+        // Attached once the job is persisted, so the payload is never cloned.
+        request: None,
         artifact: None,
         error: None,
         artifact_path: None,
@@ -86,6 +186,7 @@ pub async fn create_export(
         next_seq: 1,
         current_stage: ExportStage::Queued,
         stage_progress: 0.0,
+        stage_started_at: Some(now),
         last_event_at: now,
         metadata: Some(JobBackendMetadata {
             backend: match state.config.backend_mode {
@@ -95,13 +196,49 @@ pub async fn create_export(
             runpod_job_id: None,
             payload_ref: None,
             artifact_ref: None,
+            base_capsule_ref: None,
             worker_metrics: None,
         }),
+        owner_key_id: Some(key.id.clone()),
+        planned_frames,
+        produced_bytes: 0,
+        trace_parent: telemetry::traceparent(&Span::current()),
     };
 
-    {
+    let snapshot = match JobSnapshot::from_record(&record) {
+        Ok(snapshot) => snapshot,
+        Err(err) => return job_store_unavailable(&job_id, err),
+    };
+
+    // Quotas are checked under the write lock so concurrent submissions from
+    // one key cannot both slip under the limit.
+    let violation = {
         let mut jobs = state.jobs.write().await;
-        jobs.insert(job_id.clone(), record);
+        let usage = ExportUsage::for_key(jobs.values(), &key.id, now);
+        let checked = ExportQuota::from_config(&state.config).check(&usage, planned_frames, now);
+        if checked.is_ok() {
+            jobs.insert(job_id.clone(), record);
+        }
+        checked.err()
+    };
+    if let Some(violation) = violation {
+        info!(job_id = %job_id, key_id = %key.id, code = violation.code, "Export rejected by quota");
+        metrics().rate_limited("export", violation.code);
+        return rate_limited_response(
+            violation.code,
+            violation.message,
+            RateLimitHeaders {
+                reset_seconds: violation.retry_after_seconds,
+                ..rate.headers
+            },
+        );
+    }
+    if let Err(err) = state.job_store.insert_job(snapshot, &payload).await {
+        state.jobs.write().await.remove(&job_id);
+        return job_store_unavailable(&job_id, err);
+    }
+    if let Some(job) = state.jobs.write().await.get_mut(&job_id) {
+        job.request = Some(payload);
     }
     {
         let mut buses = state.event_buses.write().await;
@@ -114,6 +251,7 @@ pub async fn create_export(
         jobs.remove(&job_id);
         let mut buses = state.event_buses.write().await;
         buses.remove(&job_id);
+        let _ = state.job_store.delete_job(&job_id).await;
         return (
             StatusCode::SERVICE_UNAVAILABLE,
             Json(json!({
@@ -147,7 +285,7 @@ pub async fn create_export(
     };
 
     let _ = append_job_event(
-        &state,
+        state,
         &job_id,
         ExportEventType::StageProgress,
         ExportStage::Queued,
@@ -162,7 +300,24 @@ pub async fn create_export(
     )
     .await;
 
-    (StatusCode::ACCEPTED, Json(response)).into_response()
+    let mut response = (StatusCode::ACCEPTED, Json(response)).into_response();
+    insert_rate_headers(response.headers_mut(), &rate.headers);
+    response
+}
+
+fn job_store_unavailable(job_id: &str, err: anyhow::Error) -> Response {
+    warn!(job_id = %job_id, "Failed to persist export job: {err:#}");
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, "5")],
+        Json(json!({
+            "error": {
+                "code": "JOB_STORE_UNAVAILABLE",
+                "message": "Export job could not be recorded; retry shortly."
+            }
+        })),
+    )
+        .into_response()
 }
 
 pub async fn get_export(
@@ -170,12 +325,16 @@ pub async fn get_export(
     headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
-    }
+    let key = match authorize(&state.keys, &headers, Scope::ExportRead) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
 
     let jobs = state.jobs.read().await;
-    let Some(job) = jobs.get(&job_id) else {
+    let Some(job) = jobs
+        .get(&job_id)
+        .filter(|job| key.can_access(job.owner_key_id.as_deref()))
+    else {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({
@@ -196,15 +355,19 @@ pub async fn cancel_export(
     headers: HeaderMap,
     Path(job_id): Path<String>,
 ) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
-    }
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
 
     let mut artifact_to_delete = None;
     let mut became_canceled = false;
     let response = {
         let mut jobs = state.jobs.write().await;
-        let Some(job) = jobs.get_mut(&job_id) else {
+        let Some(job) = jobs
+            .get_mut(&job_id)
+            .filter(|job| key.can_access(job.owner_key_id.as_deref()))
+        else {
             return (
                 StatusCode::NOT_FOUND,
                 Json(json!({
@@ -240,6 +403,7 @@ pub async fn cancel_export(
     };
 
     if became_canceled {
+        state.job_cancellations.cancel(&job_id);
         let _ = append_job_event(
             &state,
             &job_id,
@@ -254,7 +418,7 @@ pub async fn cancel_export(
     }
 
     if let Some(path) = artifact_to_delete {
-        if let Err(err) = delete_file_if_exists(&path).await {
+        if let Err(err) = delete_artifact_set(state.artifacts.as_ref(), &path).await {
             warn!(
                 "Failed removing artifact during cancel {}: {err:#}",
                 path.display()
@@ -271,9 +435,10 @@ pub async fn get_export_events(
     Path(job_id): Path<String>,
     Query(params): Query<EventsQueryParams>,
 ) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
-    }
+    let key = match authorize(&state.keys, &headers, Scope::ExportRead) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
 
     let since_seq = params.since_seq.unwrap_or(0);
     let limit = params
@@ -282,7 +447,10 @@ pub async fn get_export_events(
         .clamp(1, EVENTS_MAX_LIMIT);
 
     let jobs = state.jobs.read().await;
-    let Some(job) = jobs.get(&job_id) else {
+    let Some(job) = jobs
+        .get(&job_id)
+        .filter(|job| key.can_access(job.owner_key_id.as_deref()))
+    else {
         return (
             StatusCode::NOT_FOUND,
             Json(json!({
@@ -332,14 +500,18 @@ pub async fn stream_export_events(
     Path(job_id): Path<String>,
     Query(params): Query<EventsQueryParams>,
 ) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
-    }
+    let key = match authorize(&state.keys, &headers, Scope::ExportRead) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
 
     let since_seq = params.since_seq.unwrap_or(0);
     let replay_events = {
         let jobs = state.jobs.read().await;
-        let Some(job) = jobs.get(&job_id) else {
+        let Some(job) = jobs
+            .get(&job_id)
+            .filter(|job| key.can_access(job.owner_key_id.as_deref()))
+        else {
             return (
                 StatusCode::NOT_FOUND,
                 Json(json!({
@@ -390,8 +562,11 @@ pub async fn stream_export_events(
     };
 
     let (tx, out_rx) = tokio::sync::mpsc::channel::<Result<Event, Infallible>>(256);
+    let shutdown = state.shutdown.clone();
     tokio::spawn(async move {
+        let mut last_seq = since_seq;
         for event in replay_events {
+            last_seq = event.seq;
             if tx.send(Ok(to_sse_event(&event))).await.is_err() {
                 return;
             }
@@ -399,8 +574,20 @@ pub async fn stream_export_events(
 
         let mut rx = rx;
         loop {
-            match rx.recv().await {
+            let received = tokio::select! {
+                _ = shutdown.stopping() => {
+                    // Lets clients reconnect elsewhere with `sinceSeq`.
+                    let closing = Event::default()
+                        .event("stream_closed")
+                        .data(json!({ "reason": "server_shutdown", "lastSeq": last_seq }).to_string());
+                    let _ = tx.send(Ok(closing)).await;
+                    return;
+                }
+                received = rx.recv() => received,
+            };
+            match received {
                 Ok(event) => {
+                    last_seq = event.seq;
                     if tx.send(Ok(to_sse_event(&event))).await.is_err() {
                         return;
                     }
@@ -430,99 +617,363 @@ fn to_sse_event(event: &ExportLogEvent) -> Event {
         }))
 }
 
+/// Looks up a completed job's capsule path and file name, or the error
+/// response to return when it cannot be downloaded.
+/// `key` is `None` for unbound signed URLs, whose signature already proves
+/// access was granted by someone who could see the job.
+async fn resolve_download_artifact(
+    state: &AppState,
+    key: Option<&ApiKey>,
+    job_id: &str,
+) -> Result<(PathBuf, String), Response> {
+    let jobs = state.jobs.read().await;
+    let Some(job) = jobs
+        .get(job_id)
+        .filter(|job| key.is_none_or(|key| key.can_access(job.owner_key_id.as_deref())))
+    else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": {
+                    "code": "JOB_NOT_FOUND",
+                    "message": "Export job not found."
+                }
+            })),
+        )
+            .into_response());
+    };
+
+    if !matches!(job.status, JobState::Completed) {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+                "error": {
+                    "code": "ARTIFACT_NOT_READY",
+                    "message": "Export artifact is not ready for download."
+                }
+            })),
+        )
+            .into_response());
+    }
+
+    let Some(path) = &job.artifact_path else {
+        return Err((
+            StatusCode::GONE,
+            Json(json!({
+                "error": {
+                    "code": "ARTIFACT_MISSING",
+                    "message": "Export artifact has been removed."
+                }
+            })),
+        )
+            .into_response());
+    };
+
+    let file_name = job
+        .artifact
+        .as_ref()
+        .map(|a| a.file_name.clone())
+        .unwrap_or_else(|| format!("{job_id}.mv2"));
+
+    Ok((path.clone(), file_name))
+}
+
+/// Capsules held in an object store are downloaded from it directly, via a
+/// short-lived presigned URL, unless `MEMVID_S3_REDIRECT_DOWNLOADS=false`.
+/// Returns `None` when the API should stream the file itself.
+fn artifact_store_redirect(state: &AppState, path: &FsPath, file_name: &str) -> Option<Response> {
+    if !state.config.s3_redirect_downloads {
+        return None;
+    }
+    let ttl = Duration::from_secs(state.config.s3_presign_ttl_seconds);
+    match state.artifacts.presigned_get(path, file_name, ttl) {
+        Ok(url) => url.map(|url| Redirect::temporary(&url).into_response()),
+        Err(err) => {
+            warn!(path = %path.display(), "Failed to presign artifact download: {err:#}");
+            None
+        }
+    }
+}
+
+/// Fetches a capsule and its sidecar from the artifact store when they are
+/// not on local disk; a capsule that is still missing is reported as gone by
+/// `serve_file`.
+async fn ensure_local_download(state: &AppState, job_id: &str, path: &FsPath) {
+    if let Err(err) = ensure_local_artifact(state.artifacts.as_ref(), path).await {
+        warn!(job_id = %job_id, "Failed to fetch artifact from store: {err:#}");
+    }
+}
+
+/// Downloads accept either a bearer token or a pre-signed URL. A signed URL
+/// bound to a key id is only honoured while that key is still valid.
+async fn authorize_download(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: &SignedUrlQuery,
+    peer: SocketAddr,
+    job_id: &str,
+    target: DownloadTarget,
+) -> Result<Option<ApiKey>, Response> {
+    if !query.is_signed() {
+        return authorize(&state.keys, headers, Scope::ExportRead)
+            .map(Some)
+            .map_err(IntoResponse::into_response);
+    }
+
+    let invalid = |message: &str| {
+        (
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "code": "INVALID_SIGNATURE",
+                    "message": message
+                }
+            })),
+        )
+            .into_response()
+    };
+    let ip = client_ip(headers, peer, state.config.trust_forwarded_for);
+    verify_signed_download(
+        &state.config.download_signing_key,
+        job_id,
+        target,
+        query,
+        ip,
+        Utc::now(),
+    )
+    .map_err(invalid)?;
+
+    let Some(key_id) = &query.kid else {
+        return Ok(None);
+    };
+    match state.keys.get(key_id) {
+        Some(key) if !key.is_expired() && key.has_scope(Scope::ExportRead) => Ok(Some(key.clone())),
+        _ => Err(invalid(
+            "The API key this URL was issued for is no longer valid",
+        )),
+    }
+}
+
+/// Mints a pre-signed URL for one of a job's downloads. The URL works
+/// without a bearer token until it expires, which is never later than the
+/// artifact's own `expiresAt`.
+pub async fn create_download_url(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    body: Bytes,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportRead) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+
+    let request: DownloadUrlRequest = if body.is_empty() {
+        DownloadUrlRequest::default()
+    } else {
+        match decode_json_body(&headers, body, state.config.max_decoded_body_bytes).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        }
+    };
+
+    if let Err(response) = resolve_download_artifact(&state, Some(&key), &job_id).await {
+        return response;
+    }
+    let artifact_expires_at = state
+        .jobs
+        .read()
+        .await
+        .get(&job_id)
+        .and_then(|job| job.artifact.as_ref())
+        .map(|artifact| artifact.expires_at);
+
+    let ttl = request
+        .ttl_seconds
+        .unwrap_or(state.config.signed_url_ttl_seconds)
+        .clamp(1, state.config.signed_url_max_ttl_seconds);
+    let mut expires_at = Utc::now() + chrono::Duration::seconds(ttl as i64);
+    if let Some(artifact_expires_at) = artifact_expires_at {
+        expires_at = expires_at.min(artifact_expires_at);
+    }
+
+    let grant = DownloadGrant {
+        job_id: &job_id,
+        target: request.target,
+        expires_at: expires_at.timestamp(),
+        ip: request.bind_ip,
+        key_id: request.bind_key.then_some(key.id.as_str()),
+    };
+    let url = grant.signed_url(&state.config.download_signing_key);
+
+    info!(
+        job_id = %job_id,
+        target = request.target.as_str(),
+        key_id = %key.id,
+        expires_at = grant.expires_at,
+        "Signed download URL issued"
+    );
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "url": url,
+            "target": request.target.as_str(),
+            "expiresAt": DateTime::from_timestamp(grant.expires_at, 0),
+            "boundIp": grant.ip,
+            "boundKeyId": grant.key_id,
+        })),
+    )
+        .into_response()
+}
+
 pub async fn download_export(
     State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    method: Method,
     headers: HeaderMap,
     Path(job_id): Path<String>,
+    Query(signed): Query<SignedUrlQuery>,
 ) -> impl IntoResponse {
-    if let Err(err) = verify_bearer(&headers, &state.config.api_key) {
-        return err.into_response();
-    }
+    let key = match authorize_download(
+        &state,
+        &headers,
+        &signed,
+        peer,
+        &job_id,
+        DownloadTarget::Capsule,
+    )
+    .await
+    {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
 
-    let (path, artifact_file_name, status) = {
-        let jobs = state.jobs.read().await;
-        let Some(job) = jobs.get(&job_id) else {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(json!({
-                    "error": {
-                        "code": "JOB_NOT_FOUND",
-                        "message": "Export job not found."
-                    }
-                })),
-            )
-                .into_response();
+    let (path, artifact_file_name) =
+        match resolve_download_artifact(&state, key.as_ref(), &job_id).await {
+            Ok(found) => found,
+            Err(response) => return response,
         };
 
-        if !matches!(job.status, JobState::Completed) {
-            return (
-                StatusCode::CONFLICT,
-                Json(json!({
-                    "error": {
-                        "code": "ARTIFACT_NOT_READY",
-                        "message": "Export artifact is not ready for download."
-                    }
-                })),
-            )
-                .into_response();
-        }
+    info!(
+        job_id = %job_id,
+        artifact = %artifact_file_name,
+        method = %method,
+        range = headers
+            .get(header::RANGE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("-"),
+        "Export artifact download requested"
+    );
 
-        let Some(path) = &job.artifact_path else {
-            return (
-                StatusCode::GONE,
-                Json(json!({
-                    "error": {
-                        "code": "ARTIFACT_MISSING",
-                        "message": "Export artifact has been removed."
-                    }
-                })),
-            )
-                .into_response();
-        };
+    if let Some(redirect) = artifact_store_redirect(&state, &path, &artifact_file_name) {
+        return redirect;
+    }
+    ensure_local_download(&state, &job_id, &path).await;
 
-        let file_name = job
-            .artifact
-            .as_ref()
-            .map(|a| a.file_name.clone())
-            .unwrap_or_else(|| format!("{job_id}.mv2"));
+    serve_file(
+        &method,
+        &headers,
+        &path,
+        &artifact_file_name,
+        "application/octet-stream",
+    )
+    .await
+}
 
-        (path.clone(), file_name, job.status.clone())
+pub async fn download_export_sidecar(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Query(signed): Query<SignedUrlQuery>,
+) -> impl IntoResponse {
+    let key = match authorize_download(
+        &state,
+        &headers,
+        &signed,
+        peer,
+        &job_id,
+        DownloadTarget::Sidecar,
+    )
+    .await
+    {
+        Ok(key) => key,
+        Err(response) => return response,
     };
 
-    if !matches!(status, JobState::Completed) {
+    let (path, artifact_file_name) =
+        match resolve_download_artifact(&state, key.as_ref(), &job_id).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+    ensure_local_download(&state, &job_id, &path).await;
+    let sidecar_path = sidecar_path_for_capsule(&path);
+    if !fs::try_exists(&sidecar_path).await.unwrap_or(false) {
         return (
-            StatusCode::CONFLICT,
+            StatusCode::NOT_FOUND,
             Json(json!({
                 "error": {
-                    "code": "ARTIFACT_NOT_READY",
-                    "message": "Export artifact is not ready for download."
+                    "code": "SIDECAR_NOT_FOUND",
+                    "message": "No sidecar index exists for this export."
                 }
             })),
         )
             .into_response();
     }
 
-    let bytes = match fs::read(&path).await {
-        Ok(bytes) => bytes,
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-            return (
-                StatusCode::GONE,
-                Json(json!({
-                    "error": {
-                        "code": "ARTIFACT_MISSING",
-                        "message": "Export artifact no longer exists."
-                    }
-                })),
-            )
-                .into_response();
-        }
+    info!(job_id = %job_id, method = %method, "Export sidecar download requested");
+
+    serve_file(
+        &method,
+        &headers,
+        &sidecar_path,
+        &format!("{artifact_file_name}.index.v1.sqlite"),
+        "application/vnd.sqlite3",
+    )
+    .await
+}
+
+pub async fn download_export_bundle(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+    Query(signed): Query<SignedUrlQuery>,
+) -> impl IntoResponse {
+    let key = match authorize_download(
+        &state,
+        &headers,
+        &signed,
+        peer,
+        &job_id,
+        DownloadTarget::Bundle,
+    )
+    .await
+    {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+
+    let (path, artifact_file_name) =
+        match resolve_download_artifact(&state, key.as_ref(), &job_id).await {
+            Ok(found) => found,
+            Err(response) => return response,
+        };
+
+    ensure_local_download(&state, &job_id, &path).await;
+    let bundle_path = match ensure_bundle(&path, &job_id).await {
+        Ok(bundle_path) => bundle_path,
         Err(err) => {
+            warn!(job_id = %job_id, "Failed to build export bundle: {err:#}");
             return (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(json!({
                     "error": {
-                        "code": "ARTIFACT_READ_FAILED",
-                        "message": format!("Failed to read artifact: {err}")
+                        "code": "BUNDLE_BUILD_FAILED",
+                        "message": format!("Failed to build export bundle: {err}")
                     }
                 })),
             )
@@ -530,30 +981,109 @@ pub async fn download_export(
         }
     };
 
-    let content_disposition = format!("attachment; filename=\"{artifact_file_name}\"");
+    info!(job_id = %job_id, method = %method, "Export bundle download requested");
 
-    info!(
-        job_id = %job_id,
-        artifact = %artifact_file_name,
-        bytes = bytes.len(),
-        "Export artifact downloaded"
-    );
+    serve_file(
+        &method,
+        &headers,
+        &bundle_path,
+        &bundle_file_name(&artifact_file_name),
+        "application/zip",
+    )
+    .await
+}
 
-    Response::builder()
-        .status(StatusCode::OK)
-        .header(header::CONTENT_TYPE, "application/octet-stream")
-        .header(header::CONTENT_DISPOSITION, content_disposition)
-        .body(Body::from(bytes))
-        .unwrap_or_else(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                    "error": {
-                        "code": "RESPONSE_BUILD_FAILED",
-                        "message": "Failed to build download response."
-                    }
-                })),
-            )
-                .into_response()
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::LEGACY_KEY_ID,
+        job_store::JobStore,
+        rate_limit::{MemoryRateLimitBackend, RateLimiter},
+        test_support::{job_record, sample_request, test_config, test_state, TempDir},
+    };
+    use std::sync::Arc;
+
+    fn admin(state: &AppState) -> ApiKey {
+        state.keys.get(LEGACY_KEY_ID).unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn quota_rejections_never_reach_the_job_store() {
+        let dir = TempDir::new("submit-quota");
+        let mut config = test_config(dir.path());
+        config.export_max_active_jobs_per_key = 1;
+        let (state, _rx) = test_state(config);
+        let key = admin(&state);
+
+        let mut active = job_record("active", JobState::Running);
+        active.owner_key_id = Some(key.id.clone());
+        state
+            .jobs
+            .write()
+            .await
+            .insert(active.job_id.clone(), active);
+
+        let response = submit_export(&state, sample_request(), &key).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(state.jobs.read().await.len(), 1);
+        assert!(state.job_store.load_all(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn invalid_requests_do_not_spend_rate_limit_tokens() {
+        let dir = TempDir::new("submit-rate");
+        let (mut state, mut rx) = test_state(test_config(dir.path()));
+        state.export_rate_limiter = Arc::new(RateLimiter::new(
+            "export",
+            1,
+            1,
+            Arc::new(MemoryRateLimitBackend::default()),
+        ));
+        let key = admin(&state);
+
+        let mut invalid = sample_request();
+        invalid.nodes.clear();
+        for _ in 0..3 {
+            let response = submit_export(&state, invalid.clone(), &key).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+        let response = submit_export(&state, sample_request(), &key).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        rx.recv().await.unwrap();
+
+        let response = submit_export(&state, sample_request(), &key).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn accepted_jobs_are_persisted_with_their_request() {
+        let dir = TempDir::new("submit-accepted");
+        let (state, mut rx) = test_state(test_config(dir.path()));
+
+        let response = submit_export(&state, sample_request(), &admin(&state)).await;
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        let job_id = rx.recv().await.unwrap();
+        assert!(state.jobs.read().await[&job_id].request.is_some());
+        let persisted = state.job_store.load_all(10).await.unwrap();
+        assert_eq!(persisted[0].job_id, job_id);
+        assert!(persisted[0].request.is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_persistence_rolls_back_the_accepted_job() {
+        let dir = TempDir::new("submit-rollback");
+        let (mut state, mut rx) = test_state(test_config(dir.path()));
+        let store_path = dir.path().join("jobs.sqlite");
+        state.job_store = JobStore::open(&store_path).unwrap();
+        rusqlite::Connection::open(&store_path)
+            .unwrap()
+            .execute_batch("DROP TABLE jobs;")
+            .unwrap();
+
+        let response = submit_export(&state, sample_request(), &admin(&state)).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(state.jobs.read().await.is_empty());
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -1,7 +1,97 @@
-use std::path::{Path, PathBuf};
+use std::{
+    future::Future,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
+use chrono::Utc;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use tokio::fs;
+use uuid::Uuid;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::mcp_index::sidecar_path_for_capsule;
+
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Durable home of staged payloads, capsules and sidecars.
+///
+/// Exports are always produced on local disk first, because memvid-core and
+/// SQLite need real files; the store holds the copy that downloads, restarts
+/// and other replicas rely on. Objects are addressed by their local path
+/// under the export or staging root.
+pub trait ArtifactStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Copies the local file at `path` into the store.
+    fn put<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, ()>;
+
+    /// Restores `path` from the store. Returns `false` when the store has no
+    /// such object.
+    fn fetch<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, bool>;
+
+    fn delete<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, ()>;
+
+    /// A URL that downloads `path` straight from the store for `ttl`, or
+    /// `None` when the backend cannot issue one and this API must serve it.
+    fn presigned_get(&self, path: &Path, file_name: &str, ttl: Duration) -> Result<Option<String>>;
+
+    /// A URL that uploads `path` straight into the store for `ttl`, so a
+    /// remote worker can return output without a shared volume.
+    fn presigned_put(&self, path: &Path, ttl: Duration) -> Result<Option<String>>;
+
+    /// Cheap connectivity check for `/readyz`.
+    fn probe(&self) -> StoreFuture<'_, ()>;
+}
+
+/// The local disk is the store: puts and fetches are no-ops and downloads
+/// are served by the API. Runpod workers share files through the volume.
+#[derive(Debug, Default)]
+pub struct FilesystemArtifactStore;
+
+impl ArtifactStore for FilesystemArtifactStore {
+    fn name(&self) -> &'static str {
+        "filesystem"
+    }
+
+    fn put<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            fs::metadata(path)
+                .await
+                .with_context(|| format!("Failed to stat {}", path.display()))?;
+            Ok(())
+        })
+    }
+
+    fn fetch<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, bool> {
+        Box::pin(async move { Ok(fs::try_exists(path).await.unwrap_or(false)) })
+    }
+
+    fn delete<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, ()> {
+        Box::pin(delete_file_if_exists(path))
+    }
+
+    fn presigned_get(
+        &self,
+        _path: &Path,
+        _file_name: &str,
+        _ttl: Duration,
+    ) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn presigned_put(&self, _path: &Path, _ttl: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    fn probe(&self) -> StoreFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+}
 
 pub async fn ensure_export_root(path: &Path) -> Result<()> {
     fs::create_dir_all(path)
@@ -33,3 +123,243 @@ pub async fn delete_file_if_exists(path: &Path) -> Result<()> {
         Err(err) => Err(err).with_context(|| format!("Failed to delete {}", path.display())),
     }
 }
+
+pub async fn delete_dir_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_dir_all(path).await {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).with_context(|| format!("Failed to delete {}", path.display())),
+    }
+}
+
+pub fn bundle_path_for_capsule(capsule_path: &Path) -> PathBuf {
+    let mut file_name = capsule_path
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_else(|| "capsule.mv2".to_string());
+    file_name.push_str(".bundle.zip");
+    capsule_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(file_name)
+}
+
+pub fn bundle_file_name(capsule_file_name: &str) -> String {
+    format!(
+        "{}.bundle.zip",
+        capsule_file_name
+            .strip_suffix(".mv2")
+            .unwrap_or(capsule_file_name)
+    )
+}
+
+/// Removes the capsule together with the files derived from it (sidecar
+/// index and download bundle), locally and from the store.
+pub async fn delete_artifact_set(store: &dyn ArtifactStore, capsule_path: &Path) -> Result<()> {
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    delete_file_if_exists(&sidecar_path).await?;
+    delete_file_if_exists(&bundle_path_for_capsule(capsule_path)).await?;
+    delete_file_if_exists(capsule_path).await?;
+    store.delete(&sidecar_path).await?;
+    store.delete(capsule_path).await
+}
+
+/// Copies a finished capsule and its sidecar index (when built) to the store.
+pub async fn publish_artifact_set(store: &dyn ArtifactStore, capsule_path: &Path) -> Result<()> {
+    store.put(capsule_path).await?;
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    if fs::try_exists(&sidecar_path).await.unwrap_or(false) {
+        store.put(&sidecar_path).await?;
+    }
+    Ok(())
+}
+
+/// Makes sure a capsule and its sidecar are on local disk, fetching them
+/// from the store when this instance does not have them (another replica
+/// wrote them, or the local copy was lost). Returns `false` when the capsule
+/// is in neither place.
+pub async fn ensure_local_artifact(store: &dyn ArtifactStore, capsule_path: &Path) -> Result<bool> {
+    if !fs::try_exists(capsule_path).await.unwrap_or(false) && !store.fetch(capsule_path).await? {
+        return Ok(false);
+    }
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    if !fs::try_exists(&sidecar_path).await.unwrap_or(false) {
+        store.fetch(&sidecar_path).await?;
+    }
+    Ok(true)
+}
+
+/// Returns the zip bundle for a capsule, building it on first request. The
+/// bundle holds the capsule, its sidecar index (when present) and a
+/// `manifest.json` with SHA-256 checksums for both.
+pub async fn ensure_bundle(capsule_path: &Path, job_id: &str) -> Result<PathBuf> {
+    let bundle_path = bundle_path_for_capsule(capsule_path);
+    let capsule_modified = fs::metadata(capsule_path)
+        .await
+        .with_context(|| format!("Failed to stat {}", capsule_path.display()))?
+        .modified()
+        .ok();
+    if let Ok(meta) = fs::metadata(&bundle_path).await {
+        if meta.modified().ok() >= capsule_modified {
+            return Ok(bundle_path);
+        }
+    }
+
+    let capsule_path = capsule_path.to_path_buf();
+    let job_id = job_id.to_string();
+    tokio::task::spawn_blocking(move || write_bundle(&capsule_path, &job_id))
+        .await
+        .context("Bundle writer task join error")?
+}
+
+fn write_bundle(capsule_path: &Path, job_id: &str) -> Result<PathBuf> {
+    let bundle_path = bundle_path_for_capsule(capsule_path);
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    let tmp_path = bundle_path.with_extension(format!("zip.{}.tmp", Uuid::new_v4()));
+
+    let mut entries = vec![(capsule_path.to_path_buf(), CompressionMethod::Stored)];
+    if sidecar_path.exists() {
+        entries.push((sidecar_path, CompressionMethod::Deflated));
+    }
+
+    let result = (|| -> Result<()> {
+        let file = std::fs::File::create(&tmp_path)
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        let mut zip = ZipWriter::new(std::io::BufWriter::new(file));
+        let mut manifest_files = Vec::new();
+
+        for (path, method) in entries {
+            let name = path
+                .file_name()
+                .map(|v| v.to_string_lossy().to_string())
+                .context("Bundle entry has no file name")?;
+            let size = std::fs::metadata(&path)
+                .with_context(|| format!("Failed to stat {}", path.display()))?
+                .len();
+            let options = FileOptions::default()
+                .compression_method(method)
+                .large_file(size >= u32::MAX as u64);
+            zip.start_file(name.as_str(), options)?;
+
+            let mut reader = std::fs::File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            let mut hasher = Sha256::new();
+            let mut buf = vec![0u8; 1024 * 1024];
+            loop {
+                let read = reader.read(&mut buf)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buf[..read]);
+                zip.write_all(&buf[..read])?;
+            }
+            manifest_files.push(json!({
+                "name": name,
+                "sizeBytes": size,
+                "sha256": hex::encode(hasher.finalize()),
+            }));
+        }
+
+        let manifest = json!({
+            "jobId": job_id,
+            "generatedAt": Utc::now(),
+            "files": manifest_files,
+        });
+        zip.start_file(
+            "manifest.json",
+            FileOptions::default().compression_method(CompressionMethod::Deflated),
+        )?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        zip.finish()?.flush()?;
+        Ok(())
+    })();
+
+    if let Err(err) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(err);
+    }
+    std::fs::rename(&tmp_path, &bundle_path)
+        .with_context(|| format!("Failed to finalize bundle {}", bundle_path.display()))?;
+    Ok(bundle_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn capsule_in(dir: &TempDir, with_sidecar: bool) -> PathBuf {
+        let capsule = dir.path().join("demo.mv2");
+        std::fs::write(&capsule, b"capsule-bytes").unwrap();
+        if with_sidecar {
+            std::fs::write(sidecar_path_for_capsule(&capsule), b"sidecar-bytes").unwrap();
+        }
+        capsule
+    }
+
+    fn manifest(bundle: &Path) -> (usize, serde_json::Value) {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(bundle).unwrap()).unwrap();
+        let mut manifest = String::new();
+        archive
+            .by_name("manifest.json")
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        (archive.len(), serde_json::from_str(&manifest).unwrap())
+    }
+
+    #[test]
+    fn bundle_contains_capsule_sidecar_and_checksums() {
+        let dir = TempDir::new("bundle");
+        let capsule = capsule_in(&dir, true);
+
+        let (entries, manifest) = manifest(&write_bundle(&capsule, "job-1").unwrap());
+        assert_eq!(entries, 3);
+        assert_eq!(manifest["jobId"], "job-1");
+        assert_eq!(manifest["files"][0]["name"], "demo.mv2");
+        assert_eq!(
+            manifest["files"][0]["sha256"],
+            hex::encode(Sha256::digest(b"capsule-bytes"))
+        );
+        assert_eq!(manifest["files"][1]["name"], "demo.mv2.index.v1.sqlite");
+    }
+
+    #[test]
+    fn bundle_without_sidecar_holds_capsule_and_manifest() {
+        let dir = TempDir::new("bundle-no-sidecar");
+        let capsule = capsule_in(&dir, false);
+
+        let (entries, manifest) = manifest(&write_bundle(&capsule, "job-1").unwrap());
+        assert_eq!(entries, 2);
+        assert_eq!(manifest["files"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_bundles_are_rebuilt() {
+        let dir = TempDir::new("bundle-stale");
+        let capsule = capsule_in(&dir, false);
+        let bundle = ensure_bundle(&capsule, "job-1").await.unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&bundle)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        std::fs::write(sidecar_path_for_capsule(&capsule), b"sidecar-bytes").unwrap();
+        let (entries, _) = manifest(&ensure_bundle(&capsule, "job-1").await.unwrap());
+        assert_eq!(entries, 3);
+    }
+
+    #[tokio::test]
+    async fn deleting_an_artifact_set_removes_derived_files() {
+        let dir = TempDir::new("bundle-delete");
+        let capsule = capsule_in(&dir, true);
+        ensure_bundle(&capsule, "job-1").await.unwrap();
+
+        delete_artifact_set(&FilesystemArtifactStore, &capsule)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
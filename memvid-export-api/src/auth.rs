@@ -1,20 +1,285 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
 use axum::{
     http::{header::AUTHORIZATION, HeaderMap, StatusCode},
     Json,
 };
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
 use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// Key id given to `MEMVID_EXPORT_API_KEY`, which always has `admin`.
+pub const LEGACY_KEY_ID: &str = "default";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "export:write")]
+    ExportWrite,
+    #[serde(rename = "export:read")]
+    ExportRead,
+    #[serde(rename = "mcp:read")]
+    McpRead,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "export:write" => Ok(Self::ExportWrite),
+            "export:read" => Ok(Self::ExportRead),
+            "mcp:read" => Ok(Self::McpRead),
+            "admin" => Ok(Self::Admin),
+            other => bail!(
+                "Unsupported API key scope `{other}`. Supported: export:write, export:read, mcp:read, admin."
+            ),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ExportWrite => "export:write",
+            Self::ExportRead => "export:read",
+            Self::McpRead => "mcp:read",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub label: String,
+    pub scopes: Vec<Scope>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_admin(&self) -> bool {
+        self.scopes.contains(&Scope::Admin)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.is_admin() || self.scopes.contains(&scope)
+    }
+
+    /// Non-admin keys only see jobs and uploads they created. Records from
+    /// before key ownership was tracked have no owner and are admin-only.
+    pub fn can_access(&self, owner_key_id: Option<&str>) -> bool {
+        self.is_admin() || owner_key_id == Some(self.id.as_str())
+    }
+}
+
+/// Bearer tokens accepted by the API, indexed by SHA-256 so raw tokens are
+/// never kept in memory past startup.
+#[derive(Debug, Default)]
+pub struct KeyRegistry {
+    by_token_hash: HashMap<String, ApiKey>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFile {
+    keys: Vec<KeyFileEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeyFileEntry {
+    id: String,
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    token_sha256: Option<String>,
+    scopes: Vec<Scope>,
+    #[serde(default)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl KeyRegistry {
+    /// Loads `MEMVID_EXPORT_API_KEYS_PATH` (JSON, or SQLite when the file
+    /// ends in `.sqlite`/`.db`) and adds the single configured API key as
+    /// the `default` admin key. A generated fallback key is only used when
+    /// no registry is configured.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut registry = Self::default();
+        if let Some(path) = &config.api_keys_path {
+            let is_sqlite = matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("sqlite" | "sqlite3" | "db")
+            );
+            let entries = if is_sqlite {
+                load_key_db(path)?
+            } else {
+                load_key_file(path)?
+            };
+            for (hash, key) in entries {
+                registry.insert(hash, key)?;
+            }
+        }
+
+        if !config.api_key_is_fallback || config.api_keys_path.is_none() {
+            registry.insert(
+                token_sha256(&config.api_key),
+                ApiKey {
+                    id: LEGACY_KEY_ID.to_string(),
+                    label: "MEMVID_EXPORT_API_KEY".to_string(),
+                    scopes: vec![Scope::Admin],
+                    expires_at: None,
+                },
+            )?;
+        }
+        Ok(registry)
+    }
+
+    fn insert(&mut self, token_hash: String, key: ApiKey) -> Result<()> {
+        if self.by_token_hash.values().any(|k| k.id == key.id) {
+            bail!("Duplicate API key id `{}`", key.id);
+        }
+        if self.by_token_hash.contains_key(&token_hash) {
+            bail!("API key `{}` reuses another key's token", key.id);
+        }
+        self.by_token_hash.insert(token_hash, key);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_token_hash.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_token_hash.is_empty()
+    }
+
+    pub fn lookup(&self, token: &str) -> Option<&ApiKey> {
+        self.by_token_hash.get(&token_sha256(token))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ApiKey> {
+        self.by_token_hash.values().find(|key| key.id == id)
+    }
+}
+
+pub fn token_sha256(token: &str) -> String {
+    hex::encode(Sha256::digest(token.trim().as_bytes()))
+}
+
+fn load_key_file(path: &Path) -> Result<Vec<(String, ApiKey)>> {
+    let raw = fs::read_to_string(path)
+        .with_context(|| format!("Failed reading API key file {}", path.display()))?;
+    let file: KeyFile = serde_json::from_str(&raw)
+        .with_context(|| format!("Invalid API key file {}", path.display()))?;
+
+    file.keys
+        .into_iter()
+        .map(|entry| {
+            let hash = match (entry.token, entry.token_sha256) {
+                (Some(token), None) => token_sha256(&token),
+                (None, Some(hash)) => hash.trim().to_ascii_lowercase(),
+                _ => bail!(
+                    "API key `{}` must set exactly one of token or tokenSha256",
+                    entry.id
+                ),
+            };
+            Ok((
+                hash,
+                ApiKey {
+                    label: entry.label.unwrap_or_else(|| entry.id.clone()),
+                    id: entry.id,
+                    scopes: entry.scopes,
+                    expires_at: entry.expires_at,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Reads `api_keys(id, label, token_sha256, scopes, expires_at)`, where
+/// `scopes` is comma or space separated and `expires_at` is RFC 3339 or NULL.
+fn load_key_db(path: &Path) -> Result<Vec<(String, ApiKey)>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed opening API key database {}", path.display()))?;
+    let mut stmt =
+        conn.prepare("SELECT id, label, token_sha256, scopes, expires_at FROM api_keys")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, Option<String>>(4)?,
+        ))
+    })?;
 
-pub fn verify_bearer(
+    let mut keys = Vec::new();
+    for row in rows {
+        let (id, label, hash, scopes, expires_at) = row?;
+        let scopes = scopes
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .map(Scope::parse)
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid scopes for API key `{id}`"))?;
+        let expires_at = expires_at
+            .map(|raw| {
+                DateTime::parse_from_rfc3339(&raw)
+                    .map(|v| v.with_timezone(&Utc))
+                    .with_context(|| format!("Invalid expires_at for API key `{id}`"))
+            })
+            .transpose()?;
+        keys.push((
+            hash.trim().to_ascii_lowercase(),
+            ApiKey {
+                label: label.unwrap_or_else(|| id.clone()),
+                id,
+                scopes,
+                expires_at,
+            },
+        ));
+    }
+    Ok(keys)
+}
+
+/// Resolves the bearer token to a key that is unexpired and holds `scope`.
+pub fn authorize(
+    keys: &KeyRegistry,
     headers: &HeaderMap,
-    expected_key: &str,
-) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    scope: Scope,
+) -> Result<ApiKey, (StatusCode, Json<serde_json::Value>)> {
     let token = extract_bearer_token(headers)?;
-
-    if token.trim() != expected_key {
+    let Some(key) = keys.lookup(&token) else {
         return Err(unauthorized("Invalid API key"));
+    };
+
+    if key.is_expired() {
+        return Err(unauthorized("API key has expired"));
     }
 
-    Ok(())
+    if !key.has_scope(scope) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({
+                "error": {
+                    "code": "FORBIDDEN",
+                    "message": format!("API key lacks the `{}` scope", scope.as_str())
+                }
+            })),
+        ));
+    }
+
+    Ok(key.clone())
 }
 
 pub fn extract_bearer_token(
@@ -46,3 +311,178 @@ fn unauthorized(message: &str) -> (StatusCode, Json<serde_json::Value>) {
         })),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, TempDir};
+    use axum::http::HeaderValue;
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}")).unwrap(),
+        );
+        headers
+    }
+
+    fn key_file(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("keys.json");
+        fs::write(
+            &path,
+            json!({
+                "keys": [
+                    { "id": "ci", "token": "ci-token", "scopes": ["export:write", "export:read"] },
+                    { "id": "agent", "tokenSha256": token_sha256("agent-token"), "scopes": ["mcp:read"] },
+                    { "id": "ops", "label": "Operations", "token": "ops-token", "scopes": ["admin"] },
+                    { "id": "old", "token": "old-token", "scopes": ["admin"], "expiresAt": "2001-01-01T00:00:00Z" }
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        path
+    }
+
+    fn registry(dir: &TempDir) -> KeyRegistry {
+        let mut registry = KeyRegistry::default();
+        for (hash, key) in load_key_file(&key_file(dir)).unwrap() {
+            registry.insert(hash, key).unwrap();
+        }
+        registry
+    }
+
+    fn status(result: Result<ApiKey, (StatusCode, Json<serde_json::Value>)>) -> StatusCode {
+        result.map(|_| StatusCode::OK).unwrap_or_else(|(s, _)| s)
+    }
+
+    #[test]
+    fn key_file_accepts_raw_and_hashed_tokens() {
+        let dir = TempDir::new("keys-file");
+        let registry = registry(&dir);
+        assert_eq!(registry.len(), 4);
+        assert_eq!(registry.lookup("ci-token").unwrap().id, "ci");
+        assert_eq!(registry.lookup(" agent-token ").unwrap().id, "agent");
+        assert_eq!(registry.get("ops").unwrap().label, "Operations");
+        assert_eq!(registry.get("ci").unwrap().label, "ci");
+    }
+
+    #[test]
+    fn scopes_gate_each_route_and_admin_holds_all() {
+        let dir = TempDir::new("keys-scopes");
+        let registry = registry(&dir);
+        let check = |token: &str, scope| status(authorize(&registry, &headers(token), scope));
+
+        assert_eq!(check("ci-token", Scope::ExportWrite), StatusCode::OK);
+        assert_eq!(check("ci-token", Scope::McpRead), StatusCode::FORBIDDEN);
+        assert_eq!(check("agent-token", Scope::McpRead), StatusCode::OK);
+        assert_eq!(
+            check("agent-token", Scope::ExportWrite),
+            StatusCode::FORBIDDEN
+        );
+        for scope in [
+            Scope::ExportWrite,
+            Scope::ExportRead,
+            Scope::McpRead,
+            Scope::Admin,
+        ] {
+            assert_eq!(check("ops-token", scope), StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn expired_unknown_and_malformed_credentials_are_unauthorized() {
+        let dir = TempDir::new("keys-expired");
+        let registry = registry(&dir);
+        let check = |headers: &HeaderMap| status(authorize(&registry, headers, Scope::McpRead));
+
+        assert_eq!(check(&headers("old-token")), StatusCode::UNAUTHORIZED);
+        assert_eq!(check(&headers("nope")), StatusCode::UNAUTHORIZED);
+        assert_eq!(check(&HeaderMap::new()), StatusCode::UNAUTHORIZED);
+        let mut basic = HeaderMap::new();
+        basic.insert(AUTHORIZATION, HeaderValue::from_static("Basic Y2k6eA=="));
+        assert_eq!(check(&basic), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn non_admin_keys_only_reach_their_own_records() {
+        let dir = TempDir::new("keys-owner");
+        let registry = registry(&dir);
+        let ci = registry.get("ci").unwrap();
+        assert!(ci.can_access(Some("ci")));
+        assert!(!ci.can_access(Some("agent")));
+        assert!(!ci.can_access(None));
+
+        let ops = registry.get("ops").unwrap();
+        assert!(ops.can_access(Some("ci")));
+        assert!(ops.can_access(None));
+    }
+
+    #[test]
+    fn key_database_parses_scope_lists_and_expiry() {
+        let dir = TempDir::new("keys-db");
+        let path = dir.path().join("keys.sqlite");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE api_keys (id TEXT, label TEXT, token_sha256 TEXT, scopes TEXT, expires_at TEXT);
+             INSERT INTO api_keys VALUES
+                ('ci', 'CI', '{}', 'export:write, export:read', '2999-01-01T00:00:00Z'),
+                ('agent', NULL, '{}', 'mcp:read', NULL);",
+            token_sha256("ci-token").to_ascii_uppercase(),
+            token_sha256("agent-token"),
+        ))
+        .unwrap();
+        drop(conn);
+
+        let keys: HashMap<String, ApiKey> = load_key_db(&path).unwrap().into_iter().collect();
+        let ci = &keys[&token_sha256("ci-token")];
+        assert_eq!(ci.label, "CI");
+        assert_eq!(ci.scopes, vec![Scope::ExportWrite, Scope::ExportRead]);
+        assert!(ci.expires_at.is_some() && !ci.is_expired());
+        assert_eq!(keys[&token_sha256("agent-token")].label, "agent");
+    }
+
+    #[test]
+    fn duplicate_ids_tokens_and_ambiguous_entries_are_rejected() {
+        let dir = TempDir::new("keys-duplicates");
+        let mut registry = registry(&dir);
+        let key = |id: &str| ApiKey {
+            id: id.to_string(),
+            label: id.to_string(),
+            scopes: vec![Scope::McpRead],
+            expires_at: None,
+        };
+        assert!(registry.insert(token_sha256("fresh"), key("ci")).is_err());
+        assert!(registry
+            .insert(token_sha256("ci-token"), key("new"))
+            .is_err());
+
+        let path = dir.path().join("ambiguous.json");
+        fs::write(
+            &path,
+            json!({ "keys": [{ "id": "x", "token": "t", "tokenSha256": "abc", "scopes": [] }] })
+                .to_string(),
+        )
+        .unwrap();
+        let err = load_key_file(&path).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("exactly one of token or tokenSha256"));
+    }
+
+    #[test]
+    fn configured_key_is_the_default_admin_unless_it_is_a_generated_fallback() {
+        let dir = TempDir::new("keys-config");
+        let mut config = test_config(dir.path());
+        config.api_keys_path = Some(key_file(&dir));
+        let registry = KeyRegistry::from_config(&config).unwrap();
+        assert_eq!(registry.lookup("test-key").unwrap().id, LEGACY_KEY_ID);
+        assert!(registry.get(LEGACY_KEY_ID).unwrap().is_admin());
+
+        config.api_key_is_fallback = true;
+        let registry = KeyRegistry::from_config(&config).unwrap();
+        assert!(registry.lookup("test-key").is_none());
+        assert_eq!(registry.len(), 4);
+    }
+}
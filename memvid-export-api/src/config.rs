@@ -1,9 +1,21 @@
-use std::{env, fs, net::SocketAddr, path::PathBuf};
+use std::{env, fs, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use anyhow::{bail, Result};
 use uuid::Uuid;
 
-use crate::embedding::{default_model_for_provider, EmbeddingRuntimeConfig};
+use crate::{
+    artifact_store::{ArtifactStore, FilesystemArtifactStore},
+    embedding::{
+        default_model_for_provider, EmbeddingModeKind, EmbeddingProviderKind,
+        EmbeddingRuntimeConfig,
+    },
+    embedding_cache::{EmbeddingCache, EMBEDDING_CACHE_FILE_NAME},
+    http_client::HttpClient,
+    rate_limit::{
+        MemoryRateLimitBackend, RateLimitBackend, SqliteRateLimitBackend, RATE_LIMIT_DB_FILE_NAME,
+    },
+    s3::{S3ArtifactStore, S3Config},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ExportBackendMode {
@@ -20,6 +32,36 @@ impl ExportBackendMode {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitBackendKind {
+    Memory,
+    Sqlite,
+}
+
+impl RateLimitBackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Memory => "memory",
+            Self::Sqlite => "sqlite",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArtifactStoreKind {
+    Filesystem,
+    S3,
+}
+
+impl ArtifactStoreKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Filesystem => "filesystem",
+            Self::S3 => "s3",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EmbeddingMode {
     ExternalApi,
@@ -40,15 +82,37 @@ pub struct Config {
     pub bind_addr: SocketAddr,
     pub api_key: String,
     pub api_key_is_fallback: bool,
+    pub api_keys_path: Option<PathBuf>,
+    pub download_signing_key: String,
+    pub signed_url_ttl_seconds: u64,
+    pub signed_url_max_ttl_seconds: u64,
+    pub trust_forwarded_for: bool,
     pub export_root: PathBuf,
+    /// Set at startup when the configured export root was not writable and a
+    /// fallback such as `/tmp` was used instead.
+    pub export_root_is_fallback: bool,
+    pub job_store_path: Option<PathBuf>,
     pub retention_seconds: u64,
+    pub shutdown_grace_seconds: u64,
     pub queue_capacity: usize,
+    pub worker_concurrency: usize,
+    pub local_write_concurrency: usize,
+    pub runpod_concurrency: usize,
     pub mcp_response_budget_bytes: usize,
     pub mcp_rate_limit_per_minute: u32,
     pub mcp_rate_limit_burst: u32,
     pub mcp_dev_log_payloads: bool,
     pub mcp_allow_external_capsules: bool,
     pub mcp_cache_capacity: usize,
+    pub mcp_index_cache_max_entries: usize,
+    pub mcp_index_cache_max_bytes: usize,
+    pub export_rate_limit_per_minute: u32,
+    pub export_rate_limit_burst: u32,
+    pub export_max_active_jobs_per_key: usize,
+    pub export_daily_frame_quota: u64,
+    pub export_daily_byte_quota: u64,
+    pub rate_limit_backend: RateLimitBackendKind,
+    pub rate_limit_db_path: Option<PathBuf>,
     pub backend_mode: ExportBackendMode,
     pub runpod_api_base: String,
     pub runpod_endpoint_id: Option<String>,
@@ -57,7 +121,14 @@ pub struct Config {
     pub runpod_poll_interval_seconds: u64,
     pub runpod_execution_timeout_ms: u64,
     pub runpod_ttl_ms: u64,
+    pub runpod_output_prefix: Option<String>,
     pub staging_root: PathBuf,
+    pub artifact_store: ArtifactStoreKind,
+    pub s3: Option<S3Config>,
+    pub s3_presign_ttl_seconds: u64,
+    pub s3_redirect_downloads: bool,
+    pub max_decoded_body_bytes: usize,
+    pub upload_ttl_seconds: u64,
     pub embedding_mode: EmbeddingMode,
     pub embedding_provider: String,
     pub embedding_model: String,
@@ -72,6 +143,15 @@ pub struct Config {
     pub voyage_output_dtype: String,
     pub voyage_truncation: bool,
     pub embed_request_timeout_seconds: u64,
+    pub embed_batch_size: usize,
+    pub embed_max_retries: u32,
+    pub embed_max_concurrency: usize,
+    pub embed_cache_path: Option<PathBuf>,
+    pub embed_cache_max_bytes: u64,
+    pub embed_cache_max_age_seconds: u64,
+    pub http_timeout_seconds: u64,
+    pub http_connect_timeout_seconds: u64,
+    pub http_ca_bundle_path: Option<PathBuf>,
     pub ollama_host: Option<String>,
 }
 
@@ -89,21 +169,80 @@ impl Config {
             .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 8080)));
 
         let (api_key, api_key_is_fallback) = resolve_api_key();
+        let api_keys_path = env::var("MEMVID_EXPORT_API_KEYS_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        // Rotating the signing key revokes every outstanding signed URL.
+        let download_signing_key = env::var("MEMVID_DOWNLOAD_SIGNING_KEY")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| api_key.clone());
+        let signed_url_max_ttl_seconds = env::var("MEMVID_SIGNED_URL_MAX_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(7 * 24 * 60 * 60)
+            .max(1);
+        let signed_url_ttl_seconds = env::var("MEMVID_SIGNED_URL_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60 * 60)
+            .clamp(1, signed_url_max_ttl_seconds);
+        let trust_forwarded_for = env::var("MEMVID_TRUST_FORWARDED_FOR")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "on"
+                )
+            })
+            .unwrap_or(false);
 
         let export_root = PathBuf::from(
             env::var("MEMVID_EXPORT_ROOT").unwrap_or_else(|_| "/data/exports".to_string()),
         );
 
+        let job_store_path = env::var("MEMVID_EXPORT_JOB_STORE_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
         let retention_seconds = env::var("MEMVID_EXPORT_RETENTION_SECONDS")
             .ok()
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(24 * 60 * 60);
 
+        let shutdown_grace_seconds = env::var("MEMVID_SHUTDOWN_GRACE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(25);
+
         let queue_capacity = env::var("MEMVID_EXPORT_QUEUE_CAPACITY")
             .ok()
             .and_then(|v| v.parse::<usize>().ok())
             .unwrap_or(128);
 
+        let worker_concurrency = env::var("MEMVID_EXPORT_WORKER_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(16)
+            .max(1);
+
+        let local_write_concurrency = env::var("MEMVID_EXPORT_LOCAL_WRITE_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(2)
+            .max(1);
+
+        let runpod_concurrency = env::var("MEMVID_EXPORT_RUNPOD_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(16)
+            .max(1);
+
         let mcp_response_budget_bytes = env::var("MEMVID_MCP_RESPONSE_BUDGET_BYTES")
             .ok()
             .and_then(|v| v.parse::<usize>().ok())
@@ -144,6 +283,51 @@ impl Config {
             .and_then(|v| v.parse::<usize>().ok())
             .unwrap_or(256);
 
+        let mcp_index_cache_max_entries = env::var("MEMVID_MCP_INDEX_CACHE_MAX_ENTRIES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(64)
+            .max(1);
+
+        let mcp_index_cache_max_bytes = env::var("MEMVID_MCP_INDEX_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1024 * 1024 * 1024);
+        let export_rate_limit_per_minute = env::var("MEMVID_EXPORT_RATE_LIMIT_PER_MINUTE")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(30);
+        let export_rate_limit_burst = env::var("MEMVID_EXPORT_RATE_LIMIT_BURST")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(10);
+        let export_max_active_jobs_per_key = env::var("MEMVID_EXPORT_MAX_ACTIVE_JOBS_PER_KEY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(16);
+        let export_daily_frame_quota = env::var("MEMVID_EXPORT_DAILY_FRAME_QUOTA")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        let export_daily_byte_quota = env::var("MEMVID_EXPORT_DAILY_BYTE_QUOTA")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        let rate_limit_backend = match env::var("MEMVID_RATE_LIMIT_BACKEND")
+            .unwrap_or_else(|_| "memory".to_string())
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "sqlite" => RateLimitBackendKind::Sqlite,
+            _ => RateLimitBackendKind::Memory,
+        };
+        let rate_limit_db_path = env::var("MEMVID_RATE_LIMIT_DB_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
         let backend_mode = match env::var("MEMVID_EXPORT_BACKEND_MODE")
             .unwrap_or_else(|_| "legacy_vps".to_string())
             .trim()
@@ -185,10 +369,55 @@ impl Config {
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(86_400_000)
             .max(10_000);
+        let runpod_output_prefix = env::var("RUNPOD_OUTPUT_PREFIX")
+            .ok()
+            .map(|v| v.trim().trim_end_matches('/').to_string())
+            .filter(|v| !v.is_empty());
+        if let Some(prefix) = &runpod_output_prefix {
+            if !(prefix.starts_with("http://") || prefix.starts_with("https://")) {
+                bail!("RUNPOD_OUTPUT_PREFIX must be an http(s) URL, got {prefix}");
+            }
+        }
         let staging_root = PathBuf::from(
             env::var("MEMVID_EXPORT_STAGING_ROOT")
                 .unwrap_or_else(|_| "/data/exports/staging".to_string()),
         );
+        let artifact_store = match env::var("MEMVID_ARTIFACT_STORE")
+            .unwrap_or_else(|_| "filesystem".to_string())
+            .trim()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "s3" => ArtifactStoreKind::S3,
+            _ => ArtifactStoreKind::Filesystem,
+        };
+        let s3 = match artifact_store {
+            ArtifactStoreKind::Filesystem => None,
+            ArtifactStoreKind::S3 => Some(s3_config_from_env()?),
+        };
+        let s3_presign_ttl_seconds = env::var("MEMVID_S3_PRESIGN_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(300)
+            .max(1);
+        let s3_redirect_downloads = env::var("MEMVID_S3_REDIRECT_DOWNLOADS")
+            .ok()
+            .map(|v| {
+                matches!(
+                    v.trim().to_ascii_lowercase().as_str(),
+                    "1" | "true" | "yes" | "on"
+                )
+            })
+            .unwrap_or(true);
+        let max_decoded_body_bytes = env::var("MEMVID_EXPORT_MAX_DECODED_BODY_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(2 * 1024 * 1024 * 1024);
+        let upload_ttl_seconds = env::var("MEMVID_EXPORT_UPLOAD_TTL_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(6 * 60 * 60)
+            .max(60);
 
         let embedding_mode = match env::var("MEMVID_EMBEDDING_MODE")
             .unwrap_or_else(|_| "external_api".to_string())
@@ -277,6 +506,48 @@ impl Config {
             .and_then(|v| v.parse::<u64>().ok())
             .unwrap_or(60)
             .max(1);
+        let embed_batch_size = env::var("MEMVID_EMBED_BATCH_SIZE")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(64)
+            .max(1);
+        let embed_max_retries = env::var("MEMVID_EMBED_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(5);
+        let embed_max_concurrency = env::var("MEMVID_EMBED_MAX_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(4)
+            .max(1);
+        let embed_cache_path = env::var("MEMVID_EMBED_CACHE_PATH")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        let embed_cache_max_bytes = env::var("MEMVID_EMBED_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(2 * 1024 * 1024 * 1024);
+        let embed_cache_max_age_seconds = env::var("MEMVID_EMBED_CACHE_MAX_AGE_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(30 * 24 * 60 * 60);
+        let http_timeout_seconds = env::var("MEMVID_HTTP_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(60)
+            .max(1);
+        let http_connect_timeout_seconds = env::var("MEMVID_HTTP_CONNECT_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(10)
+            .max(1);
+        let http_ca_bundle_path = env::var("MEMVID_HTTP_CA_BUNDLE")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
         let ollama_host = env::var("OLLAMA_HOST")
             .ok()
             .map(|v| v.trim().to_string())
@@ -286,15 +557,35 @@ impl Config {
             bind_addr,
             api_key,
             api_key_is_fallback,
+            api_keys_path,
+            download_signing_key,
+            signed_url_ttl_seconds,
+            signed_url_max_ttl_seconds,
+            trust_forwarded_for,
             export_root,
+            export_root_is_fallback: false,
+            job_store_path,
             retention_seconds,
+            shutdown_grace_seconds,
             queue_capacity,
+            worker_concurrency,
+            local_write_concurrency,
+            runpod_concurrency,
             mcp_response_budget_bytes,
             mcp_rate_limit_per_minute,
             mcp_rate_limit_burst,
             mcp_dev_log_payloads,
             mcp_allow_external_capsules,
             mcp_cache_capacity,
+            mcp_index_cache_max_entries,
+            mcp_index_cache_max_bytes,
+            export_rate_limit_per_minute,
+            export_rate_limit_burst,
+            export_max_active_jobs_per_key,
+            export_daily_frame_quota,
+            export_daily_byte_quota,
+            rate_limit_backend,
+            rate_limit_db_path,
             backend_mode,
             runpod_api_base,
             runpod_endpoint_id,
@@ -303,7 +594,14 @@ impl Config {
             runpod_poll_interval_seconds,
             runpod_execution_timeout_ms,
             runpod_ttl_ms,
+            runpod_output_prefix,
             staging_root,
+            artifact_store,
+            s3,
+            s3_presign_ttl_seconds,
+            s3_redirect_downloads,
+            max_decoded_body_bytes,
+            upload_ttl_seconds,
             embedding_mode,
             embedding_provider,
             embedding_model,
@@ -318,6 +616,15 @@ impl Config {
             voyage_output_dtype,
             voyage_truncation,
             embed_request_timeout_seconds,
+            embed_batch_size,
+            embed_max_retries,
+            embed_max_concurrency,
+            embed_cache_path,
+            embed_cache_max_bytes,
+            embed_cache_max_age_seconds,
+            http_timeout_seconds,
+            http_connect_timeout_seconds,
+            http_ca_bundle_path,
             ollama_host,
         })
     }
@@ -328,7 +635,52 @@ impl Config {
             && self.runpod_api_key.is_some()
     }
 
-    pub fn embedding_runtime_config(&self) -> Result<EmbeddingRuntimeConfig> {
+    /// Opens the shared embedding cache, or `None` when it is disabled
+    /// (`MEMVID_EMBED_CACHE_MAX_BYTES=0`).
+    pub fn open_embedding_cache(&self) -> Result<Option<EmbeddingCache>> {
+        if self.embed_cache_max_bytes == 0 {
+            return Ok(None);
+        }
+        let path = self
+            .embed_cache_path
+            .clone()
+            .unwrap_or_else(|| self.export_root.join(EMBEDDING_CACHE_FILE_NAME));
+        EmbeddingCache::open(
+            &path,
+            self.embed_cache_max_bytes,
+            Duration::from_secs(self.embed_cache_max_age_seconds),
+        )
+        .map(Some)
+    }
+
+    pub fn open_rate_limit_backend(&self) -> Result<Arc<dyn RateLimitBackend>> {
+        Ok(match self.rate_limit_backend {
+            RateLimitBackendKind::Memory => Arc::new(MemoryRateLimitBackend::default()),
+            RateLimitBackendKind::Sqlite => {
+                let path = self
+                    .rate_limit_db_path
+                    .clone()
+                    .unwrap_or_else(|| self.export_root.join(RATE_LIMIT_DB_FILE_NAME));
+                Arc::new(SqliteRateLimitBackend::open(&path)?)
+            }
+        })
+    }
+
+    /// Opens the artifact store. Call after the export root is final, since
+    /// S3 keys are derived from paths under it.
+    pub fn open_artifact_store(&self, http: HttpClient) -> Result<Arc<dyn ArtifactStore>> {
+        Ok(match &self.s3 {
+            None => Arc::new(FilesystemArtifactStore),
+            Some(s3) => Arc::new(S3ArtifactStore::new(
+                http,
+                s3.clone(),
+                self.export_root.clone(),
+                self.staging_root.clone(),
+            )?),
+        })
+    }
+
+    pub fn embedding_runtime_config(&self, http: HttpClient) -> Result<EmbeddingRuntimeConfig> {
         EmbeddingRuntimeConfig::new(
             self.embedding_mode.as_str(),
             &self.embedding_provider,
@@ -345,10 +697,90 @@ impl Config {
             self.voyage_output_dtype.clone(),
             self.voyage_truncation,
             self.embed_request_timeout_seconds,
+            self.embed_batch_size,
+            self.embed_max_retries,
+            self.embed_max_concurrency,
+            http,
+        )
+    }
+
+    /// Runtime for embedding search queries against a capsule, using the
+    /// provider and model recorded in the capsule rather than the configured
+    /// export defaults. Retries are capped so a flaky provider cannot stall
+    /// an MCP call.
+    pub fn query_embedding_config(
+        &self,
+        http: HttpClient,
+        provider: &str,
+        model: &str,
+        dimension: usize,
+    ) -> Result<EmbeddingRuntimeConfig> {
+        let provider_kind = EmbeddingProviderKind::parse(provider)?;
+        let mode = if provider_kind == EmbeddingProviderKind::Ollama {
+            EmbeddingModeKind::RunpodGpu
+        } else {
+            EmbeddingModeKind::ExternalApi
+        };
+        let voyage_output_dimension = if provider_kind == EmbeddingProviderKind::Voyage {
+            u16::try_from(dimension).ok()
+        } else {
+            None
+        };
+        EmbeddingRuntimeConfig::new(
+            mode.as_str(),
+            provider,
+            model,
+            self.nvidia_api_key.clone(),
+            self.openai_api_key.clone(),
+            self.voyage_api_key.clone(),
+            self.ollama_host.clone(),
+            self.nvidia_embed_base_url.clone(),
+            self.openai_embed_base_url.clone(),
+            self.voyage_embed_base_url.clone(),
+            "query".to_string(),
+            voyage_output_dimension,
+            "float".to_string(),
+            self.voyage_truncation,
+            self.embed_request_timeout_seconds,
+            1,
+            self.embed_max_retries.min(2),
+            1,
+            http,
         )
     }
 }
 
+fn s3_config_from_env() -> Result<S3Config> {
+    let var = |name: &str| {
+        env::var(name)
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let region = var("MEMVID_S3_REGION").unwrap_or_else(|| "us-east-1".to_string());
+    let Some(bucket) = var("MEMVID_S3_BUCKET") else {
+        bail!("MEMVID_S3_BUCKET must be set when MEMVID_ARTIFACT_STORE=s3");
+    };
+    let (Some(access_key_id), Some(secret_access_key)) = (
+        var("MEMVID_S3_ACCESS_KEY_ID").or_else(|| var("AWS_ACCESS_KEY_ID")),
+        var("MEMVID_S3_SECRET_ACCESS_KEY").or_else(|| var("AWS_SECRET_ACCESS_KEY")),
+    ) else {
+        bail!("MEMVID_S3_ACCESS_KEY_ID and MEMVID_S3_SECRET_ACCESS_KEY must be set when MEMVID_ARTIFACT_STORE=s3");
+    };
+    Ok(S3Config {
+        endpoint: var("MEMVID_S3_ENDPOINT")
+            .unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com")),
+        region,
+        bucket,
+        access_key_id,
+        secret_access_key,
+        prefix: var("MEMVID_S3_PREFIX").unwrap_or_default(),
+        path_style: var("MEMVID_S3_PATH_STYLE")
+            .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(true),
+    })
+}
+
 fn resolve_api_key() -> (String, bool) {
     if let Ok(value) = env::var("MEMVID_EXPORT_API_KEY") {
         let trimmed = value.trim();
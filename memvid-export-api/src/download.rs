@@ -0,0 +1,362 @@
+use std::{path::Path, time::SystemTime};
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, Response, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, SeekFrom},
+};
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+/// Parses a single `bytes=` range against a file of `size` bytes. Multi-range
+/// and malformed headers fall back to the full body, as RFC 9110 allows.
+pub fn parse_range(raw: &str, size: u64) -> ByteRange {
+    let Some(spec) = raw.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start_raw, end_raw)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start_raw, end_raw) = (start_raw.trim(), end_raw.trim());
+
+    if start_raw.is_empty() {
+        let Ok(suffix) = end_raw.parse::<u64>() else {
+            return ByteRange::Full;
+        };
+        if suffix == 0 || size == 0 {
+            return ByteRange::Unsatisfiable;
+        }
+        return ByteRange::Partial {
+            start: size.saturating_sub(suffix),
+            end: size - 1,
+        };
+    }
+
+    let Ok(start) = start_raw.parse::<u64>() else {
+        return ByteRange::Full;
+    };
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    let end = if end_raw.is_empty() {
+        size - 1
+    } else {
+        match end_raw.parse::<u64>() {
+            Ok(end) if end >= start => end.min(size - 1),
+            _ => return ByteRange::Full,
+        }
+    };
+    ByteRange::Partial { start, end }
+}
+
+fn entity_tag(size: u64, modified: Option<SystemTime>) -> String {
+    let mtime = modified
+        .and_then(|m| m.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("\"{size:x}-{mtime:x}\"")
+}
+
+fn etag_matches(raw: &str, etag: &str) -> bool {
+    raw.split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// `If-Range` only allows the partial response when the validator still
+/// matches the current artifact; otherwise the whole file is sent.
+fn if_range_allows(headers: &HeaderMap, etag: &str, last_modified: Option<&str>) -> bool {
+    let Some(raw) = headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    let raw = raw.trim();
+    if raw.starts_with('"') {
+        return raw == etag;
+    }
+    last_modified.is_some_and(|lm| lm == raw)
+}
+
+fn download_error(status: StatusCode, code: &str, message: String) -> Response<Body> {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "code": code,
+                "message": message
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// Streams `path` from disk with `Range`/`If-Range`, `ETag`/`Last-Modified`
+/// and `HEAD` support.
+pub async fn serve_file(
+    method: &Method,
+    headers: &HeaderMap,
+    path: &Path,
+    file_name: &str,
+    content_type: &str,
+) -> Response<Body> {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return download_error(
+                StatusCode::GONE,
+                "ARTIFACT_MISSING",
+                "Export artifact no longer exists.".to_string(),
+            );
+        }
+        Err(err) => {
+            return download_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "ARTIFACT_READ_FAILED",
+                format!("Failed to read artifact: {err}"),
+            );
+        }
+    };
+    let metadata = match file.metadata().await {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            return download_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "ARTIFACT_READ_FAILED",
+                format!("Failed to read artifact: {err}"),
+            );
+        }
+    };
+
+    let size = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = entity_tag(size, modified);
+    let last_modified = modified.map(httpdate::fmt_http_date);
+
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &etag);
+    if let Some(last_modified) = &last_modified {
+        builder = builder.header(header::LAST_MODIFIED, last_modified);
+    }
+
+    if headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|raw| etag_matches(raw, &etag))
+    {
+        return finish(builder.status(StatusCode::NOT_MODIFIED), Body::empty());
+    }
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| if_range_allows(headers, &etag, last_modified.as_deref()))
+        .map(|raw| parse_range(raw, size))
+        .unwrap_or(ByteRange::Full);
+
+    let (status, start, len) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, size),
+        ByteRange::Partial { start, end } => {
+            builder = builder.header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{size}"));
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            return finish(
+                builder
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{size}")),
+                Body::empty(),
+            );
+        }
+    };
+
+    builder = builder
+        .status(status)
+        .header(header::CONTENT_LENGTH, HeaderValue::from(len));
+
+    if method == Method::HEAD {
+        return finish(builder, Body::empty());
+    }
+
+    if start > 0 {
+        if let Err(err) = file.seek(SeekFrom::Start(start)).await {
+            return download_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "ARTIFACT_READ_FAILED",
+                format!("Failed to read artifact: {err}"),
+            );
+        }
+    }
+
+    finish(
+        builder,
+        Body::from_stream(ReaderStream::new(file.take(len))),
+    )
+}
+
+fn finish(builder: axum::http::response::Builder, body: Body) -> Response<Body> {
+    builder.body(body).unwrap_or_else(|_| {
+        download_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "RESPONSE_BUILD_FAILED",
+            "Failed to build download response.".to_string(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    async fn artifact(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("demo.mv2");
+        tokio::fs::write(&path, b"0123456789").await.unwrap();
+        path
+    }
+
+    async fn get(path: &Path, headers: &[(header::HeaderName, &str)]) -> Response<Body> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        serve_file(
+            &Method::GET,
+            &map,
+            path,
+            "demo.mv2",
+            "application/octet-stream",
+        )
+        .await
+    }
+
+    async fn body_bytes(response: Response<Body>) -> Vec<u8> {
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+            .to_vec()
+    }
+
+    #[tokio::test]
+    async fn range_requests_stream_only_the_requested_bytes() {
+        let dir = TempDir::new("download-range");
+        let path = artifact(&dir).await;
+
+        let response = get(&path, &[(header::RANGE, "bytes=2-5")]).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "4");
+        assert_eq!(body_bytes(response).await, b"2345");
+
+        let response = get(&path, &[(header::RANGE, "bytes=20-")]).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn validators_short_circuit_or_reset_ranges() {
+        let dir = TempDir::new("download-validators");
+        let path = artifact(&dir).await;
+        let etag = get(&path, &[]).await.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let response = get(&path, &[(header::IF_NONE_MATCH, &etag)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(body_bytes(response).await.is_empty());
+
+        let response = get(
+            &path,
+            &[(header::RANGE, "bytes=0-1"), (header::IF_RANGE, &etag)],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+        let response = get(
+            &path,
+            &[
+                (header::RANGE, "bytes=0-1"),
+                (header::IF_RANGE, "\"stale\""),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_bytes(response).await, b"0123456789");
+    }
+
+    #[tokio::test]
+    async fn head_reports_the_length_without_a_body() {
+        let dir = TempDir::new("download-head");
+        let path = artifact(&dir).await;
+        let response = serve_file(
+            &Method::HEAD,
+            &HeaderMap::new(),
+            &path,
+            "demo.mv2",
+            "application/octet-stream",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "10");
+        assert!(body_bytes(response).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_artifacts_are_gone() {
+        let dir = TempDir::new("download-missing");
+        let response = get(&dir.path().join("gone.mv2"), &[]).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+    }
+
+    #[test]
+    fn parse_range_handles_open_suffix_and_bounds() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse_range("bytes=900-", 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse_range("bytes=500-5000", 1000),
+            ByteRange::Partial {
+                start: 500,
+                end: 999
+            }
+        );
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-1", 1000), ByteRange::Full);
+    }
+}
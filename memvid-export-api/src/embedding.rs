@@ -1,7 +1,25 @@
-use std::process::Command;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 
 use anyhow::{bail, Context, Result};
+use reqwest::Method;
 use serde_json::{json, Value};
+use tokio::{sync::Semaphore, task::JoinSet};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{
+    embedding_cache::{EmbeddingCache, EmbeddingIdentity},
+    http_client::HttpClient,
+    metrics::metrics,
+};
+
+const RETRY_BASE_DELAY_MS: u64 = 500;
+const RETRY_MAX_DELAY_MS: u64 = 30_000;
+const RETRY_AFTER_MAX_SECONDS: u64 = 120;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EmbeddingModeKind {
@@ -85,6 +103,11 @@ pub struct EmbeddingRuntimeConfig {
     pub voyage_output_dtype: String,
     pub voyage_truncation: bool,
     pub request_timeout_seconds: u64,
+    pub batch_size: usize,
+    pub max_retries: u32,
+    pub max_concurrency: usize,
+    pub http: HttpClient,
+    pub cache: Option<EmbeddingCache>,
 }
 
 impl EmbeddingRuntimeConfig {
@@ -105,6 +128,10 @@ impl EmbeddingRuntimeConfig {
         voyage_output_dtype: String,
         voyage_truncation: bool,
         request_timeout_seconds: u64,
+        batch_size: usize,
+        max_retries: u32,
+        max_concurrency: usize,
+        http: HttpClient,
     ) -> Result<Self> {
         let mode = EmbeddingModeKind::parse(mode)?;
         let provider = EmbeddingProviderKind::parse(provider)?;
@@ -129,45 +156,165 @@ impl EmbeddingRuntimeConfig {
             voyage_output_dtype: voyage_output_dtype.trim().to_ascii_lowercase(),
             voyage_truncation,
             request_timeout_seconds: request_timeout_seconds.max(1),
+            batch_size: batch_size.max(1),
+            max_retries,
+            max_concurrency: max_concurrency.max(1),
+            http,
+            cache: None,
         };
         config.validate_runtime_requirements()?;
         Ok(config)
     }
 
+    pub fn with_cache(mut self, cache: Option<EmbeddingCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Everything that determines the vector for a given text.
+    pub fn identity(&self) -> EmbeddingIdentity {
+        let voyage = self.provider == EmbeddingProviderKind::Voyage;
+        EmbeddingIdentity {
+            provider: self.provider.as_str().to_string(),
+            model: self.model.clone(),
+            dimension: self.voyage_output_dimension.filter(|_| voyage),
+            dtype: if voyage {
+                self.voyage_output_dtype.clone()
+            } else {
+                "float".to_string()
+            },
+            input_type: Some(self.voyage_input_type.clone()).filter(|_| voyage),
+            truncation: Some(self.voyage_truncation).filter(|_| voyage),
+        }
+    }
+
     pub fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed_texts(&[text])?
+            .pop()
+            .context("Embedding provider returned no vectors")
+    }
+
+    /// Number of texts worth collecting before calling `embed_texts`, so every
+    /// allowed concurrent request gets a full batch.
+    pub fn chunk_len(&self) -> usize {
+        self.batch_size * self.max_concurrency
+    }
+
+    /// Embeds `texts` in provider-sized batches, returning vectors in input
+    /// order. Callable from blocking code (the capsule writer); must not be
+    /// called from an async task.
+    pub fn embed_texts(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle.block_on(self.embed_texts_async(texts)),
+            Err(_) => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .context("Failed to start embedding runtime")?
+                .block_on(self.embed_texts_async(texts)),
+        }
+    }
+
+    /// Batches run concurrently up to `max_concurrency`, and every request
+    /// also takes a slot from the process-wide limit for the provider.
+    pub async fn embed_texts_async(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
         self.validate_runtime_requirements()?;
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let limiter = provider_limiter(self.provider, self.max_concurrency);
+        let mut tasks = JoinSet::new();
+        for (batch_idx, batch) in texts.chunks(self.batch_size).enumerate() {
+            let runtime = self.clone();
+            let limiter = Arc::clone(&limiter);
+            let batch: Vec<String> = batch.iter().map(|text| text.to_string()).collect();
+            tasks.spawn(async move {
+                let _slot = limiter
+                    .acquire_owned()
+                    .await
+                    .context("Embedding limiter closed")?;
+                let refs: Vec<&str> = batch.iter().map(String::as_str).collect();
+                runtime
+                    .embed_batch(&refs)
+                    .await
+                    .map(|embeddings| (batch_idx, embeddings))
+            });
+        }
+
+        let mut batches = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            batches.push(joined.context("Embedding task join error")??);
+        }
+        batches.sort_by_key(|(batch_idx, _)| *batch_idx);
+
+        let embeddings: Vec<Vec<f32>> = batches
+            .into_iter()
+            .flat_map(|(_, embeddings)| embeddings)
+            .collect();
+        if embeddings.len() != texts.len() {
+            bail!(
+                "Embedding provider returned {} vectors for {} inputs",
+                embeddings.len(),
+                texts.len()
+            );
+        }
+        Ok(embeddings)
+    }
+
+    async fn embed_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let started = Instant::now();
+        let result = self.request_batch(texts).await;
+        metrics().observe_embedding(self.provider.as_str(), started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn request_batch(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
         match self.provider {
-            EmbeddingProviderKind::Nvidia => self.embed_openai_compatible(
-                format!("{}/embeddings", self.nvidia_embed_base_url),
-                self.nvidia_api_key
-                    .as_deref()
-                    .context("NVIDIA_API_KEY is required for provider=nvidia")?,
-                text,
-            ),
-            EmbeddingProviderKind::Openai => self.embed_openai_compatible(
-                format!("{}/embeddings", self.openai_embed_base_url),
-                self.openai_api_key
-                    .as_deref()
-                    .context("OPENAI_API_KEY is required for provider=openai")?,
-                text,
-            ),
-            EmbeddingProviderKind::Voyage => self.embed_voyage(
-                format!("{}/embeddings", self.voyage_embed_base_url),
-                self.voyage_api_key
-                    .as_deref()
-                    .context("VOYAGE_API_KEY is required for provider=voyage/voyageai")?,
-                text,
-            ),
-            EmbeddingProviderKind::Ollama => self.embed_ollama(
-                format!(
+            EmbeddingProviderKind::Nvidia => {
+                self.embed_openai_compatible(
+                    format!("{}/embeddings", self.nvidia_embed_base_url),
+                    self.nvidia_api_key
+                        .as_deref()
+                        .context("NVIDIA_API_KEY is required for provider=nvidia")?,
+                    texts,
+                )
+                .await
+            }
+            EmbeddingProviderKind::Openai => {
+                self.embed_openai_compatible(
+                    format!("{}/embeddings", self.openai_embed_base_url),
+                    self.openai_api_key
+                        .as_deref()
+                        .context("OPENAI_API_KEY is required for provider=openai")?,
+                    texts,
+                )
+                .await
+            }
+            EmbeddingProviderKind::Voyage => {
+                self.embed_voyage(
+                    format!("{}/embeddings", self.voyage_embed_base_url),
+                    self.voyage_api_key
+                        .as_deref()
+                        .context("VOYAGE_API_KEY is required for provider=voyage/voyageai")?,
+                    texts,
+                )
+                .await
+            }
+            EmbeddingProviderKind::Ollama => {
+                // `/api/embeddings` takes a single prompt per request.
+                let url = format!(
                     "{}/api/embeddings",
                     self.ollama_host
                         .as_deref()
                         .context("OLLAMA_HOST is required for provider=ollama/local")?
                         .trim_end_matches('/')
-                ),
-                text,
-            ),
+                );
+                let mut embeddings = Vec::with_capacity(texts.len());
+                for text in texts {
+                    embeddings.push(self.embed_ollama(&url, text).await?);
+                }
+                Ok(embeddings)
+            }
         }
     }
 
@@ -267,34 +414,40 @@ impl EmbeddingRuntimeConfig {
         Ok(())
     }
 
-    fn embed_openai_compatible(&self, url: String, api_key: &str, text: &str) -> Result<Vec<f32>> {
+    async fn embed_openai_compatible(
+        &self,
+        url: String,
+        api_key: &str,
+        texts: &[&str],
+    ) -> Result<Vec<Vec<f32>>> {
         let body = json!({
             "model": self.model,
-            "input": [text]
+            "input": texts
         });
-        let response = execute_curl_json(
-            &url,
-            Some(format!("Authorization: Bearer {}", api_key)),
-            self.request_timeout_seconds,
-            body.to_string(),
-        )?;
-        extract_embedding_from_response(&response, &url)
+        let response = self
+            .post_json_with_retry(&url, Some(api_key), &body)
+            .await?;
+        extract_embeddings_from_response(&response, &url, texts.len())
     }
 
-    fn embed_ollama(&self, url: String, text: &str) -> Result<Vec<f32>> {
+    async fn embed_ollama(&self, url: &str, text: &str) -> Result<Vec<f32>> {
         let body = json!({
             "model": self.model,
             "prompt": text
         });
-        let response =
-            execute_curl_json(&url, None, self.request_timeout_seconds, body.to_string())?;
-        extract_embedding_from_response(&response, &url)
+        let response = self.post_json_with_retry(url, None, &body).await?;
+        extract_embedding_from_response(&response, url)
     }
 
-    fn embed_voyage(&self, url: String, api_key: &str, text: &str) -> Result<Vec<f32>> {
+    async fn embed_voyage(
+        &self,
+        url: String,
+        api_key: &str,
+        texts: &[&str],
+    ) -> Result<Vec<Vec<f32>>> {
         let mut body = json!({
             "model": self.model,
-            "input": [text],
+            "input": texts,
             "input_type": self.voyage_input_type,
             "output_dtype": self.voyage_output_dtype,
             "truncation": self.voyage_truncation
@@ -303,61 +456,117 @@ impl EmbeddingRuntimeConfig {
             body["output_dimension"] = json!(dim);
         }
 
-        let response = execute_curl_json(
-            &url,
-            Some(format!("Authorization: Bearer {}", api_key)),
-            self.request_timeout_seconds,
-            body.to_string(),
-        )?;
-        extract_embedding_from_response(&response, &url)
+        let response = self
+            .post_json_with_retry(&url, Some(api_key), &body)
+            .await?;
+        extract_embeddings_from_response(&response, &url, texts.len())
     }
-}
 
-fn execute_curl_json(
-    url: &str,
-    auth_header: Option<String>,
-    timeout_seconds: u64,
-    body: String,
-) -> Result<Value> {
-    let mut command = Command::new("curl");
-    command
-        .arg("-sS")
-        .arg("--max-time")
-        .arg(timeout_seconds.to_string())
-        .arg("-X")
-        .arg("POST")
-        .arg(url)
-        .arg("-H")
-        .arg("Content-Type: application/json")
-        .arg("-d")
-        .arg(body);
-
-    if let Some(header) = auth_header {
-        command.arg("-H").arg(header);
-    }
+    /// Retries 408/429/5xx responses and transport failures with exponential
+    /// backoff, waiting at least as long as the provider's `Retry-After`.
+    async fn post_json_with_retry(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        body: &Value,
+    ) -> Result<Value> {
+        let timeout = Duration::from_secs(self.request_timeout_seconds);
+        let mut attempt = 0u32;
+        loop {
+            let err = match self
+                .http
+                .send_json(Method::POST, url, api_key, Some(body), Some(timeout))
+                .await
+            {
+                Ok(parsed) => {
+                    if let Some(error) = parsed.get("error") {
+                        bail!("Embedding provider returned error: {error}");
+                    }
+                    return Ok(parsed);
+                }
+                Err(err) => err,
+            };
 
-    let output = command
-        .output()
-        .with_context(|| format!("Failed to execute curl for embedding request to {url}"))?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("Embedding request failed: {}", stderr.trim());
+            if !err.is_retryable() || attempt >= self.max_retries {
+                return Err(anyhow::Error::new(err).context(format!(
+                    "Embedding request to {url} failed after {} attempts",
+                    attempt + 1
+                )));
+            }
+            let retry_after = err
+                .retry_after()
+                .map(|delay| delay.min(Duration::from_secs(RETRY_AFTER_MAX_SECONDS)));
+            let delay = backoff_delay(attempt).max(retry_after.unwrap_or_default());
+            warn!(
+                url,
+                attempt = attempt + 1,
+                delay_ms = delay.as_millis() as u64,
+                "Embedding request failed, retrying: {err}"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
     }
+}
+
+fn backoff_delay(attempt: u32) -> Duration {
+    let base = RETRY_BASE_DELAY_MS.saturating_mul(1 << attempt.min(16));
+    let capped = base.min(RETRY_MAX_DELAY_MS);
+    // Up to 25% jitter so parallel batches do not retry in lockstep.
+    let jitter = (Uuid::new_v4().as_u128() % 1000) as u64 * capped / 4000;
+    Duration::from_millis(capped + jitter)
+}
+
+/// Semaphore shared by every export in the process, per provider.
+fn provider_limiter(provider: EmbeddingProviderKind, max_concurrency: usize) -> Arc<Semaphore> {
+    static LIMITERS: OnceLock<Mutex<HashMap<&'static str, Arc<Semaphore>>>> = OnceLock::new();
+    let mut limiters = LIMITERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    Arc::clone(
+        limiters
+            .entry(provider.as_str())
+            .or_insert_with(|| Arc::new(Semaphore::new(max_concurrency.max(1)))),
+    )
+}
 
-    let stdout =
-        String::from_utf8(output.stdout).context("Embedding provider response was not UTF-8")?;
-    let parsed = serde_json::from_str::<Value>(&stdout).with_context(|| {
-        format!(
-            "Embedding provider response was not valid JSON for {url}: {}",
-            stdout.trim()
-        )
-    })?;
-
-    if let Some(error) = parsed.get("error") {
-        bail!("Embedding provider returned error: {error}");
+fn extract_embeddings_from_response(
+    response: &Value,
+    url: &str,
+    expected: usize,
+) -> Result<Vec<Vec<f32>>> {
+    let items = response
+        .get("data")
+        .and_then(Value::as_array)
+        .with_context(|| format!("Embedding response from {url} did not contain `data`."))?;
+    if items.len() != expected {
+        bail!(
+            "Embedding response from {url} returned {} vectors for {expected} inputs.",
+            items.len()
+        );
     }
 
-    Ok(parsed)
+    let mut indexed = Vec::with_capacity(items.len());
+    for (position, item) in items.iter().enumerate() {
+        let index = item
+            .get("index")
+            .and_then(Value::as_u64)
+            .map(|v| v as usize)
+            .unwrap_or(position);
+        let embedding = item
+            .get("embedding")
+            .and_then(Value::as_array)
+            .with_context(|| {
+                format!("Embedding response from {url} item {index} had no vector.")
+            })?;
+        indexed.push((index, parse_embedding_array(embedding, url)?));
+    }
+    indexed.sort_by_key(|(index, _)| *index);
+    Ok(indexed
+        .into_iter()
+        .map(|(_, embedding)| embedding)
+        .collect())
 }
 
 fn extract_embedding_from_response(response: &Value, url: &str) -> Result<Vec<f32>> {
@@ -392,3 +601,64 @@ fn parse_embedding_array(items: &[Value], url: &str) -> Result<Vec<f32>> {
     }
     Ok(embedding)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{openai_embedding_runtime, spawn_embeddings_mock};
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn texts_are_batched_and_returned_in_input_order() {
+        let (base_url, requests) = spawn_embeddings_mock(0).await;
+        let runtime = openai_embedding_runtime(base_url, 2, 0);
+
+        let texts = ["a", "bb", "ccc", "dddd", "eeeee"];
+        let embeddings = runtime.embed_texts_async(&texts).await.unwrap();
+        let lengths: Vec<f32> = embeddings.iter().map(|v| v[0]).collect();
+        assert_eq!(lengths, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retryable_failures_are_retried_up_to_the_limit() {
+        let (base_url, requests) = spawn_embeddings_mock(1).await;
+        let embeddings = openai_embedding_runtime(base_url, 8, 1)
+            .embed_texts_async(&["abc"])
+            .await
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![3.0, 1.0]]);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        let (base_url, requests) = spawn_embeddings_mock(usize::MAX).await;
+        let err = openai_embedding_runtime(base_url, 8, 0)
+            .embed_texts_async(&["abc"])
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("after 1 attempts"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_cap() {
+        let first = backoff_delay(0).as_millis() as u64;
+        assert!((RETRY_BASE_DELAY_MS..=RETRY_BASE_DELAY_MS * 5 / 4).contains(&first));
+        let third = backoff_delay(2).as_millis() as u64;
+        assert!(third >= RETRY_BASE_DELAY_MS * 4);
+        let capped = backoff_delay(30).as_millis() as u64;
+        assert!((RETRY_MAX_DELAY_MS..=RETRY_MAX_DELAY_MS * 5 / 4).contains(&capped));
+    }
+
+    #[test]
+    fn batch_response_is_ordered_by_index() {
+        let response = json!({
+            "data": [
+                { "index": 1, "embedding": [0.5, 0.5] },
+                { "index": 0, "embedding": [1.0, 0.0] }
+            ]
+        });
+        let embeddings = extract_embeddings_from_response(&response, "test", 2).unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.5, 0.5]]);
+        assert!(extract_embeddings_from_response(&response, "test", 3).is_err());
+    }
+}
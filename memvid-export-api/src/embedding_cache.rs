@@ -0,0 +1,412 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+pub const EMBEDDING_CACHE_FILE_NAME: &str = "embeddings.v1.sqlite";
+
+/// What produced a vector. Two texts only share a cache entry when every
+/// field matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddingIdentity {
+    pub provider: String,
+    pub model: String,
+    pub dimension: Option<u16>,
+    pub dtype: String,
+    /// Voyage `input_type` and `truncation`; `None` for providers without them.
+    pub input_type: Option<String>,
+    pub truncation: Option<bool>,
+}
+
+impl EmbeddingIdentity {
+    /// Readable form of every field, stored on capsule frames so a later
+    /// export only reuses a vector produced the same way.
+    pub fn fingerprint(&self) -> String {
+        let mut parts = vec![
+            format!("provider={}", self.provider),
+            format!("model={}", self.model),
+            format!("dtype={}", self.dtype),
+        ];
+        if let Some(dimension) = self.dimension {
+            parts.push(format!("dimension={dimension}"));
+        }
+        if let Some(input_type) = &self.input_type {
+            parts.push(format!("input_type={input_type}"));
+        }
+        if let Some(truncation) = self.truncation {
+            parts.push(format!("truncation={truncation}"));
+        }
+        parts.join(";")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddingCacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// On-disk, content-addressed embedding cache shared by every export.
+///
+/// Entries are keyed by SHA-256 over the identity and the text hash, stored
+/// as little-endian `f32` blobs, and evicted by age and then least-recent use
+/// once the total size passes `max_bytes`.
+#[derive(Debug, Clone)]
+pub struct EmbeddingCache {
+    conn: Arc<Mutex<Connection>>,
+    max_bytes: u64,
+    max_age: Duration,
+}
+
+impl EmbeddingCache {
+    pub fn open(path: &Path, max_bytes: u64, max_age: Duration) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!(
+                    "Failed creating embedding cache directory {}",
+                    parent.display()
+                )
+            })?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed opening embedding cache {}", path.display()))?;
+        Self::init(conn, max_bytes, max_age)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory(max_bytes: u64, max_age: Duration) -> Result<Self> {
+        Self::init(Connection::open_in_memory()?, max_bytes, max_age)
+    }
+
+    fn init(conn: Connection, max_bytes: u64, max_age: Duration) -> Result<Self> {
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(
+            "
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+            CREATE TABLE IF NOT EXISTS embeddings (
+                cache_key TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                dims INTEGER NOT NULL,
+                vector BLOB NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                created_at_s INTEGER NOT NULL,
+                last_used_at_s INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_embeddings_last_used ON embeddings(last_used_at_s);
+            CREATE INDEX IF NOT EXISTS idx_embeddings_created ON embeddings(created_at_s);
+            ",
+        )
+        .context("Failed initializing embedding cache schema")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            max_bytes,
+            max_age,
+        })
+    }
+
+    pub fn key(identity: &EmbeddingIdentity, text_hash: &str) -> String {
+        let mut hasher = Sha256::new();
+        // Provider options only join the key when set, so keys of providers
+        // without them stay what they were.
+        let options = identity
+            .input_type
+            .clone()
+            .into_iter()
+            .chain(identity.truncation.map(|t| format!("truncation={t}")));
+        let parts = [
+            identity.provider.clone(),
+            identity.model.clone(),
+            identity
+                .dimension
+                .map(|d| d.to_string())
+                .unwrap_or_default(),
+            identity.dtype.clone(),
+        ]
+        .into_iter()
+        .chain(options)
+        .chain(std::iter::once(text_hash.to_string()));
+        for part in parts {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Embedding cache connection lock poisoned"))
+    }
+
+    /// Looks up each key; returns one slot per key. Hits refresh their
+    /// last-used time. Entries past `max_age` count as misses.
+    pub fn get_many(&self, keys: &[String]) -> Result<Vec<Option<Vec<f32>>>> {
+        let now = Utc::now().timestamp();
+        let min_created = now - self.max_age.as_secs() as i64;
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        let mut found = Vec::with_capacity(keys.len());
+        {
+            let mut select = tx.prepare_cached(
+                "SELECT vector FROM embeddings WHERE cache_key=?1 AND created_at_s>=?2",
+            )?;
+            let mut touch =
+                tx.prepare_cached("UPDATE embeddings SET last_used_at_s=?2 WHERE cache_key=?1")?;
+            for key in keys {
+                let blob = select
+                    .query_row(params![key, min_created], |row| row.get::<_, Vec<u8>>(0))
+                    .optional()?;
+                if blob.is_some() {
+                    touch.execute(params![key, now])?;
+                }
+                found.push(blob.map(|blob| decode_vector(&blob)));
+            }
+        }
+        tx.commit()?;
+        Ok(found)
+    }
+
+    pub fn put_many(
+        &self,
+        identity: &EmbeddingIdentity,
+        entries: &[(String, &[f32])],
+    ) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let mut conn = self.lock()?;
+        let tx = conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO embeddings(cache_key,provider,model,dims,vector,size_bytes,created_at_s,last_used_at_s)
+                 VALUES(?1,?2,?3,?4,?5,?6,?7,?7)",
+            )?;
+            for (key, vector) in entries {
+                let blob = encode_vector(vector);
+                insert.execute(params![
+                    key,
+                    identity.provider,
+                    identity.model,
+                    vector.len() as i64,
+                    blob,
+                    blob.len() as i64,
+                    now
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Drops entries older than `max_age`, then the least recently used ones
+    /// until the cache fits in `max_bytes`. Returns the number removed.
+    pub fn prune(&self) -> Result<usize> {
+        let min_created = Utc::now().timestamp() - self.max_age.as_secs() as i64;
+        let conn = self.lock()?;
+        let mut removed = conn.execute(
+            "DELETE FROM embeddings WHERE created_at_s<?1",
+            params![min_created],
+        )?;
+
+        let total: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(size_bytes),0) FROM embeddings",
+            [],
+            |row| row.get(0),
+        )?;
+        let excess = total - self.max_bytes as i64;
+        if excess > 0 {
+            // Count how many least-recently-used rows cover the excess, then
+            // delete exactly those.
+            let mut stmt = conn.prepare(
+                "SELECT size_bytes FROM embeddings ORDER BY last_used_at_s ASC, rowid ASC",
+            )?;
+            let mut rows = stmt.query([])?;
+            let mut freed = 0i64;
+            let mut count = 0i64;
+            while let Some(row) = rows.next()? {
+                freed += row.get::<_, i64>(0)?;
+                count += 1;
+                if freed >= excess {
+                    break;
+                }
+            }
+            drop(rows);
+            removed += conn.execute(
+                "DELETE FROM embeddings WHERE rowid IN (
+                    SELECT rowid FROM embeddings ORDER BY last_used_at_s ASC, rowid ASC LIMIT ?1
+                 )",
+                params![count],
+            )?;
+        }
+        Ok(removed)
+    }
+}
+
+fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn decode_vector(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn openai_identity() -> EmbeddingIdentity {
+        EmbeddingIdentity {
+            provider: "openai".to_string(),
+            model: "text-embedding-3-large".to_string(),
+            dimension: None,
+            dtype: "float".to_string(),
+            input_type: None,
+            truncation: None,
+        }
+    }
+
+    fn voyage_identity() -> EmbeddingIdentity {
+        EmbeddingIdentity {
+            provider: "voyage".to_string(),
+            model: "voyage-code-3".to_string(),
+            dimension: Some(1024),
+            dtype: "float".to_string(),
+            input_type: Some("document".to_string()),
+            truncation: Some(true),
+        }
+    }
+
+    fn total_bytes(cache: &EmbeddingCache) -> i64 {
+        cache
+            .lock()
+            .unwrap()
+            .query_row("SELECT SUM(size_bytes) FROM embeddings", [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn every_identity_field_separates_cache_keys() {
+        let voyage = voyage_identity();
+        let key = EmbeddingCache::key(&voyage, "hash-a");
+        let variants = [
+            EmbeddingIdentity {
+                model: "voyage-3".to_string(),
+                ..voyage.clone()
+            },
+            EmbeddingIdentity {
+                dimension: Some(512),
+                ..voyage.clone()
+            },
+            EmbeddingIdentity {
+                dtype: "int8".to_string(),
+                ..voyage.clone()
+            },
+            EmbeddingIdentity {
+                input_type: Some("query".to_string()),
+                ..voyage.clone()
+            },
+            EmbeddingIdentity {
+                truncation: Some(false),
+                ..voyage.clone()
+            },
+        ];
+        for variant in &variants {
+            assert_ne!(key, EmbeddingCache::key(variant, "hash-a"), "{variant:?}");
+        }
+        assert_ne!(key, EmbeddingCache::key(&voyage, "hash-b"));
+        assert_eq!(key, EmbeddingCache::key(&voyage_identity(), "hash-a"));
+    }
+
+    #[test]
+    fn lookups_only_hit_the_identity_that_stored_the_vector() {
+        let cache = EmbeddingCache::open_in_memory(1 << 20, Duration::from_secs(3600)).unwrap();
+        let identity = openai_identity();
+        let other_model = EmbeddingIdentity {
+            model: "text-embedding-3-small".to_string(),
+            ..identity.clone()
+        };
+        let key = EmbeddingCache::key(&identity, "hash-a");
+        let vector = [1.0f32, -2.5, 3.0];
+        cache
+            .put_many(&identity, &[(key.clone(), &vector[..])])
+            .unwrap();
+
+        let found = cache
+            .get_many(&[key, EmbeddingCache::key(&other_model, "hash-a")])
+            .unwrap();
+        assert_eq!(found[0].as_deref(), Some(&vector[..]));
+        assert!(found[1].is_none());
+    }
+
+    #[test]
+    fn prune_evicts_least_recently_used_entries_past_the_size_limit() {
+        let cache = EmbeddingCache::open_in_memory(40, Duration::from_secs(3600)).unwrap();
+        let identity = openai_identity();
+        let keys: Vec<String> = ["hash-a", "hash-b", "hash-c"]
+            .iter()
+            .map(|hash| EmbeddingCache::key(&identity, hash))
+            .collect();
+        let vector = [1.0f32, 2.0, 3.0, 4.0];
+        for key in &keys {
+            cache
+                .put_many(&identity, &[(key.clone(), &vector[..])])
+                .unwrap();
+        }
+        // All three share a timestamp; rowid order breaks the tie.
+        assert_eq!(cache.prune().unwrap(), 1);
+        assert!(total_bytes(&cache) <= 40);
+        let found = cache.get_many(&keys).unwrap();
+        assert!(found[0].is_none());
+        assert!(found[1].is_some() && found[2].is_some());
+    }
+
+    #[test]
+    fn expired_entries_miss_and_are_pruned() {
+        let cache = EmbeddingCache::open_in_memory(1 << 20, Duration::ZERO).unwrap();
+        let identity = openai_identity();
+        let key = EmbeddingCache::key(&identity, "hash-a");
+        cache
+            .put_many(&identity, &[(key.clone(), &[1.0f32][..])])
+            .unwrap();
+        cache
+            .lock()
+            .unwrap()
+            .execute("UPDATE embeddings SET created_at_s=created_at_s-10", [])
+            .unwrap();
+
+        assert!(cache.get_many(&[key]).unwrap()[0].is_none());
+        assert_eq!(cache.prune().unwrap(), 1);
+    }
+
+    #[test]
+    fn entries_survive_reopening_the_cache_file() {
+        let dir = TempDir::new("embedding-cache");
+        let path = dir.path().join("cache").join(EMBEDDING_CACHE_FILE_NAME);
+        let identity = voyage_identity();
+        let key = EmbeddingCache::key(&identity, "hash-a");
+        {
+            let cache = EmbeddingCache::open(&path, 1 << 20, Duration::from_secs(3600)).unwrap();
+            cache
+                .put_many(&identity, &[(key.clone(), &[0.5f32, 0.25][..])])
+                .unwrap();
+        }
+        let cache = EmbeddingCache::open(&path, 1 << 20, Duration::from_secs(3600)).unwrap();
+        assert_eq!(
+            cache.get_many(&[key]).unwrap()[0].as_deref(),
+            Some(&[0.5f32, 0.25][..])
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use reqwest::{header, Method, StatusCode};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+const MAX_ERROR_BODY_CHARS: usize = 2_048;
+/// Artifact uploads and downloads outlive the client's default request timeout.
+pub const TRANSFER_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Failure of an outbound HTTP call, with enough detail for callers to decide
+/// whether to retry.
+#[derive(Debug, Error)]
+pub enum HttpError {
+    #[error("{method} {url} returned HTTP {status}: {body}")]
+    Status {
+        method: Method,
+        url: String,
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("{method} {url} failed: {source}")]
+    Transport {
+        method: Method,
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("{method} {url} returned an invalid body: {message}")]
+    Decode {
+        method: Method,
+        url: String,
+        message: String,
+    },
+}
+
+impl HttpError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Self::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// 408, 429 and 5xx responses, plus timeouts and connection failures.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Status { status, .. } => {
+                matches!(status.as_u16(), 408 | 429) || status.is_server_error()
+            }
+            Self::Transport { source, .. } => {
+                source.is_timeout() || source.is_connect() || source.is_request()
+            }
+            Self::Decode { .. } => false,
+        }
+    }
+}
+
+/// Shared outbound HTTP client (connection-pooled, rustls). Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    inner: reqwest::Client,
+}
+
+impl HttpClient {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(
+            Duration::from_secs(config.http_timeout_seconds),
+            Duration::from_secs(config.http_connect_timeout_seconds),
+            config.http_ca_bundle_path.as_deref(),
+        )
+    }
+
+    pub fn new(
+        timeout: Duration,
+        connect_timeout: Duration,
+        ca_bundle_path: Option<&Path>,
+    ) -> Result<Self> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(concat!("memvid-export-api/", env!("CARGO_PKG_VERSION")))
+            .timeout(timeout)
+            .connect_timeout(connect_timeout)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .pool_max_idle_per_host(16)
+            .tcp_keepalive(Duration::from_secs(60))
+            .min_tls_version(reqwest::tls::Version::TLS_1_2);
+
+        if let Some(path) = ca_bundle_path {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed reading CA bundle {}", path.display()))?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("Invalid PEM in CA bundle {}", path.display()))?
+            {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        Ok(Self {
+            inner: builder.build().context("Failed to build HTTP client")?,
+        })
+    }
+
+    /// Sends a JSON request and decodes a JSON response. Non-2xx responses are
+    /// returned as `HttpError::Status` with the (truncated) body.
+    pub async fn send_json(
+        &self,
+        method: Method,
+        url: &str,
+        bearer_token: Option<&str>,
+        body: Option<&Value>,
+        timeout: Option<Duration>,
+    ) -> Result<Value, HttpError> {
+        let mut request = self
+            .inner
+            .request(method.clone(), url)
+            .header(header::ACCEPT, "application/json");
+        if let Some(token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        if let Some(timeout) = timeout {
+            request = request.timeout(timeout);
+        }
+
+        let bytes = self.execute(&method, without_query(url), request).await?;
+        serde_json::from_slice::<Value>(&bytes).map_err(|err| HttpError::Decode {
+            method,
+            url: without_query(url).to_string(),
+            message: format!("{err}: {}", truncate_body(&bytes)),
+        })
+    }
+
+    pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>, HttpError> {
+        let request = self.inner.get(url);
+        self.execute(&Method::GET, without_query(url), request)
+            .await
+            .map(|bytes| bytes.to_vec())
+    }
+
+    /// Starts a request on the shared client, for callers that set their own
+    /// headers or stream the body. Send it with [`HttpClient::send_streaming`].
+    pub fn request(&self, method: Method, url: &str) -> reqwest::RequestBuilder {
+        self.inner.request(method, url)
+    }
+
+    /// Sends `request` and returns the response with its body unread, so
+    /// large downloads can be streamed. Non-2xx responses are returned as
+    /// `HttpError::Status`.
+    pub async fn send_streaming(
+        &self,
+        method: &Method,
+        url: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, HttpError> {
+        // reqwest errors print their full URL; callers pass `url` without
+        // any presigned query, so drop it from the source too.
+        let transport = |source: reqwest::Error| HttpError::Transport {
+            method: method.clone(),
+            url: url.to_string(),
+            source: source.without_url(),
+        };
+        let response = request.send().await.map_err(transport)?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_retry_after);
+        let bytes = response.bytes().await.map_err(transport)?;
+        Err(HttpError::Status {
+            method: method.clone(),
+            url: url.to_string(),
+            status,
+            body: truncate_body(&bytes),
+            retry_after,
+        })
+    }
+
+    /// Uploads a file with a streamed `PUT`, e.g. to a presigned URL.
+    pub async fn put_file(&self, url: &str, path: &Path) -> Result<()> {
+        let (body, size) = file_body(path).await?;
+        let request = self
+            .request(Method::PUT, url)
+            .header(header::CONTENT_LENGTH, size)
+            .body(body)
+            .timeout(TRANSFER_TIMEOUT);
+        self.send_streaming(&Method::PUT, without_query(url), request)
+            .await
+            .with_context(|| format!("Failed to upload {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Downloads `url` to `path`. Returns `false` when the server answers 404.
+    pub async fn download_file(&self, url: &str, path: &Path) -> Result<bool> {
+        let request = self.request(Method::GET, url).timeout(TRANSFER_TIMEOUT);
+        match self
+            .send_streaming(&Method::GET, without_query(url), request)
+            .await
+        {
+            Ok(response) => write_response_to_file(response, path).await.map(|_| true),
+            Err(err) if err.status() == Some(StatusCode::NOT_FOUND) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn execute(
+        &self,
+        method: &Method,
+        url: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<bytes::Bytes, HttpError> {
+        let response = self.send_streaming(method, url, request).await?;
+        response
+            .bytes()
+            .await
+            .map_err(|source| HttpError::Transport {
+                method: method.clone(),
+                url: url.to_string(),
+                source: source.without_url(),
+            })
+    }
+}
+
+/// Streams a file as a request body; returns it with its length, which
+/// object stores require up front.
+pub async fn file_body(path: &Path) -> Result<(reqwest::Body, u64)> {
+    let file = fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let size = file
+        .metadata()
+        .await
+        .with_context(|| format!("Failed to stat {}", path.display()))?
+        .len();
+    Ok((reqwest::Body::wrap_stream(ReaderStream::new(file)), size))
+}
+
+/// Writes a response body to a temp file next to `path` and renames it into
+/// place, so an interrupted download never leaves a partial file behind.
+pub async fn write_response_to_file(mut response: reqwest::Response, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut tmp_name = OsString::from(path.as_os_str());
+    tmp_name.push(format!(".{}.tmp", Uuid::new_v4()));
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let url = without_query(response.url().as_str()).to_string();
+    let result = async {
+        let mut file = fs::File::create(&tmp_path)
+            .await
+            .with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .with_context(|| format!("Failed to download {url}"))?
+        {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        fs::rename(&tmp_path, path)
+            .await
+            .with_context(|| format!("Failed to finalize {}", path.display()))
+    }
+    .await;
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path).await;
+    }
+    result
+}
+
+/// Strips the query string, which holds the signature of a presigned URL,
+/// so the URL can be logged or reported.
+pub fn without_query(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(base, _)| base)
+}
+
+/// `Retry-After` as delta-seconds or an HTTP date.
+pub fn parse_retry_after(raw: &str) -> Option<Duration> {
+    let raw = raw.trim();
+    match raw.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(raw)
+            .ok()?
+            .duration_since(std::time::SystemTime::now())
+            .ok()
+            .or(Some(Duration::ZERO)),
+    }
+}
+
+fn truncate_body(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim();
+    if text.chars().count() <= MAX_ERROR_BODY_CHARS {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(MAX_ERROR_BODY_CHARS).collect();
+    truncated.push_str("...");
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use axum::{
+        body::Bytes,
+        http::HeaderMap,
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    async fn spawn_mock() -> String {
+        let blob: Arc<Mutex<Option<Vec<u8>>>> = Arc::default();
+        let stored = Arc::clone(&blob);
+        let app = Router::new()
+            .route(
+                "/echo",
+                post(|headers: HeaderMap, Json(body): Json<Value>| async move {
+                    let auth = headers
+                        .get(header::AUTHORIZATION)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default()
+                        .to_string();
+                    Json(json!({ "auth": auth, "body": body }))
+                }),
+            )
+            .route(
+                "/limited",
+                get(|| async {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [(header::RETRY_AFTER, "3")],
+                        "slow down",
+                    )
+                }),
+            )
+            .route(
+                "/broken",
+                get(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "x".repeat(5_000)) }),
+            )
+            .route("/text", get(|| async { "not json" }))
+            .route(
+                "/blob",
+                get(move || {
+                    let blob = blob.lock().unwrap().clone();
+                    async move { blob.ok_or(StatusCode::NOT_FOUND) }
+                })
+                .put(move |body: Bytes| {
+                    *stored.lock().unwrap() = Some(body.to_vec());
+                    async { StatusCode::OK }
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn client() -> HttpClient {
+        HttpClient::new(Duration::from_secs(5), Duration::from_secs(5), None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn send_json_sends_bearer_token_and_body() {
+        let base = spawn_mock().await;
+        let echoed = client()
+            .send_json(
+                Method::POST,
+                &format!("{base}/echo"),
+                Some("secret"),
+                Some(&json!({ "input": ["a", "b"] })),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(echoed["auth"], "Bearer secret");
+        assert_eq!(echoed["body"]["input"][1], "b");
+    }
+
+    #[tokio::test]
+    async fn status_errors_carry_retry_after_and_a_truncated_body() {
+        let base = spawn_mock().await;
+        let client = client();
+
+        let err = client
+            .send_json(Method::GET, &format!("{base}/limited"), None, None, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status(), Some(StatusCode::TOO_MANY_REQUESTS));
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(3)));
+        assert!(err.is_retryable());
+        assert!(err.to_string().contains("slow down"));
+
+        let err = client
+            .get_bytes(&format!("{base}/broken"))
+            .await
+            .unwrap_err();
+        assert!(err.is_retryable());
+        assert!(err.to_string().ends_with(&format!("{}...", "x".repeat(10))));
+        assert!(err.to_string().len() < 2_200);
+    }
+
+    #[tokio::test]
+    async fn errors_do_not_carry_presigned_query_strings() {
+        let base = spawn_mock().await;
+        let client = client();
+        let signed = |path: &str| format!("{path}?X-Amz-Signature=leaked");
+
+        let status = client
+            .get_bytes(&signed(&format!("{base}/broken")))
+            .await
+            .unwrap_err();
+        let decode = client
+            .send_json(
+                Method::GET,
+                &signed(&format!("{base}/text")),
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap_err();
+        // Nothing listens on port 1, so the connection is refused.
+        let transport = client
+            .get_bytes(&signed("http://127.0.0.1:1/payload"))
+            .await
+            .unwrap_err();
+        assert!(matches!(transport, HttpError::Transport { .. }));
+        for err in [status, decode, transport] {
+            let rendered = format!("{:#}", anyhow::Error::new(err));
+            assert!(!rendered.contains("leaked"), "{rendered}");
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_json_is_a_non_retryable_decode_error() {
+        let base = spawn_mock().await;
+        let err = client()
+            .send_json(Method::GET, &format!("{base}/text"), None, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, HttpError::Decode { .. }));
+        assert!(!err.is_retryable());
+        assert!(err.to_string().contains("not json"));
+    }
+
+    #[tokio::test]
+    async fn files_round_trip_through_put_and_download() {
+        let base = spawn_mock().await;
+        let dir = TempDir::new("http-client");
+        let client = client();
+        let url = format!("{base}/blob?X-Amz-Signature=abc");
+
+        let target = dir.path().join("nested/download.bin");
+        assert!(!client.download_file(&url, &target).await.unwrap());
+        assert!(!target.exists());
+
+        let source = dir.path().join("upload.bin");
+        std::fs::write(&source, b"capsule bytes").unwrap();
+        client.put_file(&url, &source).await.unwrap();
+
+        assert!(client.download_file(&url, &target).await.unwrap());
+        assert_eq!(std::fs::read(&target).unwrap(), b"capsule bytes");
+        let leftovers = std::fs::read_dir(target.parent().unwrap()).unwrap().count();
+        assert_eq!(leftovers, 1, "no temp files are left next to the download");
+    }
+
+    #[test]
+    fn retry_after_and_presigned_urls_are_parsed() {
+        assert_eq!(parse_retry_after(" 7 "), Some(Duration::from_secs(7)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(
+            without_query("https://bucket/key?X-Amz-Signature=abc"),
+            "https://bucket/key"
+        );
+        assert_eq!(without_query("https://bucket/key"), "https://bucket/key");
+    }
+}
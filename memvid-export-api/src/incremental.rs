@@ -0,0 +1,328 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use memvid_core::{
+    Memvid, TimelineQuery, MEMVID_EMBEDDING_MODEL_KEY, MEMVID_EMBEDDING_PROVIDER_KEY,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    embedding_cache::{EmbeddingCacheStats, EmbeddingIdentity},
+    models::FrameDocument,
+};
+
+pub const CONTENT_HASH_TAG: &str = "content_sha256";
+/// [`EmbeddingIdentity::fingerprint`] of the run that embedded the frame.
+pub const EMBEDDING_IDENTITY_TAG: &str = "embedding_identity";
+
+pub fn content_hash(text: &str) -> String {
+    hex::encode(Sha256::digest(text.as_bytes()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameChange {
+    Added,
+    Changed,
+    Unchanged,
+}
+
+/// Frame-level diff between a new export and the capsule it is based on.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_capsule: Option<PathBuf>,
+    pub total_frames: usize,
+    pub added_frames: usize,
+    pub changed_frames: usize,
+    pub unchanged_frames: usize,
+    pub removed_frames: usize,
+    pub reused_embeddings: usize,
+    pub computed_embeddings: usize,
+    /// Reported separately as `embeddingCache` in worker metrics.
+    #[serde(skip)]
+    pub embedding_cache: EmbeddingCacheStats,
+}
+
+struct BaseFrame {
+    frame_id: u64,
+    content_hash: String,
+    embedding_provider: Option<String>,
+    embedding_model: Option<String>,
+    embedding_identity: Option<String>,
+}
+
+/// Read-only view of a previous capsule, keyed by frame URI. Node and
+/// relationship frames use their graph ids in the URI, so a URI match is an
+/// id match and the content hash tells whether the frame changed.
+pub struct BaseCapsule {
+    path: PathBuf,
+    mem: Memvid,
+    frames: HashMap<String, BaseFrame>,
+}
+
+impl BaseCapsule {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut mem = Memvid::open_read_only(path)
+            .with_context(|| format!("Failed opening base capsule {}", path.display()))?;
+        let timeline = mem.timeline(TimelineQuery::builder().no_limit().build())?;
+
+        let mut frames = HashMap::new();
+        for entry in timeline {
+            let Ok(frame) = mem.frame_by_id(entry.frame_id) else {
+                continue;
+            };
+            let Some(uri) = frame.uri.clone().or(entry.uri.clone()) else {
+                continue;
+            };
+            let content_hash = match frame.extra_metadata.get(CONTENT_HASH_TAG) {
+                Some(hash) => hash.clone(),
+                None => content_hash(frame.search_text.as_deref().unwrap_or(&entry.preview)),
+            };
+            frames.insert(
+                uri,
+                BaseFrame {
+                    frame_id: entry.frame_id,
+                    content_hash,
+                    embedding_provider: frame
+                        .extra_metadata
+                        .get(MEMVID_EMBEDDING_PROVIDER_KEY)
+                        .cloned(),
+                    embedding_model: frame
+                        .extra_metadata
+                        .get(MEMVID_EMBEDDING_MODEL_KEY)
+                        .cloned(),
+                    embedding_identity: frame.extra_metadata.get(EMBEDDING_IDENTITY_TAG).cloned(),
+                },
+            );
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            mem,
+            frames,
+        })
+    }
+
+    pub fn classify(&self, doc: &FrameDocument, hash: &str) -> FrameChange {
+        match self.frames.get(&doc.uri) {
+            None => FrameChange::Added,
+            Some(frame) if frame.content_hash == hash => FrameChange::Unchanged,
+            Some(_) => FrameChange::Changed,
+        }
+    }
+
+    /// Returns the stored embedding for an unchanged frame when it was
+    /// produced with the same embedding identity as the current run. Frames
+    /// from before identities were recorded only match providers without
+    /// output options, where provider and model decide the vector.
+    pub fn reusable_embedding(
+        &mut self,
+        doc: &FrameDocument,
+        hash: &str,
+        identity: &EmbeddingIdentity,
+    ) -> Option<Vec<f32>> {
+        let frame = self.frames.get(&doc.uri)?;
+        if frame.content_hash != hash {
+            return None;
+        }
+        let same_identity = match frame.embedding_identity.as_deref() {
+            Some(fingerprint) => fingerprint == identity.fingerprint(),
+            None => {
+                identity.dimension.is_none()
+                    && identity.input_type.is_none()
+                    && identity.truncation.is_none()
+                    && frame.embedding_provider.as_deref() == Some(identity.provider.as_str())
+                    && frame.embedding_model.as_deref() == Some(identity.model.as_str())
+            }
+        };
+        if !same_identity {
+            return None;
+        }
+        let embedding = match self.mem.frame_embedding(frame.frame_id) {
+            Ok(embedding) => embedding.filter(|v| !v.is_empty())?,
+            Err(err) => {
+                warn!(uri = %doc.uri, "Failed reading base embedding: {err}");
+                return None;
+            }
+        };
+        if identity
+            .dimension
+            .is_some_and(|dimension| embedding.len() != usize::from(dimension))
+        {
+            return None;
+        }
+        Some(embedding)
+    }
+
+    pub fn start_diff(&self, docs: &[FrameDocument]) -> ExportDiff {
+        let new_uris: HashSet<&str> = docs.iter().map(|doc| doc.uri.as_str()).collect();
+        ExportDiff {
+            base_capsule: Some(self.path.clone()),
+            total_frames: docs.len(),
+            removed_frames: self
+                .frames
+                .keys()
+                .filter(|uri| !new_uris.contains(uri.as_str()))
+                .count(),
+            ..ExportDiff::default()
+        }
+    }
+}
+
+impl ExportDiff {
+    pub fn record(&mut self, change: FrameChange) {
+        match change {
+            FrameChange::Added => self.added_frames += 1,
+            FrameChange::Changed => self.changed_frames += 1,
+            FrameChange::Unchanged => self.unchanged_frames += 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memvid_writer::write_mv2_core_only,
+        test_support::{spawn_embeddings_mock, voyage_embedding_runtime, TempDir},
+    };
+    use serde_json::Value;
+    use std::sync::atomic::Ordering;
+
+    fn doc(id: &str, text: &str) -> FrameDocument {
+        FrameDocument {
+            title: id.to_string(),
+            label: "Function".to_string(),
+            text: text.to_string(),
+            uri: format!("mv2://nodes/{id}"),
+            track: "nodes".to_string(),
+            tags: Vec::new(),
+            metadata: Value::Null,
+        }
+    }
+
+    fn write_base(dir: &TempDir) -> PathBuf {
+        let base_path = dir.path().join("base.mv2");
+        let base_docs = vec![doc("a", "alpha"), doc("b", "beta"), doc("c", "gamma")];
+        write_mv2_core_only(&base_path, &base_docs, false, None, None, |_, _| Ok(())).unwrap();
+        base_path
+    }
+
+    #[test]
+    fn frames_are_classified_by_uri_and_content_hash() {
+        let dir = TempDir::new("incremental-classify");
+        let base = BaseCapsule::open(&write_base(&dir)).unwrap();
+
+        let unchanged = doc("a", "alpha");
+        let changed = doc("b", "beta v2");
+        let added = doc("d", "delta");
+        for (doc, expected) in [
+            (&unchanged, FrameChange::Unchanged),
+            (&changed, FrameChange::Changed),
+            (&added, FrameChange::Added),
+        ] {
+            assert_eq!(base.classify(doc, &content_hash(&doc.text)), expected);
+        }
+        assert_eq!(
+            base.start_diff(&[unchanged, changed, added]).removed_frames,
+            1
+        );
+    }
+
+    #[test]
+    fn embeddings_are_not_reused_without_a_matching_model() {
+        let dir = TempDir::new("incremental-embeddings");
+        let mut base = BaseCapsule::open(&write_base(&dir)).unwrap();
+        let unchanged = doc("a", "alpha");
+        let hash = content_hash(&unchanged.text);
+        let identity = EmbeddingIdentity {
+            provider: "openai".to_string(),
+            model: "text-embedding-3-small".to_string(),
+            dimension: None,
+            dtype: "float".to_string(),
+            input_type: None,
+            truncation: None,
+        };
+        assert!(base
+            .reusable_embedding(&unchanged, &hash, &identity)
+            .is_none());
+    }
+
+    #[test]
+    fn changed_output_dimension_forces_re_embedding() {
+        let dir = TempDir::new("incremental-dimension");
+        let docs = vec![doc("a", "alpha"), doc("b", "beta")];
+        // The mock serves from its own runtime while the writer blocks.
+        let server = tokio::runtime::Runtime::new().unwrap();
+        let (base_url, requests) = server.block_on(spawn_embeddings_mock(0));
+
+        let base_path = dir.path().join("base.mv2");
+        let runtime = voyage_embedding_runtime(base_url.clone(), 256);
+        write_mv2_core_only(&base_path, &docs, true, Some(runtime), None, |_, _| Ok(())).unwrap();
+
+        let same_path = dir.path().join("same.mv2");
+        let runtime = voyage_embedding_runtime(base_url.clone(), 256);
+        let (diff, _) = write_mv2_core_only(
+            &same_path,
+            &docs,
+            true,
+            Some(runtime),
+            Some(&base_path),
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(diff.reused_embeddings, 2);
+        let seen = requests.load(Ordering::SeqCst);
+
+        let resized_path = dir.path().join("resized.mv2");
+        let runtime = voyage_embedding_runtime(base_url, 512);
+        let (diff, _) = write_mv2_core_only(
+            &resized_path,
+            &docs,
+            true,
+            Some(runtime.clone()),
+            Some(&base_path),
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(diff.reused_embeddings, 0);
+        assert!(requests.load(Ordering::SeqCst) > seen);
+
+        let mut resized = BaseCapsule::open(&resized_path).unwrap();
+        let alpha = doc("a", "alpha");
+        let embedding = resized
+            .reusable_embedding(&alpha, &content_hash(&alpha.text), &runtime.identity())
+            .expect("same identity is reusable");
+        assert_eq!(embedding.len(), 512);
+    }
+
+    #[test]
+    fn rewrite_against_base_reports_frame_diff() {
+        let dir = TempDir::new("incremental-rewrite");
+        let base_path = write_base(&dir);
+        let next_path = dir.path().join("next.mv2");
+
+        let next_docs = vec![doc("a", "alpha"), doc("b", "beta v2"), doc("d", "delta")];
+        let (diff, _) = write_mv2_core_only(
+            &next_path,
+            &next_docs,
+            false,
+            None,
+            Some(&base_path),
+            |_, _| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(diff.base_capsule.as_deref(), Some(base_path.as_path()));
+        assert_eq!(diff.unchanged_frames, 1);
+        assert_eq!(diff.changed_frames, 1);
+        assert_eq!(diff.added_frames, 1);
+        assert_eq!(diff.removed_frames, 1);
+    }
+}
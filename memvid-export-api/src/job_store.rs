@@ -0,0 +1,356 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use tokio::task;
+use tracing::warn;
+
+use crate::models::{ExportLogEvent, ExportRequest, JobRecord};
+
+pub const JOB_STORE_FILE_NAME: &str = "jobs.v1.sqlite";
+
+/// Durable copy of every job record and its event log.
+///
+/// The in-memory `AppState.jobs` map stays the source of truth while the
+/// process runs; this store is written through on every change so the map
+/// can be rebuilt after a restart.
+#[derive(Clone)]
+pub struct JobStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+/// Serialized job row, prepared while the caller still holds the jobs lock.
+#[derive(Debug, Clone)]
+pub struct JobSnapshot {
+    pub job_id: String,
+    pub status: String,
+    pub updated_at_us: i64,
+    pub record_json: String,
+    pub has_request: bool,
+}
+
+impl JobSnapshot {
+    pub fn from_record(record: &JobRecord) -> Result<Self> {
+        Ok(Self {
+            job_id: record.job_id.clone(),
+            status: serde_json::to_value(&record.status)?
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            updated_at_us: record.updated_at.timestamp_micros(),
+            record_json: serde_json::to_string(record).context("Failed to encode job record")?,
+            has_request: record.request.is_some(),
+        })
+    }
+}
+
+impl JobStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed creating job store directory {}", parent.display())
+            })?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed opening job store {}", path.display()))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch(
+            "
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+            CREATE TABLE IF NOT EXISTS jobs (
+                job_id TEXT PRIMARY KEY,
+                status TEXT NOT NULL,
+                updated_at_us INTEGER NOT NULL,
+                record_json TEXT NOT NULL,
+                request_json BLOB
+            );
+            CREATE TABLE IF NOT EXISTS job_events (
+                job_id TEXT NOT NULL,
+                seq INTEGER NOT NULL,
+                event_json TEXT NOT NULL,
+                PRIMARY KEY (job_id, seq)
+            );
+            CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
+            ",
+        )
+        .context("Failed initializing job store schema")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn with_conn<T, F>(&self, op: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        task::spawn_blocking(move || {
+            let mut guard = conn
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Job store connection lock poisoned"))?;
+            op(&mut guard)
+        })
+        .await
+        .context("Job store task join error")?
+    }
+
+    /// Inserts a freshly accepted job together with its request payload so it
+    /// can be requeued if the process restarts before a worker stages it.
+    pub async fn insert_job(&self, snapshot: JobSnapshot, request: &ExportRequest) -> Result<()> {
+        let request_json = serde_json::to_vec(request).context("Failed to encode job request")?;
+        self.with_conn(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO jobs(job_id,status,updated_at_us,record_json,request_json) VALUES(?1,?2,?3,?4,?5)",
+                params![
+                    snapshot.job_id,
+                    snapshot.status,
+                    snapshot.updated_at_us,
+                    snapshot.record_json,
+                    request_json
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn save_job(&self, snapshot: JobSnapshot) -> Result<()> {
+        self.with_conn(move |conn| upsert_snapshot(conn, &snapshot))
+            .await
+    }
+
+    pub async fn append_event(
+        &self,
+        snapshot: JobSnapshot,
+        event: &ExportLogEvent,
+        max_events: usize,
+    ) -> Result<()> {
+        let event_json = serde_json::to_string(event).context("Failed to encode job event")?;
+        let seq = event.seq as i64;
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT OR REPLACE INTO job_events(job_id,seq,event_json) VALUES(?1,?2,?3)",
+                params![snapshot.job_id, seq, event_json],
+            )?;
+            tx.execute(
+                "DELETE FROM job_events WHERE job_id=?1 AND seq<=?2",
+                params![snapshot.job_id, seq - max_events as i64],
+            )?;
+            upsert_snapshot(&tx, &snapshot)?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn clear_events(&self, job_id: &str) -> Result<()> {
+        let job_id = job_id.to_string();
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM job_events WHERE job_id=?1", params![job_id])?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn delete_job(&self, job_id: &str) -> Result<()> {
+        let job_id = job_id.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM job_events WHERE job_id=?1", params![job_id])?;
+            tx.execute("DELETE FROM jobs WHERE job_id=?1", params![job_id])?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Loads every persisted job with its request payload (when still kept)
+    /// and the most recent `max_events` events.
+    pub async fn load_all(&self, max_events: usize) -> Result<Vec<JobRecord>> {
+        self.with_conn(move |conn| {
+            let mut records = Vec::new();
+            let mut job_stmt = conn.prepare("SELECT job_id,record_json,request_json FROM jobs")?;
+            let mut event_stmt = conn.prepare(
+                "SELECT event_json FROM job_events WHERE job_id=?1 ORDER BY seq DESC LIMIT ?2",
+            )?;
+
+            let rows = job_stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<Vec<u8>>>(2)?,
+                ))
+            })?;
+
+            for row in rows {
+                let (job_id, record_json, request_json) = row?;
+                let mut record = match serde_json::from_str::<JobRecord>(&record_json) {
+                    Ok(record) => record,
+                    Err(err) => {
+                        warn!(job_id = %job_id, "Skipping unreadable persisted job: {err}");
+                        continue;
+                    }
+                };
+
+                if let Some(raw) = request_json {
+                    match serde_json::from_slice::<ExportRequest>(&raw) {
+                        Ok(request) => record.request = Some(request),
+                        Err(err) => {
+                            warn!(job_id = %job_id, "Persisted job request is unreadable: {err}");
+                        }
+                    }
+                }
+
+                let events = event_stmt.query_map(params![job_id, max_events as i64], |row| {
+                    row.get::<_, String>(0)
+                })?;
+                for event in events {
+                    match serde_json::from_str::<ExportLogEvent>(&event?) {
+                        Ok(event) => record.events.push_front(event),
+                        Err(err) => {
+                            warn!(job_id = %job_id, "Skipping unreadable persisted event: {err}");
+                        }
+                    }
+                }
+
+                records.push(record);
+            }
+
+            Ok(records)
+        })
+        .await
+    }
+}
+
+fn upsert_snapshot(conn: &Connection, snapshot: &JobSnapshot) -> Result<()> {
+    // Older snapshots may race newer ones through the blocking pool; only
+    // apply a write when it is at least as recent as what is stored.
+    conn.execute(
+        "INSERT INTO jobs(job_id,status,updated_at_us,record_json,request_json) VALUES(?1,?2,?3,?4,NULL)
+         ON CONFLICT(job_id) DO UPDATE SET
+            status=excluded.status,
+            updated_at_us=excluded.updated_at_us,
+            record_json=excluded.record_json,
+            request_json=CASE WHEN ?5 THEN jobs.request_json ELSE NULL END
+         WHERE excluded.updated_at_us >= jobs.updated_at_us",
+        params![
+            snapshot.job_id,
+            snapshot.status,
+            snapshot.updated_at_us,
+            snapshot.record_json,
+            snapshot.has_request
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{ExportEventType, ExportStage, JobState},
+        test_support::{job_record, sample_request},
+    };
+
+    fn event(job: &JobRecord, seq: u64) -> ExportLogEvent {
+        ExportLogEvent {
+            seq,
+            ts: job.updated_at,
+            job_id: job.job_id.clone(),
+            event_type: ExportEventType::StageProgress,
+            stage: ExportStage::FramePrep,
+            progress: 20.0,
+            stage_progress: Some(0.0),
+            emoji: String::new(),
+            message: format!("event {seq}"),
+            meta: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_snapshots_do_not_overwrite_newer_ones() {
+        let store = JobStore::open_in_memory().unwrap();
+        let mut job = job_record("job-1", JobState::Running);
+        job.planned_frames = 42;
+        store
+            .save_job(JobSnapshot::from_record(&job).unwrap())
+            .await
+            .unwrap();
+
+        let mut stale = job.clone();
+        stale.updated_at = job.updated_at - chrono::Duration::seconds(5);
+        stale.status = JobState::Queued;
+        store
+            .save_job(JobSnapshot::from_record(&stale).unwrap())
+            .await
+            .unwrap();
+
+        let restored = store.load_all(10).await.unwrap();
+        assert_eq!(restored.len(), 1);
+        assert!(matches!(restored[0].status, JobState::Running));
+        assert_eq!(restored[0].planned_frames, 42);
+        assert_eq!(restored[0].owner_key_id.as_deref(), Some("ci"));
+    }
+
+    #[tokio::test]
+    async fn events_beyond_the_limit_are_trimmed_oldest_first() {
+        let store = JobStore::open_in_memory().unwrap();
+        let job = job_record("job-1", JobState::Running);
+        for seq in 1..=5 {
+            store
+                .append_event(
+                    JobSnapshot::from_record(&job).unwrap(),
+                    &event(&job, seq),
+                    3,
+                )
+                .await
+                .unwrap();
+        }
+
+        let restored = store.load_all(10).await.unwrap();
+        let seqs: Vec<u64> = restored[0].events.iter().map(|event| event.seq).collect();
+        assert_eq!(seqs, vec![3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn request_blob_is_dropped_once_the_record_releases_it() {
+        let store = JobStore::open_in_memory().unwrap();
+        let mut job = job_record("job-1", JobState::Queued);
+        job.request = Some(sample_request());
+        store
+            .insert_job(
+                JobSnapshot::from_record(&job).unwrap(),
+                job.request.as_ref().unwrap(),
+            )
+            .await
+            .unwrap();
+        store
+            .append_event(JobSnapshot::from_record(&job).unwrap(), &event(&job, 1), 10)
+            .await
+            .unwrap();
+        assert!(store.load_all(10).await.unwrap()[0].request.is_some());
+
+        job.request = None;
+        job.status = JobState::Running;
+        store
+            .save_job(JobSnapshot::from_record(&job).unwrap())
+            .await
+            .unwrap();
+        let restored = store.load_all(10).await.unwrap();
+        assert!(matches!(restored[0].status, JobState::Running));
+        assert!(restored[0].request.is_none());
+    }
+}
@@ -2,36 +2,59 @@ mod api;
 mod artifact_store;
 mod auth;
 mod config;
+mod download;
 mod embedding;
+mod embedding_cache;
+mod http_client;
+mod incremental;
+mod job_store;
 mod mcp_api;
 mod mcp_index;
+mod mcp_semantic;
 mod memvid_writer;
+mod metrics;
 mod models;
 mod queue;
 mod rate_limit;
+mod readiness;
 mod runpod;
 mod runpod_execute;
+mod s3;
+mod shutdown;
+mod signed_url;
+mod telemetry;
+#[cfg(test)]
+mod test_support;
 mod transform;
+mod uploads;
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::Result;
+use artifact_store::ArtifactStore;
+use auth::KeyRegistry;
 use axum::{
+    body::Body,
     extract::DefaultBodyLimit,
-    routing::{get, post},
+    http::Request,
+    routing::{get, post, put},
     Router,
 };
 use config::Config;
-use mcp_api::{new_query_cache, QueryCache};
-use mcp_index::CapsuleIndex;
+use embedding_cache::EmbeddingCache;
+use http_client::HttpClient;
+use job_store::{JobStore, JOB_STORE_FILE_NAME};
+use mcp_api::{new_index_cache, new_query_cache, IndexCache, QueryCache};
 use models::{ExportLogEvent, JobRecord};
-use rate_limit::RateLimiter;
+use queue::{ExportLimits, JobCancellations};
+use rate_limit::{MemoryRateLimitBackend, RateLimiter};
+use shutdown::Shutdown;
 use tokio::sync::{broadcast, mpsc, RwLock};
 use tower_http::{
     cors::{Any, CorsLayer},
     trace::TraceLayer,
 };
-use tracing::{info, warn};
+use tracing::{info, info_span, warn, Span};
 
 const MAX_EXPORT_BODY_BYTES: usize = 500 * 1024 * 1024;
 
@@ -41,24 +64,35 @@ pub struct AppState {
     pub jobs: Arc<RwLock<HashMap<String, JobRecord>>>,
     pub event_buses: Arc<RwLock<HashMap<String, broadcast::Sender<ExportLogEvent>>>>,
     pub queue_tx: mpsc::Sender<String>,
-    pub mcp_indexes: Arc<RwLock<HashMap<String, Arc<CapsuleIndex>>>>,
+    pub job_store: JobStore,
+    pub export_limits: Arc<ExportLimits>,
+    pub job_cancellations: JobCancellations,
+    pub http: HttpClient,
+    pub embedding_cache: Option<EmbeddingCache>,
+    pub artifacts: Arc<dyn ArtifactStore>,
+    pub mcp_indexes: Arc<tokio::sync::Mutex<IndexCache>>,
     pub mcp_cache: Arc<tokio::sync::Mutex<QueryCache>>,
     pub rate_limiter: Arc<RateLimiter>,
+    pub export_rate_limiter: Arc<RateLimiter>,
+    pub keys: Arc<KeyRegistry>,
+    pub shutdown: Shutdown,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "memvid_export_api=info,tower_http=info".into()),
-        )
-        .init();
+    let args: Vec<String> = std::env::args().collect();
+    let is_runpod_execute = args.get(1).is_some_and(|arg| arg == "runpod-execute");
+    let telemetry = telemetry::init(if is_runpod_execute {
+        "memvid-export-runpod"
+    } else {
+        "memvid-export-api"
+    })?;
 
     let mut config = Config::from_env()?;
-    let args: Vec<String> = std::env::args().collect();
-    if runpod_execute::maybe_run_from_cli(&args).await? {
-        return Ok(());
+    if is_runpod_execute {
+        let result = runpod_execute::maybe_run_from_cli(&args).await;
+        telemetry.shutdown();
+        return result.map(|_| ());
     }
 
     info!(
@@ -66,6 +100,9 @@ async fn main() -> Result<()> {
         bind_addr = %config.bind_addr,
         export_root = %config.export_root.display(),
         queue_capacity = config.queue_capacity,
+        worker_concurrency = config.worker_concurrency,
+        local_write_concurrency = config.local_write_concurrency,
+        runpod_concurrency = config.runpod_concurrency,
         backend_mode = config.backend_mode.as_str(),
         embedding_mode = config.embedding_mode.as_str(),
         embedding_provider = %config.embedding_provider,
@@ -113,6 +150,7 @@ async fn main() -> Result<()> {
 
         if let Some(root) = selected_root {
             config.export_root = root;
+            config.export_root_is_fallback = true;
         } else {
             warn!(
                 original_root = %original_root.display(),
@@ -129,11 +167,15 @@ async fn main() -> Result<()> {
         );
     }
 
-    if config.api_key_is_fallback {
+    let keys = KeyRegistry::from_config(&config)?;
+    if config.api_key_is_fallback && config.api_keys_path.is_none() {
         warn!(
             "Using generated fallback API key because MEMVID_EXPORT_API_KEY/MEMVID_EXPORT_API_KEY_FILE was not usable"
         );
     }
+    if keys.is_empty() {
+        warn!("API key registry is empty; every authenticated request will be rejected");
+    }
 
     info!(
         bind_addr = %config.bind_addr,
@@ -142,30 +184,115 @@ async fn main() -> Result<()> {
         backend_mode = config.backend_mode.as_str(),
         runpod_enabled = config.runpod_enabled(),
         fallback_key = config.api_key_is_fallback,
+        otlp_export = telemetry.exporting(),
+        api_keys = keys.len(),
         "Runtime configuration initialized"
     );
 
+    let job_store_path = config
+        .job_store_path
+        .clone()
+        .unwrap_or_else(|| config.export_root.join(JOB_STORE_FILE_NAME));
+    let job_store = match JobStore::open(&job_store_path) {
+        Ok(store) => {
+            info!(path = %job_store_path.display(), "Job store opened");
+            store
+        }
+        Err(err) => {
+            warn!(
+                error = %err,
+                path = %job_store_path.display(),
+                "Failed to open job store; jobs will not survive restarts"
+            );
+            JobStore::open_in_memory()?
+        }
+    };
+
+    let embedding_cache = config.open_embedding_cache().unwrap_or_else(|err| {
+        warn!(error = %err, "Failed to open embedding cache; exports will not reuse embeddings");
+        None
+    });
+
+    let rate_limit_backend = config.open_rate_limit_backend().unwrap_or_else(|err| {
+        warn!(
+            error = %err,
+            "Failed to open shared rate limit backend; limits apply per replica"
+        );
+        Arc::new(MemoryRateLimitBackend::default())
+    });
+    info!(
+        backend = rate_limit_backend.name(),
+        "Rate limit backend ready"
+    );
+
+    let http = HttpClient::from_config(&config)?;
+    let artifacts = config.open_artifact_store(http.clone())?;
+    info!(backend = artifacts.name(), "Artifact store ready");
+
     let (queue_tx, queue_rx) = mpsc::channel(config.queue_capacity);
     let state = AppState {
         config: config.clone(),
         jobs: Arc::new(RwLock::new(HashMap::new())),
         event_buses: Arc::new(RwLock::new(HashMap::new())),
         queue_tx,
-        mcp_indexes: Arc::new(RwLock::new(HashMap::new())),
+        job_store,
+        export_limits: Arc::new(ExportLimits::from_config(&config)),
+        job_cancellations: JobCancellations::default(),
+        http,
+        embedding_cache,
+        artifacts,
+        mcp_indexes: Arc::new(tokio::sync::Mutex::new(new_index_cache(
+            config.mcp_index_cache_max_entries,
+            config.mcp_index_cache_max_bytes,
+        ))),
         mcp_cache: Arc::new(tokio::sync::Mutex::new(new_query_cache(
             config.mcp_cache_capacity,
         ))),
         rate_limiter: Arc::new(RateLimiter::new(
+            "mcp",
             config.mcp_rate_limit_per_minute,
             config.mcp_rate_limit_burst,
+            Arc::clone(&rate_limit_backend),
         )),
+        export_rate_limiter: Arc::new(RateLimiter::new(
+            "export",
+            config.export_rate_limit_per_minute,
+            config.export_rate_limit_burst,
+            rate_limit_backend,
+        )),
+        keys: Arc::new(keys),
+        shutdown: Shutdown::default(),
     };
 
+    let restored_queue = queue::restore_persisted_jobs(&state)
+        .await
+        .unwrap_or_else(|err| {
+            warn!(error = %err, "Failed to restore persisted export jobs");
+            Vec::new()
+        });
+
     queue::spawn_export_worker(state.clone(), queue_rx);
+    if !restored_queue.is_empty() {
+        let queue_tx = state.queue_tx.clone();
+        tokio::spawn(async move {
+            for job_id in restored_queue {
+                if queue_tx.send(job_id).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
     queue::spawn_cleanup_worker(state.clone());
+    let drain = tokio::spawn(shutdown::drain_on_signal(
+        state.clone(),
+        Duration::from_secs(config.shutdown_grace_seconds),
+    ));
+    let server_shutdown = state.shutdown.clone();
 
     let app = Router::new()
         .route("/healthz", get(api::healthz))
+        .route("/readyz", get(readiness::readyz))
+        .route("/metrics", get(metrics::metrics_endpoint))
         .route("/mcp", post(mcp_api::mcp))
         .route("/v1/exports", post(api::create_export))
         .route(
@@ -178,6 +305,31 @@ async fn main() -> Result<()> {
             get(api::stream_export_events),
         )
         .route("/v1/exports/{job_id}/download", get(api::download_export))
+        .route(
+            "/v1/exports/{job_id}/download-url",
+            post(api::create_download_url),
+        )
+        .route(
+            "/v1/exports/{job_id}/download/sidecar",
+            get(api::download_export_sidecar),
+        )
+        .route(
+            "/v1/exports/{job_id}/download/bundle",
+            get(api::download_export_bundle),
+        )
+        .route("/v1/uploads", post(uploads::create_upload))
+        .route(
+            "/v1/uploads/{upload_id}",
+            get(uploads::get_upload).delete(uploads::delete_upload),
+        )
+        .route(
+            "/v1/uploads/{upload_id}/commit",
+            post(uploads::commit_upload),
+        )
+        .route(
+            "/v1/uploads/{upload_id}/{kind}/{part}",
+            put(uploads::put_upload_part),
+        )
         .layer(DefaultBodyLimit::max(MAX_EXPORT_BODY_BYTES))
         .layer(
             CorsLayer::new()
@@ -185,12 +337,40 @@ async fn main() -> Result<()> {
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
-        .layer(TraceLayer::new_for_http())
+        .layer(TraceLayer::new_for_http().make_span_with(http_request_span))
         .with_state(state);
 
     info!(bind_addr = %config.bind_addr, "Startup: binding TCP listener");
     let listener = tokio::net::TcpListener::bind(config.bind_addr).await?;
     info!("memvid-export-api listening on {}", config.bind_addr);
-    axum::serve(listener, app).await?;
+    // HTTP keeps serving while jobs drain, so clients can still poll status.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move { server_shutdown.stopping().await })
+    .await?;
+
+    let checkpointed = drain.await.unwrap_or_default();
+    info!(checkpointed, "memvid-export-api stopped");
+    telemetry.shutdown();
     Ok(())
 }
+
+/// Request span at INFO so it survives the default log filter, continuing
+/// the caller's trace when a `traceparent` header is sent.
+fn http_request_span(request: &Request<Body>) -> Span {
+    let span = info_span!(
+        "http.request",
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    telemetry::set_parent(
+        &span,
+        request
+            .headers()
+            .get("traceparent")
+            .and_then(|value| value.to_str().ok()),
+    );
+    span
+}
@@ -9,24 +9,29 @@ use std::{
 use axum::{
     body::Body,
     extract::State,
-    http::{HeaderMap, HeaderValue, Response, StatusCode},
+    http::{HeaderMap, Response, StatusCode},
     response::IntoResponse,
     Json,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::task;
-use tracing::debug;
+use tracing::{debug, warn, Span};
 use uuid::Uuid;
 
 use crate::{
-    auth::extract_bearer_token,
+    artifact_store::ensure_local_artifact,
+    auth::{authorize, ApiKey, Scope},
     mcp_index::{
-        build_from_capsule, load_from_sidecar, persist_to_sidecar, sidecar_path_for_capsule,
-        CapsuleIndex, MCP_SCHEMA_VERSION,
+        build_from_capsule, fts_match_expression, load_from_sidecar, persist_to_sidecar,
+        search_fulltext, sidecar_path_for_capsule, upgrade_sidecar, CapsuleIndex, FulltextHit,
+        MAX_FULLTEXT_CANDIDATES, MCP_SCHEMA_VERSION,
     },
+    mcp_semantic::{self, SemanticIndex},
+    metrics::metrics,
     models::JobState,
-    AppState,
+    rate_limit::attach_rate_headers,
+    telemetry, AppState,
 };
 
 #[derive(Debug, Deserialize)]
@@ -228,6 +233,114 @@ pub fn new_query_cache(capacity: usize) -> QueryCache {
     QueryCache::with_capacity(capacity)
 }
 
+#[derive(Debug, Clone)]
+enum CachedIndex {
+    Graph(Arc<CapsuleIndex>),
+    /// `None` marks a capsule already found to have no embeddings.
+    Semantic(Option<Arc<SemanticIndex>>),
+}
+
+#[derive(Debug)]
+struct IndexCacheEntry {
+    value: CachedIndex,
+    bytes: usize,
+    last_used: u64,
+}
+
+/// Loaded capsule indexes, bounded by entry count and approximate heap
+/// bytes. The least recently used entry is evicted first; requests already
+/// holding an index keep it alive through its `Arc`.
+#[derive(Debug, Default)]
+pub struct IndexCache {
+    max_entries: usize,
+    max_bytes: usize,
+    used_bytes: usize,
+    tick: u64,
+    entries: HashMap<String, IndexCacheEntry>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IndexCacheStats {
+    pub graph: usize,
+    pub semantic: usize,
+    pub bytes: usize,
+}
+
+impl IndexCache {
+    pub fn stats(&self) -> IndexCacheStats {
+        let mut stats = IndexCacheStats {
+            bytes: self.used_bytes,
+            ..IndexCacheStats::default()
+        };
+        for entry in self.entries.values() {
+            match entry.value {
+                CachedIndex::Graph(_) => stats.graph += 1,
+                CachedIndex::Semantic(_) => stats.semantic += 1,
+            }
+        }
+        stats
+    }
+
+    fn with_limits(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            max_entries: max_entries.max(1),
+            max_bytes,
+            ..Self::default()
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<CachedIndex> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.last_used = self.tick;
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: String, value: CachedIndex, bytes: usize) {
+        self.tick += 1;
+        if let Some(old) = self.entries.remove(&key) {
+            self.used_bytes -= old.bytes;
+        }
+        self.used_bytes += bytes;
+        self.entries.insert(
+            key.clone(),
+            IndexCacheEntry {
+                value,
+                bytes,
+                last_used: self.tick,
+            },
+        );
+
+        // The newest entry always stays, even when it alone exceeds the
+        // byte budget; otherwise it would be reloaded on every call.
+        while self.entries.len() > 1
+            && (self.entries.len() > self.max_entries || self.used_bytes > self.max_bytes)
+        {
+            let Some(victim) = self
+                .entries
+                .iter()
+                .filter(|(k, _)| **k != key)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&victim) {
+                self.used_bytes -= evicted.bytes;
+                debug!(
+                    index = %victim,
+                    bytes = evicted.bytes,
+                    "Evicted capsule index from cache"
+                );
+            }
+        }
+    }
+}
+
+pub fn new_index_cache(max_entries: usize, max_bytes: usize) -> IndexCache {
+    IndexCache::with_limits(max_entries, max_bytes)
+}
+
 #[derive(Debug, Clone)]
 struct ToolContext {
     trace_id: String,
@@ -266,25 +379,22 @@ pub async fn mcp(
         );
     }
 
-    let token = match extract_bearer_token(&headers) {
-        Ok(token) => token,
+    let key = match authorize(&state.keys, &headers, Scope::McpRead) {
+        Ok(key) => key,
         Err((status, payload)) => {
-            return (status, payload).into_response();
+            let error = &payload.0["error"];
+            return error_response(
+                request_id,
+                status,
+                error["code"].as_str().unwrap_or("UNAUTHORIZED"),
+                error["message"].as_str().unwrap_or("Unauthorized"),
+                json!({"retryable": false}),
+                None,
+            );
         }
     };
 
-    if token.trim() != state.config.api_key {
-        return error_response(
-            request_id,
-            StatusCode::UNAUTHORIZED,
-            "UNAUTHORIZED",
-            "Invalid API key",
-            json!({"retryable": false}),
-            None,
-        );
-    }
-
-    let rate = state.rate_limiter.check(token.trim()).await;
+    let rate = state.rate_limiter.check(&key.id).await;
     if !rate.allowed {
         let retry_after = rate.headers.reset_seconds.saturating_mul(1000);
         let err = ToolError::rate_limited("Rate limit exceeded", retry_after);
@@ -355,11 +465,15 @@ pub async fn mcp(
             };
 
             let ctx = ToolContext {
-                trace_id: Uuid::new_v4().to_string(),
+                // The request span's trace id, so clients can look the call up
+                // in the tracing backend.
+                trace_id: telemetry::trace_id(&Span::current())
+                    .unwrap_or_else(|| Uuid::new_v4().simple().to_string()),
                 start: Instant::now(),
             };
 
-            let output = run_tool(&state, &ctx, &params.name, &params.arguments).await;
+            let output = run_tool(&state, &key, &ctx, &params.name, &params.arguments).await;
+            let tool_label = metric_tool_label(&params.name);
             match output {
                 Ok((result, pagination, confidence)) => {
                     let elapsed = ctx.start.elapsed().as_millis();
@@ -386,6 +500,7 @@ pub async fn mcp(
                             "Response size {} bytes exceeds budget {} bytes. Reduce limit or use pagination.",
                             bytes, state.config.mcp_response_budget_bytes
                         ));
+                        metrics().mcp_call(tool_label, err.code);
                         let err_data = err.to_data(&ctx.trace_id);
                         jsonrpc_error(
                             request_id,
@@ -396,6 +511,7 @@ pub async fn mcp(
                             Some(rate.headers.clone()),
                         )
                     } else {
+                        metrics().mcp_call(tool_label, "ok");
                         if state.config.mcp_dev_log_payloads {
                             debug!(trace_id = %ctx.trace_id, tool = %params.name, response_bytes = bytes, "MCP tool response payload");
                         }
@@ -408,6 +524,7 @@ pub async fn mcp(
                     }
                 }
                 Err(err) => {
+                    metrics().mcp_call(tool_label, err.code);
                     let code = match err.code {
                         "INVALID_ARGUMENT" => -32602,
                         "NOT_FOUND" => -32004,
@@ -418,7 +535,7 @@ pub async fn mcp(
                         "TIMEOUT" => -32008,
                         _ => -32603,
                     };
-                    let err_data = err.to_data("tool-error");
+                    let err_data = err.to_data(&ctx.trace_id);
 
                     jsonrpc_error(
                         request_id,
@@ -450,12 +567,13 @@ pub async fn mcp(
 
 async fn run_tool(
     state: &AppState,
+    key: &ApiKey,
     ctx: &ToolContext,
     tool: &str,
     args: &Value,
 ) -> Result<(Value, PaginatedResult, Value), ToolError> {
     let locator = parse_locator(args)?;
-    let capsule_path = resolve_capsule_path(state, &locator).await?;
+    let capsule_path = resolve_capsule_path(state, key, &locator).await?;
     let index = get_or_load_index(state, &capsule_path).await?;
 
     let cache_key = format!(
@@ -465,7 +583,9 @@ async fn run_tool(
         serde_json::to_string(args).unwrap_or_else(|_| "{}".to_string())
     );
 
-    if let Some(cached) = state.mcp_cache.lock().await.get(&cache_key) {
+    let cached = state.mcp_cache.lock().await.get(&cache_key);
+    metrics().query_cache(cached.is_some());
+    if let Some(cached) = cached {
         let pagination = PaginatedResult {
             items: cached
                 .get("result")
@@ -502,7 +622,17 @@ async fn run_tool(
         "node_get" => tool_node_get(&index, args),
         "neighbors_get" => tool_neighbors_get(&index, args),
         "edge_get" => tool_edge_get(&index, args),
-        "text_search" => tool_text_search(&index, args),
+        "text_search" => tool_text_search(state, &capsule_path, &index, args, None).await,
+        "semantic_search" => {
+            tool_text_search(
+                state,
+                &capsule_path,
+                &index,
+                args,
+                Some(SearchMode::Semantic),
+            )
+            .await
+        }
         "call_trace" => tool_call_trace(&index, args),
         "callers_of" => tool_callers_of(&index, args),
         "callees_of" => tool_callees_of(&index, args),
@@ -549,30 +679,40 @@ fn parse_locator(args: &Value) -> Result<LocatorArgs, ToolError> {
 
 async fn resolve_capsule_path(
     state: &AppState,
+    key: &ApiKey,
     locator: &LocatorArgs,
 ) -> Result<PathBuf, ToolError> {
     if let Some(job_id) = &locator.job_id {
         let jobs = state.jobs.read().await;
-        let Some(job) = jobs.get(job_id) else {
+        let Some(job) = jobs
+            .get(job_id)
+            .filter(|job| key.can_access(job.owner_key_id.as_deref()))
+        else {
             return Err(ToolError::not_found(format!("Unknown jobId: {job_id}")));
         };
 
-        let Some(path) = &job.artifact_path else {
+        let Some(path) = job.artifact_path.clone() else {
             return Err(ToolError::not_found(format!(
                 "Job {job_id} has no available artifact path"
             )));
         };
+        drop(jobs);
 
-        if !path.exists() {
+        if !local_artifact_available(state, &path).await {
             return Err(ToolError::not_found(format!(
-                "Artifact not found on disk for job {job_id}"
+                "Artifact not found for job {job_id}"
             )));
         }
 
-        return Ok(path.clone());
+        return Ok(path);
     }
 
     if let Some(capsule_path) = &locator.capsule_path {
+        if !key.is_admin() {
+            return Err(ToolError::invalid_argument(
+                "locator.capsulePath requires an admin API key; use locator.jobId",
+            ));
+        }
         let path = PathBuf::from(capsule_path);
         let resolved = if path.is_absolute() {
             path
@@ -609,6 +749,7 @@ async fn resolve_capsule_path(
     let latest = jobs
         .values()
         .filter(|job| matches!(job.status, JobState::Completed))
+        .filter(|job| key.can_access(job.owner_key_id.as_deref()))
         .max_by_key(|job| job.updated_at);
 
     let Some(job) = latest else {
@@ -617,62 +758,125 @@ async fn resolve_capsule_path(
         ));
     };
 
-    let Some(path) = &job.artifact_path else {
+    let Some(path) = job.artifact_path.clone() else {
         return Err(ToolError::not_found(
             "Latest completed export is missing artifact path",
         ));
     };
+    drop(jobs);
 
-    if !path.exists() {
+    if !local_artifact_available(state, &path).await {
         return Err(ToolError::not_found(format!(
-            "Latest artifact missing on disk: {}",
+            "Latest artifact missing: {}",
             path.display()
         )));
     }
 
-    Ok(path.clone())
+    Ok(path)
+}
+
+/// Whether the capsule is on local disk, fetching it from the artifact store
+/// first when another replica wrote it.
+async fn local_artifact_available(state: &AppState, path: &Path) -> bool {
+    match ensure_local_artifact(state.artifacts.as_ref(), path).await {
+        Ok(found) => found,
+        Err(err) => {
+            warn!(path = %path.display(), "Failed to fetch artifact from store: {err:#}");
+            false
+        }
+    }
 }
 
 async fn get_or_load_index(
     state: &AppState,
     capsule_path: &Path,
 ) -> Result<Arc<CapsuleIndex>, ToolError> {
-    let key = capsule_path.display().to_string();
-    {
-        let guard = state.mcp_indexes.read().await;
-        if let Some(index) = guard.get(&key) {
-            return Ok(index.clone());
-        }
+    let key = format!("graph|{}", capsule_path.display());
+    if let Some(CachedIndex::Graph(index)) = state.mcp_indexes.lock().await.get(&key) {
+        return Ok(index);
     }
 
     let capsule = capsule_path.to_path_buf();
-    let loaded = task::spawn_blocking(move || -> anyhow::Result<CapsuleIndex> {
-        let sidecar_path = sidecar_path_for_capsule(&capsule);
-        if sidecar_path.exists() {
-            return match load_from_sidecar(&capsule) {
-                Ok(index) => Ok(index),
-                Err(_) => {
-                    let index = build_from_capsule(&capsule)?;
-                    persist_to_sidecar(&index)?;
-                    Ok(index)
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    let sidecar = sidecar_path.clone();
+    let loaded = task::spawn_blocking(move || -> anyhow::Result<(CapsuleIndex, usize, bool)> {
+        let mut rewritten = false;
+        let loaded = if sidecar.exists() {
+            match upgrade_sidecar(&capsule) {
+                Ok(upgraded) => {
+                    rewritten = upgraded;
+                    load_from_sidecar(&capsule).ok()
                 }
-            };
-        }
-
-        let index = build_from_capsule(&capsule)?;
-        persist_to_sidecar(&index)?;
-        Ok(index)
+                Err(err) => {
+                    warn!("Rebuilding capsule index: {err:#}");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let index = match loaded {
+            Some(index) => index,
+            None => {
+                let index = build_from_capsule(&capsule)?;
+                persist_to_sidecar(&index)?;
+                rewritten = true;
+                index
+            }
+        };
+        let bytes = index.approx_heap_bytes();
+        Ok((index, bytes, rewritten))
     })
     .await
     .map_err(|_| ToolError::timeout("Timed out while loading capsule index"))?
     .map_err(|err| ToolError::incompatible(format!("Failed loading capsule index: {err:#}")))?;
 
-    let index = Arc::new(loaded);
-    let mut guard = state.mcp_indexes.write().await;
-    guard.insert(key, index.clone());
+    let (index, bytes, rewritten) = loaded;
+    // Other replicas and bundles read the stored copy, not this disk.
+    if rewritten {
+        if let Err(err) = state.artifacts.put(&sidecar_path).await {
+            warn!(path = %sidecar_path.display(), "Failed to republish sidecar: {err:#}");
+        }
+    }
+    let index = Arc::new(index);
+    state
+        .mcp_indexes
+        .lock()
+        .await
+        .insert(key, CachedIndex::Graph(index.clone()), bytes);
     Ok(index)
 }
 
+async fn get_or_load_semantic_index(
+    state: &AppState,
+    capsule_path: &Path,
+) -> Result<Option<Arc<SemanticIndex>>, ToolError> {
+    let key = format!("semantic|{}", capsule_path.display());
+    if let Some(CachedIndex::Semantic(index)) = state.mcp_indexes.lock().await.get(&key) {
+        return Ok(index);
+    }
+
+    let capsule = capsule_path.to_path_buf();
+    let loaded = task::spawn_blocking(move || SemanticIndex::load(&capsule))
+        .await
+        .map_err(|_| ToolError::timeout("Timed out while loading capsule embeddings"))?
+        .map_err(|err| {
+            ToolError::incompatible(format!("Failed loading capsule embeddings: {err:#}"))
+        })?
+        .map(Arc::new);
+
+    let bytes = loaded
+        .as_ref()
+        .map(|index| index.approx_heap_bytes())
+        .unwrap_or(0);
+    state
+        .mcp_indexes
+        .lock()
+        .await
+        .insert(key, CachedIndex::Semantic(loaded.clone()), bytes);
+    Ok(loaded)
+}
+
 fn parse_limit(args: &Value, default_limit: usize, max_limit: usize) -> usize {
     args.get("limit")
         .and_then(Value::as_u64)
@@ -1086,9 +1290,48 @@ fn lexical_score(text: &str, normalized_terms: &[String]) -> f64 {
     }
 }
 
-fn tool_text_search(
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchMode {
+    /// Lexical, falling back to hybrid ranking when the best lexical match
+    /// is below `LEXICAL_CONFIDENCE_THRESHOLD`.
+    Auto,
+    Lexical,
+    Semantic,
+    Hybrid,
+}
+
+impl SearchMode {
+    fn parse(args: &Value) -> Result<Self, ToolError> {
+        match args.get("mode").and_then(Value::as_str) {
+            None | Some("auto") => Ok(Self::Auto),
+            Some("lexical") => Ok(Self::Lexical),
+            Some("semantic") => Ok(Self::Semantic),
+            Some("hybrid") => Ok(Self::Hybrid),
+            Some(other) => Err(ToolError::invalid_argument(format!(
+                "Unsupported mode `{other}`. Supported: auto, lexical, semantic, hybrid"
+            ))),
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Lexical => "lexical",
+            Self::Semantic => "semantic",
+            Self::Hybrid => "hybrid",
+        }
+    }
+}
+
+const LEXICAL_CONFIDENCE_THRESHOLD: f64 = 0.6;
+const DEFAULT_SEMANTIC_WEIGHT: f64 = 0.5;
+
+async fn tool_text_search(
+    state: &AppState,
+    capsule_path: &Path,
     index: &CapsuleIndex,
     args: &Value,
+    fixed_mode: Option<SearchMode>,
 ) -> Result<(Value, PaginatedResult, Value), ToolError> {
     let query = require_str(args, "query")?;
     let scope = args
@@ -1097,82 +1340,268 @@ fn tool_text_search(
         .map(ToString::to_string);
     let limit = parse_limit(args, 25, 150);
     let cursor = parse_cursor(args);
+    let mode = match fixed_mode {
+        Some(mode) => mode,
+        None => SearchMode::parse(args)?,
+    };
+    let semantic_weight = args
+        .get("semanticWeight")
+        .and_then(Value::as_f64)
+        .unwrap_or(DEFAULT_SEMANTIC_WEIGHT)
+        .clamp(0.0, 1.0);
+
+    let match_expression = fts_match_expression(&query);
+    if match_expression.is_none() && mode != SearchMode::Semantic {
+        return Err(ToolError::invalid_argument(
+            "query contains no searchable terms",
+        ));
+    }
+
+    let hits = match match_expression {
+        Some(expression) if mode != SearchMode::Semantic => {
+            let sidecar_path = index.sidecar_path.clone();
+            let scope = scope.clone();
+            task::spawn_blocking(move || {
+                search_fulltext(
+                    &sidecar_path,
+                    &expression,
+                    scope.as_deref(),
+                    MAX_FULLTEXT_CANDIDATES,
+                )
+            })
+            .await
+            .map_err(|_| ToolError::timeout("Timed out while searching the full-text index"))?
+            .map_err(|err| ToolError::incompatible(format!("Full-text search failed: {err:#}")))?
+        }
+        _ => Vec::new(),
+    };
 
+    // BM25 is unbounded, so lexical scores are relative to the best hit and
+    // the semantic fallback is decided by how many query terms that hit
+    // actually covers.
+    let top_bm25 = hits.first().map(|hit| hit.bm25).unwrap_or(0.0);
     let normalized_terms = normalize_text(&query)
         .split_whitespace()
         .map(ToString::to_string)
         .collect::<Vec<_>>();
+    let top_coverage = hits
+        .first()
+        .and_then(|hit| index.fulltext_by_uri.get(&hit.uri))
+        .map(|idx| lexical_score(&index.fulltext[*idx].text, &normalized_terms))
+        .unwrap_or(0.0);
+    let relative_bm25 = |hit: &FulltextHit| {
+        if top_bm25 > 0.0 {
+            (hit.bm25 / top_bm25).clamp(0.0, 1.0)
+        } else {
+            1.0
+        }
+    };
 
-    if normalized_terms.is_empty() {
-        return Err(ToolError::invalid_argument(
-            "query contains no searchable terms",
-        ));
-    }
+    let wants_semantic = match mode {
+        SearchMode::Lexical => false,
+        SearchMode::Semantic | SearchMode::Hybrid => true,
+        SearchMode::Auto => top_coverage < LEXICAL_CONFIDENCE_THRESHOLD,
+    };
 
-    let mut rows = Vec::new();
-    for entry in &index.fulltext {
-        if let Some(scope) = &scope {
-            let scope_lower = scope.to_ascii_lowercase();
-            let uri_ok = entry.uri.to_ascii_lowercase().contains(&scope_lower);
-            let track_ok = entry.track.to_ascii_lowercase().contains(&scope_lower);
-            if !uri_ok && !track_ok {
-                continue;
+    let mut warnings = Vec::new();
+    let semantic = if wants_semantic {
+        match semantic_scores(state, capsule_path, &query).await {
+            Ok(semantic) => Some(semantic),
+            Err(err) if mode == SearchMode::Auto => {
+                warn!(
+                    code = err.code,
+                    "Semantic fallback unavailable: {}", err.message
+                );
+                warnings.push("semantic_fallback_unavailable");
+                None
             }
+            Err(err) => return Err(err),
         }
+    } else {
+        None
+    };
 
-        let score = lexical_score(&entry.text, &normalized_terms);
-        if score <= 0.0 {
-            continue;
-        }
+    let mut rows = Vec::new();
+    if let Some(semantic) = &semantic {
+        let scope_lower = scope.as_ref().map(|scope| scope.to_lowercase());
+        let hits_by_key = hits
+            .iter()
+            .map(|hit| (format!("{}::{}", hit.uri, hit.ref_id), hit))
+            .collect::<HashMap<_, _>>();
+
+        for entry in &index.fulltext {
+            if let Some(scope) = &scope_lower {
+                if !entry.uri.to_lowercase().contains(scope)
+                    && !entry.track.to_lowercase().contains(scope)
+                {
+                    continue;
+                }
+            }
 
-        let preview = if entry.text.chars().count() > 260 {
-            format!("{}...", entry.text.chars().take(260).collect::<String>())
-        } else {
-            entry.text.clone()
-        };
+            let key = format!("{}::{}", entry.uri, entry.ref_id);
+            let hit = hits_by_key.get(&key).copied();
+            let lexical = hit.map(relative_bm25).unwrap_or(0.0);
+            let similarity = semantic.scores.get(&entry.uri).copied();
+            let score = if mode == SearchMode::Semantic {
+                similarity.unwrap_or(0.0).max(0.0)
+            } else {
+                mcp_semantic::hybrid_score(lexical, similarity.unwrap_or(0.0), semantic_weight)
+            };
+            if score <= 0.0 {
+                continue;
+            }
 
-        rows.push(RankedItem {
-            score,
-            key: format!("{}::{}", entry.uri, entry.ref_id),
-            payload: json!({
-                "refKind": entry.ref_kind,
-                "refId": entry.ref_id,
-                "uri": entry.uri,
-                "track": entry.track,
-                "score": score,
-                "preview": preview,
-            }),
-        });
+            let (preview, highlights) = match hit {
+                Some(hit) => (hit.snippet.clone(), highlight_spans(hit)),
+                None => (preview_text(&entry.text), Vec::new()),
+            };
+            rows.push(RankedItem {
+                score,
+                key,
+                payload: json!({
+                    "refKind": entry.ref_kind,
+                    "refId": entry.ref_id,
+                    "uri": entry.uri,
+                    "track": entry.track,
+                    "score": score,
+                    "lexicalScore": lexical,
+                    "semanticScore": similarity,
+                    "preview": preview,
+                    "highlights": highlights,
+                }),
+            });
+        }
+    } else {
+        for hit in &hits {
+            let score = relative_bm25(hit);
+            rows.push(RankedItem {
+                score,
+                key: format!("{}::{}", hit.uri, hit.ref_id),
+                payload: json!({
+                    "refKind": hit.ref_kind,
+                    "refId": hit.ref_id,
+                    "uri": hit.uri,
+                    "track": hit.track,
+                    "score": score,
+                    "bm25": hit.bm25,
+                    "preview": hit.snippet,
+                    "highlights": highlight_spans(hit),
+                }),
+            });
+        }
     }
 
     let pagination = paginate_ranked(rows, limit, cursor);
-    let result = json!({
+    let semantic_used = semantic.is_some();
+    let mut result = json!({
         "query": query,
         "scope": scope,
+        "mode": mode.as_str(),
         "items": pagination.items,
-        "semanticUsed": false,
+        "semanticUsed": semantic_used,
     });
+    if let Some(semantic) = &semantic {
+        result["embedding"] = json!({
+            "provider": semantic.index.provider,
+            "model": semantic.index.model,
+            "dimension": semantic.index.dimension,
+            "frames": semantic.index.frame_count(),
+        });
+        if mode != SearchMode::Semantic {
+            result["semanticWeight"] = json!(semantic_weight);
+        }
+    }
+
+    let mut factors = Vec::new();
+    if mode != SearchMode::Semantic {
+        factors.push("bm25_lexical_match");
+    }
+    factors.push("deterministic_scoring");
+    match (mode, semantic_used) {
+        (SearchMode::Lexical, _) => factors.push("semantic_disabled"),
+        (SearchMode::Auto, false) if warnings.is_empty() => {
+            factors.push("semantic_fallback_not_needed")
+        }
+        (SearchMode::Auto, true) => factors.push("semantic_fallback_low_lexical_confidence"),
+        (_, true) => factors.push("vector_similarity"),
+        (_, false) => {}
+    }
 
     let confidence_score = if pagination.items.is_empty() {
         0.3
+    } else if semantic_used && top_coverage < LEXICAL_CONFIDENCE_THRESHOLD {
+        0.7
     } else {
         0.86
     };
     Ok((
         result,
         pagination,
-        confidence_block(
-            confidence_score,
-            vec![
-                "lexical_match",
-                "deterministic_scoring",
-                "semantic_fallback_disabled",
-            ],
-            Vec::new(),
-        ),
+        confidence_block(confidence_score, factors, warnings),
     ))
 }
 
+fn preview_text(text: &str) -> String {
+    if text.chars().count() > 260 {
+        format!("{}...", text.chars().take(260).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn highlight_spans(hit: &FulltextHit) -> Vec<Value> {
+    hit.highlights
+        .iter()
+        .map(|(start, end)| json!({ "start": start, "end": end }))
+        .collect()
+}
+
+struct SemanticScores {
+    index: Arc<SemanticIndex>,
+    scores: HashMap<String, f64>,
+}
+
+async fn semantic_scores(
+    state: &AppState,
+    capsule_path: &Path,
+    query: &str,
+) -> Result<SemanticScores, ToolError> {
+    let Some(index) = get_or_load_semantic_index(state, capsule_path).await? else {
+        return Err(ToolError::incompatible(
+            "Capsule has no frame embeddings; re-export with semanticEnabled to use semantic search",
+        ));
+    };
+
+    let runtime = state
+        .config
+        .query_embedding_config(
+            state.http.clone(),
+            &index.provider,
+            &index.model,
+            index.dimension,
+        )
+        .map_err(|err| {
+            ToolError::incompatible(format!(
+                "Cannot embed queries for {}/{}: {err:#}",
+                index.provider, index.model
+            ))
+        })?;
+    let embedding = runtime
+        .embed_texts_async(&[query])
+        .await
+        .map_err(|err| ToolError::internal(format!("Failed embedding query: {err:#}")))?
+        .pop()
+        .ok_or_else(|| ToolError::internal("Embedding provider returned no vectors"))?;
+
+    let scores = index
+        .similarities(embedding)
+        .map_err(|err| ToolError::incompatible(format!("{err:#}")))?
+        .into_iter()
+        .map(|(uri, score)| (uri.to_string(), score))
+        .collect();
+    Ok(SemanticScores { index, scores })
+}
+
 fn tool_call_trace(
     index: &CapsuleIndex,
     args: &Value,
@@ -1884,6 +2313,7 @@ fn tool_query_explain(
             "graph_expansion_rerank",
             "semantic_fallback_if_low_confidence"
         ],
+        "semanticFallbackThreshold": LEXICAL_CONFIDENCE_THRESHOLD,
         "rankingSignals": [
             "graph_structural_confidence",
             "lexical_relevance",
@@ -1913,6 +2343,36 @@ fn tool_query_explain(
     ))
 }
 
+const TOOL_NAMES: &[&str] = &[
+    "symbol_lookup",
+    "node_get",
+    "neighbors_get",
+    "edge_get",
+    "text_search",
+    "semantic_search",
+    "call_trace",
+    "callers_of",
+    "callees_of",
+    "process_list",
+    "process_get",
+    "impact_analysis",
+    "file_outline",
+    "file_snippet",
+    "community_list",
+    "manifest_get",
+    "query_explain",
+];
+
+/// Metric label for a requested tool. Names come from the client, so anything
+/// outside [`TOOL_NAMES`] shares one label to keep the series bounded.
+fn metric_tool_label(name: &str) -> &str {
+    TOOL_NAMES
+        .iter()
+        .find(|tool| **tool == name)
+        .copied()
+        .unwrap_or("unknown")
+}
+
 fn tool_definitions() -> Vec<Value> {
     vec![
         tool_def(
@@ -1937,7 +2397,12 @@ fn tool_definitions() -> Vec<Value> {
         ),
         tool_def(
             "text_search",
-            "Lexical text search over indexed frames",
+            "Text search over indexed frames; lexical by default with semantic fallback on low lexical confidence",
+            json!({"type":"object","required":["query"],"properties":{"query":{"type":"string"},"scope":{"type":"string"},"mode":{"type":"string","enum":["auto","lexical","semantic","hybrid"]},"semanticWeight":{"type":"number","minimum":0,"maximum":1},"limit":{"type":"integer"},"cursor":{"type":"string"},"locator":{"type":"object"}}}),
+        ),
+        tool_def(
+            "semantic_search",
+            "Vector search over frame embeddings using the capsule's embedding provider and model",
             json!({"type":"object","required":["query"],"properties":{"query":{"type":"string"},"scope":{"type":"string"},"limit":{"type":"integer"},"cursor":{"type":"string"},"locator":{"type":"object"}}}),
         ),
         tool_def(
@@ -2091,30 +2556,89 @@ fn error_response(
     )
 }
 
-fn attach_rate_headers(
-    mut builder: axum::http::response::Builder,
-    headers: crate::rate_limit::RateLimitHeaders,
-) -> axum::http::response::Builder {
-    builder = builder.header(
-        "X-RateLimit-Limit",
-        HeaderValue::from_str(&headers.limit.to_string())
-            .unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    builder = builder.header(
-        "X-RateLimit-Remaining",
-        HeaderValue::from_str(&headers.remaining.to_string())
-            .unwrap_or_else(|_| HeaderValue::from_static("0")),
-    );
-    builder.header(
-        "X-RateLimit-Reset",
-        HeaderValue::from_str(&headers.reset_seconds.to_string())
-            .unwrap_or_else(|_| HeaderValue::from_static("0")),
-    )
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{decode_cursor, encode_cursor, normalize_text};
+    use super::{
+        decode_cursor, encode_cursor, get_or_load_index, metric_tool_label, normalize_text,
+        tool_definitions, CachedIndex, IndexCache, TOOL_NAMES,
+    };
+    use crate::{
+        artifact_store::{ArtifactStore, StoreFuture},
+        mcp_index::{build_and_persist_from_request, sidecar_path_for_capsule},
+        test_support::{sample_request, test_config, test_state, TempDir},
+        transform::build_frame_documents,
+    };
+    use rusqlite::Connection;
+    use std::{
+        path::{Path, PathBuf},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// Store that only records which paths were put.
+    #[derive(Default)]
+    struct RecordingStore(Mutex<Vec<PathBuf>>);
+
+    impl ArtifactStore for RecordingStore {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn put<'a>(&'a self, path: &'a Path) -> StoreFuture<'a, ()> {
+            self.0.lock().unwrap().push(path.to_path_buf());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn fetch<'a>(&'a self, _path: &'a Path) -> StoreFuture<'a, bool> {
+            Box::pin(async { Ok(false) })
+        }
+
+        fn delete<'a>(&'a self, _path: &'a Path) -> StoreFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn presigned_get(
+            &self,
+            _path: &Path,
+            _file_name: &str,
+            _ttl: Duration,
+        ) -> anyhow::Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn presigned_put(&self, _path: &Path, _ttl: Duration) -> anyhow::Result<Option<String>> {
+            Ok(None)
+        }
+
+        fn probe(&self) -> StoreFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn upgraded_sidecars_are_republished() {
+        let dir = TempDir::new("mcp-sidecar-upgrade");
+        let (mut state, _rx) = test_state(test_config(dir.path()));
+        let store = Arc::new(RecordingStore::default());
+        state.artifacts = store.clone();
+
+        let capsule = dir.path().join("demo.mv2");
+        let request = sample_request();
+        build_and_persist_from_request(&request, &build_frame_documents(&request), &capsule)
+            .unwrap();
+        let sidecar = sidecar_path_for_capsule(&capsule);
+        Connection::open(&sidecar)
+            .unwrap()
+            .execute_batch(
+                "DROP TABLE fulltext_fts;
+                 UPDATE meta SET value='gitnexus.mcp.index.v1' WHERE key='index_schema_version';",
+            )
+            .unwrap();
+
+        let index = get_or_load_index(&state, &capsule).await.unwrap();
+        assert_eq!(index.nodes.len(), 2);
+        assert_eq!(*store.0.lock().unwrap(), vec![sidecar]);
+    }
 
     #[test]
     fn cursor_roundtrip() {
@@ -2128,4 +2652,78 @@ mod tests {
     fn normalize_text_is_deterministic() {
         assert_eq!(normalize_text("Foo::Bar-baz"), "foo bar baz");
     }
+
+    #[test]
+    fn unsupported_tool_names_share_one_metric_label() {
+        let defined: Vec<String> = tool_definitions()
+            .iter()
+            .map(|tool| tool["name"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(defined, TOOL_NAMES);
+        for name in TOOL_NAMES {
+            assert_eq!(metric_tool_label(name), *name);
+        }
+        for name in ["", "drop_table", "text_search ", &"x".repeat(4096)] {
+            assert_eq!(metric_tool_label(name), "unknown");
+        }
+    }
+
+    #[test]
+    fn index_cache_evicts_the_least_recently_used_entry_past_the_count() {
+        let mut cache = IndexCache::with_limits(2, 100);
+        cache.insert("a".to_string(), CachedIndex::Semantic(None), 40);
+        cache.insert("b".to_string(), CachedIndex::Semantic(None), 40);
+        assert!(cache.get("a").is_some());
+
+        // "b" is now the least recently used.
+        cache.insert("c".to_string(), CachedIndex::Semantic(None), 10);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert_eq!(cache.used_bytes, 50);
+    }
+
+    #[test]
+    fn index_cache_keeps_the_newest_entry_even_past_the_byte_budget() {
+        let mut cache = IndexCache::with_limits(8, 100);
+        cache.insert("a".to_string(), CachedIndex::Semantic(None), 40);
+        cache.insert("b".to_string(), CachedIndex::Semantic(None), 40);
+        cache.insert("c".to_string(), CachedIndex::Semantic(None), 30);
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.used_bytes, 70);
+
+        cache.insert("d".to_string(), CachedIndex::Semantic(None), 500);
+        assert_eq!(cache.entries.len(), 1);
+        assert!(cache.get("d").is_some());
+        assert_eq!(cache.used_bytes, 500);
+    }
+
+    #[test]
+    fn reinserting_a_key_replaces_its_size() {
+        let mut cache = IndexCache::with_limits(8, 100);
+        cache.insert("a".to_string(), CachedIndex::Semantic(None), 40);
+        cache.insert("a".to_string(), CachedIndex::Semantic(None), 10);
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.stats().bytes, 10);
+        assert_eq!(cache.stats().semantic, 1);
+    }
+
+    #[tokio::test]
+    async fn loaded_indexes_are_cached_with_their_size() {
+        let dir = TempDir::new("mcp-index-cache");
+        let (state, _rx) = test_state(test_config(dir.path()));
+        let capsule = dir.path().join("demo.mv2");
+        let request = sample_request();
+        build_and_persist_from_request(&request, &build_frame_documents(&request), &capsule)
+            .unwrap();
+
+        let first = get_or_load_index(&state, &capsule).await.unwrap();
+        let stats = state.mcp_indexes.lock().await.stats();
+        assert_eq!(stats.graph, 1);
+        assert_eq!(stats.bytes, first.approx_heap_bytes());
+
+        // Served from memory, without touching the sidecar again.
+        std::fs::remove_file(sidecar_path_for_capsule(&capsule)).unwrap();
+        let second = get_or_load_index(&state, &capsule).await.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+    }
 }
@@ -1,20 +1,29 @@
 use std::{
     collections::{HashMap, HashSet},
     fs,
+    mem::size_of,
     path::{Path, PathBuf},
 };
 
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use memvid_core::{Memvid, TimelineQuery};
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
 use crate::models::{ExportRequest, FrameDocument};
 
 pub const MCP_SCHEMA_VERSION: &str = "gitnexus.mcp.v1";
-pub const MCP_INDEX_SCHEMA_VERSION: &str = "gitnexus.mcp.index.v1";
+pub const MCP_INDEX_SCHEMA_VERSION: &str = "gitnexus.mcp.index.v2";
+
+/// Upper bound on BM25 candidates fetched per `text_search` call.
+pub const MAX_FULLTEXT_CANDIDATES: usize = 1_000;
+const HIGHLIGHT_OPEN: &str = "\u{2}";
+const HIGHLIGHT_CLOSE: &str = "\u{3}";
+/// Approximate per-entry cost of a `HashMap<String, _>` slot beyond the key
+/// bytes: the `String` header, the value and hashbrown control bytes.
+const MAP_ENTRY_OVERHEAD: usize = 48;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeRecord {
@@ -110,9 +119,131 @@ pub struct CapsuleIndex {
     pub nodes_by_file: HashMap<String, Vec<usize>>,
     pub process_step_by_process: HashMap<String, Vec<usize>>,
     pub symbols_by_norm: HashMap<String, Vec<usize>>,
+    pub fulltext_by_uri: HashMap<String, usize>,
 }
 
 impl CapsuleIndex {
+    /// Rough heap footprint (record payloads plus runtime lookup maps), used
+    /// to bound the in-memory index cache.
+    pub fn approx_heap_bytes(&self) -> usize {
+        fn json_len(value: &Value) -> usize {
+            if value.is_null() {
+                0
+            } else {
+                value.to_string().len()
+            }
+        }
+        fn map_bytes<V>(map: &HashMap<String, V>, value_bytes: impl Fn(&V) -> usize) -> usize {
+            map.iter()
+                .map(|(key, value)| MAP_ENTRY_OVERHEAD + key.len() + value_bytes(value))
+                .sum()
+        }
+        let positions = |v: &Vec<usize>| v.len() * size_of::<usize>();
+
+        let nodes: usize = self
+            .nodes
+            .iter()
+            .map(|n| {
+                size_of::<NodeRecord>()
+                    + n.id.len()
+                    + n.label.len()
+                    + n.name.len()
+                    + n.file_path.len()
+                    + n.language.as_ref().map_or(0, String::len)
+                    + n.uri.len()
+                    + n.title.len()
+                    + n.search_text.len()
+                    + json_len(&n.metadata)
+            })
+            .sum();
+        let edges: usize = self
+            .edges
+            .iter()
+            .map(|e| {
+                size_of::<EdgeRecord>()
+                    + e.id.len()
+                    + e.relation_type.len()
+                    + e.source_id.len()
+                    + e.target_id.len()
+                    + e.reason.len()
+                    + e.uri.len()
+                    + e.search_text.len()
+                    + json_len(&e.metadata)
+            })
+            .sum();
+        let process_steps: usize = self
+            .process_steps
+            .iter()
+            .map(|p| {
+                size_of::<ProcessStepRecord>()
+                    + p.process_id.len()
+                    + p.function_id.len()
+                    + p.relation_uri.len()
+            })
+            .sum();
+        let symbols: usize = self
+            .symbols
+            .iter()
+            .map(|s| {
+                size_of::<SymbolRecord>()
+                    + s.symbol_norm.len()
+                    + s.symbol.len()
+                    + s.node_id.len()
+                    + s.file_path.len()
+                    + s.node_label.len()
+            })
+            .sum();
+        let hotspots: usize = self
+            .hotspots
+            .iter()
+            .map(|h| size_of::<HotspotRecord>() + h.file_path.len())
+            .sum();
+        let communities: usize = self
+            .community_membership
+            .iter()
+            .map(|c| {
+                size_of::<CommunityMembershipRecord>()
+                    + c.community_id.len()
+                    + c.node_id.len()
+                    + c.node_label.len()
+                    + c.node_name.len()
+            })
+            .sum();
+        let fulltext: usize = self
+            .fulltext
+            .iter()
+            .map(|f| {
+                size_of::<FulltextEntry>()
+                    + f.ref_kind.len()
+                    + f.ref_id.len()
+                    + f.uri.len()
+                    + f.track.len()
+                    + f.text.len()
+            })
+            .sum();
+        let maps = map_bytes(&self.node_by_id, |_| 0)
+            + map_bytes(&self.edge_by_id, |_| 0)
+            + map_bytes(&self.fulltext_by_uri, |_| 0)
+            + map_bytes(&self.edges_out_by_node, positions)
+            + map_bytes(&self.edges_in_by_node, positions)
+            + map_bytes(&self.nodes_by_label, positions)
+            + map_bytes(&self.nodes_by_file, positions)
+            + map_bytes(&self.process_step_by_process, positions)
+            + map_bytes(&self.symbols_by_norm, positions);
+
+        size_of::<Self>()
+            + json_len(&self.manifest)
+            + json_len(&self.capabilities)
+            + nodes
+            + edges
+            + process_steps
+            + symbols
+            + hotspots
+            + communities
+            + fulltext
+            + maps
+    }
+
     fn build_runtime_maps(&mut self) {
         self.node_by_id.clear();
         self.edge_by_id.clear();
@@ -122,6 +253,7 @@ impl CapsuleIndex {
         self.nodes_by_file.clear();
         self.process_step_by_process.clear();
         self.symbols_by_norm.clear();
+        self.fulltext_by_uri.clear();
 
         for (idx, node) in self.nodes.iter().enumerate() {
             self.node_by_id.insert(node.id.clone(), idx);
@@ -161,6 +293,10 @@ impl CapsuleIndex {
                 .push(idx);
         }
 
+        for (idx, entry) in self.fulltext.iter().enumerate() {
+            self.fulltext_by_uri.entry(entry.uri.clone()).or_insert(idx);
+        }
+
         for entries in self.process_step_by_process.values_mut() {
             entries.sort_by_key(|idx| {
                 let step = &self.process_steps[*idx];
@@ -375,6 +511,7 @@ pub fn build_and_persist_from_request(
         nodes_by_file: HashMap::new(),
         process_step_by_process: HashMap::new(),
         symbols_by_norm: HashMap::new(),
+        fulltext_by_uri: HashMap::new(),
     };
 
     index.build_runtime_maps();
@@ -382,10 +519,11 @@ pub fn build_and_persist_from_request(
     Ok(index)
 }
 
+/// Reads a sidecar without modifying it. Sidecars from older releases must
+/// go through [`upgrade_sidecar`] first.
 pub fn load_from_sidecar(capsule_path: &Path) -> Result<CapsuleIndex> {
     let sidecar_path = sidecar_path_for_capsule(capsule_path);
-    let conn = Connection::open(&sidecar_path)
-        .with_context(|| format!("Failed opening sidecar {}", sidecar_path.display()))?;
+    let conn = open_sidecar_read_only(&sidecar_path)?;
 
     let schema_version: String = conn
         .query_row(
@@ -593,6 +731,7 @@ pub fn load_from_sidecar(capsule_path: &Path) -> Result<CapsuleIndex> {
         nodes_by_file: HashMap::new(),
         process_step_by_process: HashMap::new(),
         symbols_by_norm: HashMap::new(),
+        fulltext_by_uri: HashMap::new(),
     };
     index.build_runtime_maps();
     Ok(index)
@@ -802,19 +941,53 @@ pub fn build_from_capsule(capsule_path: &Path) -> Result<CapsuleIndex> {
         nodes_by_file: HashMap::new(),
         process_step_by_process: HashMap::new(),
         symbols_by_norm: HashMap::new(),
+        fulltext_by_uri: HashMap::new(),
     };
     index.build_runtime_maps();
     Ok(index)
 }
 
+/// Builds the sidecar in a temp file and renames it into place, so readers
+/// never open a half-written index.
 pub fn persist_to_sidecar(index: &CapsuleIndex) -> Result<()> {
     if let Some(parent) = index.sidecar_path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("Failed creating sidecar directory {}", parent.display()))?;
     }
 
-    let conn = Connection::open(&index.sidecar_path)
-        .with_context(|| format!("Failed opening {}", index.sidecar_path.display()))?;
+    let tmp_path = index
+        .sidecar_path
+        .with_extension(format!("sqlite.{}.tmp", uuid::Uuid::new_v4()));
+    if let Err(err) = write_sidecar(&tmp_path, index) {
+        for path in sqlite_files(&tmp_path) {
+            let _ = fs::remove_file(path);
+        }
+        return Err(err);
+    }
+    // Journals left by an older sidecar would be replayed against the new one.
+    for path in sqlite_files(&index.sidecar_path).skip(1) {
+        let _ = fs::remove_file(path);
+    }
+    fs::rename(&tmp_path, &index.sidecar_path).with_context(|| {
+        format!(
+            "Failed to finalize sidecar {}",
+            index.sidecar_path.display()
+        )
+    })
+}
+
+/// The database file followed by its journal, WAL and shared-memory files.
+fn sqlite_files(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    ["", "-journal", "-wal", "-shm"].into_iter().map(|suffix| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    })
+}
+
+fn write_sidecar(path: &Path, index: &CapsuleIndex) -> Result<()> {
+    let conn =
+        Connection::open(path).with_context(|| format!("Failed opening {}", path.display()))?;
 
     conn.execute_batch(
         "
@@ -910,18 +1083,10 @@ pub fn persist_to_sidecar(index: &CapsuleIndex) -> Result<()> {
         CREATE INDEX IF NOT EXISTS idx_fulltext_kind ON fulltext_lexical_index(ref_kind);
         CREATE INDEX IF NOT EXISTS idx_fulltext_uri ON fulltext_lexical_index(uri);
         CREATE INDEX IF NOT EXISTS idx_community_id ON community_membership(community_id);
-        DELETE FROM meta;
-        DELETE FROM nodes_by_id;
-        DELETE FROM nodes_by_label;
-        DELETE FROM nodes_by_file;
-        DELETE FROM symbols_by_name_normalized;
-        DELETE FROM edges;
-        DELETE FROM edges_by_source_type;
-        DELETE FROM edges_by_target_type;
-        DELETE FROM process_steps_by_process_id;
-        DELETE FROM fulltext_lexical_index;
-        DELETE FROM hotspots;
-        DELETE FROM community_membership;
+        CREATE VIRTUAL TABLE IF NOT EXISTS fulltext_fts USING fts5(
+            text,
+            content='fulltext_lexical_index'
+        );
         ",
     )?;
 
@@ -1023,6 +1188,10 @@ pub fn persist_to_sidecar(index: &CapsuleIndex) -> Result<()> {
             params![entry.ref_kind, entry.ref_id, entry.uri, entry.track, entry.text],
         )?;
     }
+    tx.execute(
+        "INSERT INTO fulltext_fts(fulltext_fts) VALUES('rebuild')",
+        [],
+    )?;
 
     for hotspot in &index.hotspots {
         tx.execute(
@@ -1049,9 +1218,231 @@ pub fn persist_to_sidecar(index: &CapsuleIndex) -> Result<()> {
     }
 
     tx.commit()?;
+    // Fold the WAL back in so the renamed file is self-contained.
+    conn.execute_batch("PRAGMA journal_mode = DELETE;")?;
     Ok(())
 }
 
+fn open_sidecar_read_only(path: &Path) -> Result<Connection> {
+    Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("Failed opening sidecar {}", path.display()))
+}
+
+fn has_fulltext_fts(conn: &Connection) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type='table' AND name='fulltext_fts'",
+            [],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some())
+}
+
+/// Brings a sidecar written by an older release up to the current schema.
+/// The published file is never written to: a copy is migrated and renamed
+/// over it, so open readers keep their snapshot. Returns `true` when the
+/// sidecar was replaced and must be republished to the artifact store.
+pub fn upgrade_sidecar(capsule_path: &Path) -> Result<bool> {
+    let sidecar_path = sidecar_path_for_capsule(capsule_path);
+    let source = open_sidecar_read_only(&sidecar_path)?;
+    if has_fulltext_fts(&source)? {
+        return Ok(false);
+    }
+
+    let tmp_path = sidecar_path.with_extension(format!("sqlite.{}.tmp", uuid::Uuid::new_v4()));
+    let result = (|| -> Result<()> {
+        // Unlike a file copy, VACUUM INTO also picks up pages still in a WAL.
+        source.execute(
+            "VACUUM INTO ?1",
+            params![tmp_path.to_string_lossy().as_ref()],
+        )?;
+        drop(source);
+        let conn = Connection::open(&tmp_path)
+            .with_context(|| format!("Failed opening {}", tmp_path.display()))?;
+        migrate_sidecar(&conn)?;
+        conn.execute_batch("PRAGMA journal_mode = DELETE;")?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        for path in sqlite_files(&tmp_path) {
+            let _ = fs::remove_file(path);
+        }
+        return Err(err.context(format!(
+            "Failed migrating sidecar {}",
+            sidecar_path.display()
+        )));
+    }
+    for path in sqlite_files(&sidecar_path).skip(1) {
+        let _ = fs::remove_file(path);
+    }
+    fs::rename(&tmp_path, &sidecar_path)
+        .with_context(|| format!("Failed to finalize sidecar {}", sidecar_path.display()))?;
+    Ok(true)
+}
+
+/// v1 sidecars only lack the FTS5 table, which is rebuilt from
+/// `fulltext_lexical_index`.
+fn migrate_sidecar(conn: &Connection) -> Result<()> {
+    if has_fulltext_fts(conn)? {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE fulltext_fts USING fts5(
+            text,
+            content='fulltext_lexical_index'
+        );
+        INSERT INTO fulltext_fts(fulltext_fts) VALUES('rebuild');
+        ",
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO meta(key,value) VALUES('index_schema_version',?1)",
+        params![MCP_INDEX_SCHEMA_VERSION],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct FulltextHit {
+    pub ref_kind: String,
+    pub ref_id: String,
+    pub uri: String,
+    pub track: String,
+    /// Negated FTS5 `bm25()`, so higher is better.
+    pub bm25: f64,
+    pub snippet: String,
+    /// `[start, end)` character offsets of matched terms within `snippet`.
+    pub highlights: Vec<(usize, usize)>,
+}
+
+/// Translates a user query into an FTS5 MATCH expression. `"quoted text"` is
+/// a phrase, a trailing `*` makes a prefix query, and everything else is
+/// OR-ed together so BM25 ranks entries matching more terms first. Every
+/// term is quoted, so FTS5 operators in the input are treated as text.
+pub fn fts_match_expression(query: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut rest = query.trim();
+    while !rest.is_empty() {
+        let (raw, phrase, next) = if let Some(after) = rest.strip_prefix('"') {
+            match after.find('"') {
+                Some(end) => (&after[..end], true, &after[end + 1..]),
+                None => (after, true, ""),
+            }
+        } else {
+            match rest.find(char::is_whitespace) {
+                Some(end) => (&rest[..end], false, &rest[end..]),
+                None => (rest, false, ""),
+            }
+        };
+        rest = next.trim_start();
+
+        let prefix = !phrase && raw.ends_with('*');
+        let cleaned = raw
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+            .collect::<String>();
+        let words = cleaned.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        let mut term = format!("\"{}\"", words.join(" "));
+        if prefix {
+            term.push('*');
+        }
+        terms.push(term);
+    }
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" OR "))
+    }
+}
+
+/// Runs a BM25-ranked query against the sidecar's FTS5 table, optionally
+/// restricted to entries whose URI or track contains `scope`.
+pub fn search_fulltext(
+    sidecar_path: &Path,
+    match_expression: &str,
+    scope: Option<&str>,
+    limit: usize,
+) -> Result<Vec<FulltextHit>> {
+    let conn = Connection::open_with_flags(
+        sidecar_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("Failed opening sidecar {}", sidecar_path.display()))?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT f.ref_kind, f.ref_id, f.uri, f.track, bm25(fulltext_fts),
+                snippet(fulltext_fts, 0, ?3, ?4, '...', 48)
+         FROM fulltext_fts
+         JOIN fulltext_lexical_index f ON f.rowid = fulltext_fts.rowid
+         WHERE fulltext_fts MATCH ?1
+           AND (?2 IS NULL OR instr(lower(f.uri), ?2) > 0 OR instr(lower(f.track), ?2) > 0)
+         ORDER BY bm25(fulltext_fts)
+         LIMIT ?5",
+    )?;
+    let scope = scope.map(|scope| scope.to_lowercase());
+    let rows = stmt.query_map(
+        params![
+            match_expression,
+            scope,
+            HIGHLIGHT_OPEN,
+            HIGHLIGHT_CLOSE,
+            limit as i64
+        ],
+        |row| {
+            let raw_snippet: String = row.get(5)?;
+            let (snippet, highlights) = split_highlights(&raw_snippet);
+            Ok(FulltextHit {
+                ref_kind: row.get(0)?,
+                ref_id: row.get(1)?,
+                uri: row.get(2)?,
+                track: row.get(3)?,
+                bm25: -row.get::<_, f64>(4)?,
+                snippet,
+                highlights,
+            })
+        },
+    )?;
+
+    let mut hits = Vec::new();
+    for row in rows {
+        hits.push(row?);
+    }
+    Ok(hits)
+}
+
+fn split_highlights(raw: &str) -> (String, Vec<(usize, usize)>) {
+    let mut text = String::with_capacity(raw.len());
+    let mut highlights = Vec::new();
+    let mut open_at = None;
+    let mut chars = 0usize;
+    for c in raw.chars() {
+        match c {
+            '\u{2}' => open_at = Some(chars),
+            '\u{3}' => {
+                if let Some(start) = open_at.take() {
+                    highlights.push((start, chars));
+                }
+            }
+            _ => {
+                text.push(c);
+                chars += 1;
+            }
+        }
+    }
+    (text, highlights)
+}
+
 fn derive_process_steps(edges: &[EdgeRecord]) -> Vec<ProcessStepRecord> {
     let mut steps = Vec::new();
     for edge in edges {
@@ -1282,3 +1673,118 @@ fn parse_name_line(text: &str) -> Option<String> {
 fn parse_file_path_line(text: &str) -> Option<String> {
     parse_line_value(text, "filePath")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    /// Writes a v1 sidecar (no FTS5 table) next to `dir/demo.mv2` and
+    /// returns the capsule path.
+    fn v1_sidecar(dir: &TempDir) -> PathBuf {
+        let capsule = dir.path().join("demo.mv2");
+        let conn = Connection::open(sidecar_path_for_capsule(&capsule)).unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+            INSERT INTO meta VALUES('index_schema_version','gitnexus.mcp.index.v1');
+            CREATE TABLE fulltext_lexical_index (
+                ref_kind TEXT NOT NULL, ref_id TEXT NOT NULL, uri TEXT NOT NULL,
+                track TEXT NOT NULL, text TEXT NOT NULL
+            );
+            INSERT INTO fulltext_lexical_index VALUES
+                ('node','mv2://nodes/a','mv2://nodes/a','nodes','parse config file and parse env'),
+                ('node','mv2://nodes/b','mv2://nodes/b','nodes','load config from disk'),
+                ('relation','mv2://relations/c','mv2://relations/c','relations','CALLS parser');
+            ",
+        )
+        .unwrap();
+        capsule
+    }
+
+    fn schema_version(conn: &Connection) -> String {
+        conn.query_row(
+            "SELECT value FROM meta WHERE key='index_schema_version'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn v1_sidecars_are_upgraded_through_a_copy() {
+        let dir = TempDir::new("fts-upgrade");
+        let capsule = v1_sidecar(&dir);
+        let sidecar = sidecar_path_for_capsule(&capsule);
+        let reader = open_sidecar_read_only(&sidecar).unwrap();
+
+        assert!(upgrade_sidecar(&capsule).unwrap());
+        // The reader opened before the upgrade still sees the original file.
+        assert!(!has_fulltext_fts(&reader).unwrap());
+        assert_eq!(schema_version(&reader), "gitnexus.mcp.index.v1");
+
+        let upgraded = open_sidecar_read_only(&sidecar).unwrap();
+        assert!(has_fulltext_fts(&upgraded).unwrap());
+        assert_eq!(schema_version(&upgraded), MCP_INDEX_SCHEMA_VERSION);
+        let names: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["demo.mv2.index.v1.sqlite".to_string()]);
+
+        assert!(!upgrade_sidecar(&capsule).unwrap());
+    }
+
+    #[test]
+    fn fulltext_hits_are_ranked_with_bm25_and_highlighted() {
+        let dir = TempDir::new("fts-rank");
+        let capsule = v1_sidecar(&dir);
+        upgrade_sidecar(&capsule).unwrap();
+        let sidecar = sidecar_path_for_capsule(&capsule);
+
+        let expression = fts_match_expression("parse config").unwrap();
+        let hits = search_fulltext(&sidecar, &expression, None, 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].uri, "mv2://nodes/a");
+        assert!(hits[0].bm25 > hits[1].bm25);
+        let (start, end) = hits[0].highlights[0];
+        let matched: String = hits[0]
+            .snippet
+            .chars()
+            .skip(start)
+            .take(end - start)
+            .collect();
+        assert_eq!(matched, "parse");
+    }
+
+    #[test]
+    fn phrase_prefix_and_scoped_queries_narrow_hits() {
+        let dir = TempDir::new("fts-scope");
+        let capsule = v1_sidecar(&dir);
+        upgrade_sidecar(&capsule).unwrap();
+        let sidecar = sidecar_path_for_capsule(&capsule);
+
+        let phrase = fts_match_expression("\"config from\"").unwrap();
+        let hits = search_fulltext(&sidecar, &phrase, None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uri, "mv2://nodes/b");
+
+        let prefix = fts_match_expression("pars*").unwrap();
+        let hits = search_fulltext(&sidecar, &prefix, Some("relations"), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].uri, "mv2://relations/c");
+    }
+
+    #[test]
+    fn match_expressions_quote_every_term() {
+        assert_eq!(
+            fts_match_expression("parse config").unwrap(),
+            "\"parse\" OR \"config\""
+        );
+        assert_eq!(
+            fts_match_expression("\"a NEAR b\" c*").unwrap(),
+            "\"a NEAR b\" OR \"c\"*"
+        );
+        assert!(fts_match_expression("  \"\" *** ").is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use memvid_core::{
+    Memvid, TimelineQuery, MEMVID_EMBEDDING_MODEL_KEY, MEMVID_EMBEDDING_PROVIDER_KEY,
+};
+use tracing::warn;
+
+/// Frame vectors of one capsule, unit-normalized so a dot product is the
+/// cosine similarity. Only frames embedded with the capsule's recorded
+/// provider and model are included.
+#[derive(Debug)]
+pub struct SemanticIndex {
+    pub provider: String,
+    pub model: String,
+    pub dimension: usize,
+    entries: Vec<SemanticEntry>,
+}
+
+#[derive(Debug)]
+struct SemanticEntry {
+    uri: String,
+    vector: Vec<f32>,
+}
+
+impl SemanticIndex {
+    /// Returns `None` when the capsule was exported without embeddings.
+    pub fn load(capsule_path: &Path) -> Result<Option<Self>> {
+        let mut mem = Memvid::open_read_only(capsule_path)
+            .with_context(|| format!("Failed opening capsule {}", capsule_path.display()))?;
+        let timeline = mem.timeline(TimelineQuery::builder().no_limit().build())?;
+
+        let mut index: Option<Self> = None;
+        let mut skipped = 0usize;
+        for entry in timeline {
+            let Ok(frame) = mem.frame_by_id(entry.frame_id) else {
+                continue;
+            };
+            let (Some(provider), Some(model)) = (
+                frame.extra_metadata.get(MEMVID_EMBEDDING_PROVIDER_KEY),
+                frame.extra_metadata.get(MEMVID_EMBEDDING_MODEL_KEY),
+            ) else {
+                continue;
+            };
+            let vector = match mem.frame_embedding(entry.frame_id) {
+                Ok(Some(vector)) if !vector.is_empty() => vector,
+                _ => continue,
+            };
+            let uri = frame
+                .uri
+                .clone()
+                .or(entry.uri.clone())
+                .unwrap_or_else(|| format!("mv2://frame/{}", entry.frame_id));
+
+            let index = index.get_or_insert_with(|| Self {
+                provider: provider.clone(),
+                model: model.clone(),
+                dimension: vector.len(),
+                entries: Vec::new(),
+            });
+            if index.provider != *provider
+                || index.model != *model
+                || index.dimension != vector.len()
+            {
+                skipped += 1;
+                continue;
+            }
+            if let Some(vector) = normalized(vector) {
+                index.entries.push(SemanticEntry { uri, vector });
+            }
+        }
+
+        if skipped > 0 {
+            warn!(
+                capsule = %capsule_path.display(),
+                skipped,
+                "Ignoring frames embedded with a different provider, model or dimension"
+            );
+        }
+        Ok(index)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Rough heap footprint, used to bound the in-memory index cache.
+    pub fn approx_heap_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.provider.len()
+            + self.model.len()
+            + self
+                .entries
+                .iter()
+                .map(|entry| {
+                    std::mem::size_of::<SemanticEntry>()
+                        + entry.uri.len()
+                        + entry.vector.len() * std::mem::size_of::<f32>()
+                })
+                .sum::<usize>()
+    }
+
+    /// Cosine similarity of `query` against every frame, keyed by frame URI.
+    pub fn similarities(&self, query: Vec<f32>) -> Result<Vec<(&str, f64)>> {
+        if query.len() != self.dimension {
+            bail!(
+                "Query embedding has {} dimensions but the capsule uses {}",
+                query.len(),
+                self.dimension
+            );
+        }
+        let Some(query) = normalized(query) else {
+            bail!("Query embedding is a zero vector");
+        };
+        Ok(self
+            .entries
+            .iter()
+            .map(|entry| (entry.uri.as_str(), dot(&entry.vector, &query)))
+            .collect())
+    }
+}
+
+fn normalized(mut vector: Vec<f32>) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm == 0.0 || !norm.is_finite() {
+        return None;
+    }
+    vector.iter_mut().for_each(|v| *v /= norm);
+    Some(vector)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f64 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| f64::from(*x) * f64::from(*y))
+        .sum()
+}
+
+/// Blends a lexical score and a cosine similarity; negative similarities
+/// count as no semantic match.
+pub fn hybrid_score(lexical: f64, semantic: f64, semantic_weight: f64) -> f64 {
+    let weight = semantic_weight.clamp(0.0, 1.0);
+    (1.0 - weight) * lexical + weight * semantic.max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        memvid_writer::write_mv2_core_only,
+        models::FrameDocument,
+        test_support::{openai_embedding_runtime, spawn_embeddings_mock, TempDir},
+    };
+    use serde_json::Value;
+
+    fn index(vectors: &[(&str, Vec<f32>)]) -> SemanticIndex {
+        SemanticIndex {
+            provider: "openai".to_string(),
+            model: "text-embedding-3-large".to_string(),
+            dimension: 2,
+            entries: vectors
+                .iter()
+                .map(|(uri, vector)| SemanticEntry {
+                    uri: uri.to_string(),
+                    vector: normalized(vector.clone()).unwrap(),
+                })
+                .collect(),
+        }
+    }
+
+    fn write_capsule(dir: &TempDir, semantic: bool) -> std::path::PathBuf {
+        let docs: Vec<FrameDocument> = ["alpha", "beta"]
+            .iter()
+            .map(|id| FrameDocument {
+                title: id.to_string(),
+                label: "Function".to_string(),
+                text: format!("fn {id}() {{}}"),
+                uri: format!("mv2://nodes/{id}"),
+                track: "nodes".to_string(),
+                tags: Vec::new(),
+                metadata: Value::Null,
+            })
+            .collect();
+        // The mock serves from its own runtime while the writer blocks.
+        let server = tokio::runtime::Runtime::new().unwrap();
+        let runtime = semantic.then(|| {
+            let (base_url, _) = server.block_on(spawn_embeddings_mock(0));
+            openai_embedding_runtime(base_url, 8, 0)
+        });
+        let path = dir.path().join("demo.mv2");
+        write_mv2_core_only(&path, &docs, semantic, runtime, None, |_, _| Ok(())).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_collects_the_vectors_of_embedded_frames() {
+        let dir = TempDir::new("semantic-load");
+        let index = SemanticIndex::load(&write_capsule(&dir, true))
+            .unwrap()
+            .expect("capsule has embeddings");
+        assert_eq!(index.provider, "openai");
+        assert_eq!(index.model, "text-embedding-3-small");
+        assert_eq!(index.dimension, 2);
+        assert_eq!(index.frame_count(), 2);
+        assert!(index.approx_heap_bytes() > 2 * 2 * std::mem::size_of::<f32>());
+
+        let scores = index.similarities(vec![1.0, 0.0]).unwrap();
+        assert!(scores.iter().any(|(uri, _)| *uri == "mv2://nodes/alpha"));
+        assert!(scores.iter().all(|(_, score)| (0.0..=1.0).contains(score)));
+    }
+
+    #[test]
+    fn load_returns_none_without_embeddings() {
+        let dir = TempDir::new("semantic-plain");
+        assert!(SemanticIndex::load(&write_capsule(&dir, false))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn similarities_are_cosine_per_frame() {
+        let index = index(&[
+            ("mv2://nodes/a", vec![3.0, 0.0]),
+            ("mv2://nodes/b", vec![0.0, 2.0]),
+        ]);
+        let scores = index.similarities(vec![10.0, 10.0]).unwrap();
+        assert_eq!(scores[0].0, "mv2://nodes/a");
+        assert!((scores[0].1 - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+        assert_eq!(scores[1].0, "mv2://nodes/b");
+        assert!((scores[1].1 - std::f64::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mismatched_or_zero_queries_are_rejected() {
+        let index = index(&[("mv2://nodes/a", vec![1.0, 0.0])]);
+        let err = index.similarities(vec![1.0, 0.0, 0.0]).unwrap_err();
+        assert!(err.to_string().contains("has 3 dimensions"));
+        let err = index.similarities(vec![0.0, 0.0]).unwrap_err();
+        assert!(err.to_string().contains("zero vector"));
+    }
+
+    #[test]
+    fn hybrid_score_clamps_the_weight_and_ignores_negative_similarity() {
+        assert_eq!(hybrid_score(1.0, -0.4, 0.5), 0.5);
+        assert!((hybrid_score(0.5, 0.9, 0.25) - 0.6).abs() < 1e-9);
+        assert_eq!(hybrid_score(0.3, 0.9, 2.0), 0.9);
+        assert_eq!(hybrid_score(0.3, 0.9, -1.0), 0.3);
+    }
+}
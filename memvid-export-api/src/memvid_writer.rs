@@ -1,4 +1,6 @@
 use std::{
+    fmt,
+    fs::File,
     io::Write,
     path::Path,
     process::{Command, Stdio},
@@ -6,38 +8,70 @@ use std::{
 
 use anyhow::{Context, Result};
 use memvid_core::{
-    Memvid, PutOptions, MEMVID_EMBEDDING_DIMENSION_KEY, MEMVID_EMBEDDING_MODEL_KEY,
-    MEMVID_EMBEDDING_PROVIDER_KEY,
+    FrameId, FrameRole, FrameStatus, Memvid, PutOptions, MEMVID_EMBEDDING_DIMENSION_KEY,
+    MEMVID_EMBEDDING_MODEL_KEY, MEMVID_EMBEDDING_PROVIDER_KEY,
 };
+use sha2::{Digest, Sha256};
 use tracing::warn;
+use uuid::Uuid;
 
 use crate::embedding::EmbeddingRuntimeConfig;
+use crate::embedding_cache::EmbeddingCache;
+use crate::incremental::{
+    content_hash, BaseCapsule, ExportDiff, CONTENT_HASH_TAG, EMBEDDING_IDENTITY_TAG,
+};
 use crate::models::FrameDocument;
 
+/// Frame count and digest of a capsule that passed [`verify_capsule`].
+#[derive(Debug, Clone)]
+pub struct VerifiedCapsule {
+    pub frame_count: usize,
+    pub sha256: String,
+}
+
+/// Error a progress callback returns to stop a write early; the partial
+/// capsule is discarded and the CLI fallback is not tried.
+#[derive(Debug)]
+pub struct WriteInterrupted;
+
+impl fmt::Display for WriteInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Capsule write interrupted")
+    }
+}
+
+impl std::error::Error for WriteInterrupted {}
+
 pub fn write_mv2<F>(
     path: &Path,
     docs: &[FrameDocument],
     semantic_enabled: bool,
     embedding_config: Option<EmbeddingRuntimeConfig>,
+    base_capsule: Option<&Path>,
     mut on_progress: F,
-) -> Result<()>
+) -> Result<(ExportDiff, VerifiedCapsule)>
 where
-    F: FnMut(usize, usize),
+    F: FnMut(usize, usize) -> Result<()>,
 {
-    if let Err(err) = write_with_memvid_core(
-        path,
-        docs,
-        semantic_enabled,
-        embedding_config.clone(),
-        &mut on_progress,
-    ) {
-        if semantic_enabled {
-            return Err(err);
+    write_atomically(path, docs.len(), |tmp_path| {
+        match write_with_memvid_core(
+            tmp_path,
+            docs,
+            semantic_enabled,
+            embedding_config.clone(),
+            base_capsule,
+            &mut on_progress,
+        ) {
+            Ok(diff) => Ok(diff),
+            Err(err) => {
+                if semantic_enabled || err.is::<WriteInterrupted>() {
+                    return Err(err);
+                }
+                warn!("memvid-core write failed, falling back to memvid CLI: {err:#}");
+                write_with_memvid_cli(tmp_path, docs, &mut on_progress)
+            }
         }
-        warn!("memvid-core write failed, falling back to memvid CLI: {err:#}");
-        write_with_memvid_cli(path, docs, &mut on_progress)?;
-    }
-    Ok(())
+    })
 }
 
 pub fn write_mv2_core_only<F>(
@@ -45,18 +79,83 @@ pub fn write_mv2_core_only<F>(
     docs: &[FrameDocument],
     semantic_enabled: bool,
     embedding_config: Option<EmbeddingRuntimeConfig>,
+    base_capsule: Option<&Path>,
     mut on_progress: F,
-) -> Result<()>
+) -> Result<(ExportDiff, VerifiedCapsule)>
 where
-    F: FnMut(usize, usize),
+    F: FnMut(usize, usize) -> Result<()>,
 {
-    write_with_memvid_core(
-        path,
-        docs,
-        semantic_enabled,
-        embedding_config,
-        &mut on_progress,
-    )
+    write_atomically(path, docs.len(), |tmp_path| {
+        write_with_memvid_core(
+            tmp_path,
+            docs,
+            semantic_enabled,
+            embedding_config,
+            base_capsule,
+            &mut on_progress,
+        )
+    })
+}
+
+/// Writes the capsule to a temp file next to `path` and renames it into
+/// place only once it verifies, so a crash or failed write never leaves a
+/// file at `path` that looks like a finished capsule.
+fn write_atomically<T>(
+    path: &Path,
+    expected_frames: usize,
+    write: impl FnOnce(&Path) -> Result<T>,
+) -> Result<(T, VerifiedCapsule)> {
+    let tmp_path = path.with_extension(format!("mv2.{}.tmp", Uuid::new_v4()));
+    let result = write(&tmp_path).and_then(|written| {
+        let verified = verify_capsule(&tmp_path, expected_frames)?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to finalize capsule {}", path.display()))?;
+        Ok((written, verified))
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+/// Reopens a capsule read-only, checks it holds one active document frame
+/// per exported doc and returns its SHA-256. Long documents are split into
+/// extra chunk frames, so `Memvid::frame_count` alone can't be compared.
+pub fn verify_capsule(path: &Path, expected_frames: usize) -> Result<VerifiedCapsule> {
+    let mem = Memvid::open_read_only(path)
+        .with_context(|| format!("Written capsule {} does not open", path.display()))?;
+    let mut frame_count = 0usize;
+    for frame_id in 0..mem.frame_count() as FrameId {
+        let frame = mem.frame_by_id(frame_id).with_context(|| {
+            format!(
+                "Written capsule {} is missing frame {frame_id}",
+                path.display()
+            )
+        })?;
+        if frame.role == FrameRole::Document && frame.status == FrameStatus::Active {
+            frame_count += 1;
+        }
+    }
+    drop(mem);
+    if frame_count != expected_frames {
+        anyhow::bail!(
+            "Written capsule {} has {frame_count} frames, expected {expected_frames}",
+            path.display()
+        );
+    }
+    Ok(VerifiedCapsule {
+        frame_count,
+        sha256: file_sha256(path)?,
+    })
+}
+
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(hex::encode(hasher.finalize()))
 }
 
 fn write_with_memvid_core<F>(
@@ -64,11 +163,28 @@ fn write_with_memvid_core<F>(
     docs: &[FrameDocument],
     semantic_enabled: bool,
     embedding_config: Option<EmbeddingRuntimeConfig>,
+    base_capsule: Option<&Path>,
     on_progress: &mut F,
-) -> Result<()>
+) -> Result<ExportDiff>
 where
-    F: FnMut(usize, usize),
+    F: FnMut(usize, usize) -> Result<()>,
 {
+    let mut base: Option<BaseCapsule> =
+        base_capsule.and_then(|base_path| match BaseCapsule::open(base_path) {
+            Ok(base) => Some(base),
+            Err(err) => {
+                warn!("Base capsule unavailable, exporting without reuse: {err:#}");
+                None
+            }
+        });
+    let mut diff = match base.as_ref() {
+        Some(base) => base.start_diff(docs),
+        None => ExportDiff {
+            total_frames: docs.len(),
+            ..ExportDiff::default()
+        },
+    };
+
     let mut mem =
         Memvid::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
 
@@ -82,75 +198,185 @@ where
     };
 
     let total = docs.len().max(1);
-    for (idx, doc) in docs.iter().enumerate() {
-        let mut builder = PutOptions::builder()
-            .title(doc.title.clone())
-            .uri(doc.uri.clone())
-            .search_text(doc.text.clone())
-            .tag("track".to_string(), doc.track.clone())
-            .tag("label".to_string(), doc.label.clone())
-            .tag("semantic".to_string(), semantic_enabled.to_string());
-
-        for tag in &doc.tags {
-            if let Some((k, v)) = tag.split_once('=') {
-                builder = builder.tag(k.to_string(), v.to_string());
+    let chunk_len = embedding_config
+        .as_ref()
+        .map(EmbeddingRuntimeConfig::chunk_len)
+        .unwrap_or(docs.len())
+        .max(1);
+    let identity_fingerprint = embedding_config
+        .as_ref()
+        .map(|runtime| runtime.identity().fingerprint())
+        .unwrap_or_default();
+    let mut written = 0usize;
+    for chunk in docs.chunks(chunk_len) {
+        let hashes: Vec<String> = chunk.iter().map(|doc| content_hash(&doc.text)).collect();
+        if let Some(base) = base.as_ref() {
+            for (doc, hash) in chunk.iter().zip(&hashes) {
+                diff.record(base.classify(doc, hash));
             }
         }
 
-        let options = builder.build();
+        // Reuse what the base capsule already has, then embed the rest of the
+        // chunk in provider batches.
+        let mut embeddings: Vec<Option<Vec<f32>>> = vec![None; chunk.len()];
         if let Some(runtime) = embedding_config.as_ref() {
-            let embedding = runtime
-                .embed_text(&doc.text)
-                .with_context(|| format!("Failed generating embedding for frame {}", doc.uri))?;
-            let embedding_dims = embedding.len();
-            let options_with_identity = PutOptions::builder()
+            let identity = runtime.identity();
+            if let Some(base) = base.as_mut() {
+                for (slot, (doc, hash)) in embeddings.iter_mut().zip(chunk.iter().zip(&hashes)) {
+                    *slot = base.reusable_embedding(doc, hash, &identity);
+                }
+            }
+            let pending: Vec<usize> = (0..chunk.len())
+                .filter(|idx| embeddings[*idx].is_none())
+                .collect();
+            diff.reused_embeddings += chunk.len() - pending.len();
+
+            let cache_keys: Vec<String> = pending
+                .iter()
+                .map(|idx| EmbeddingCache::key(&identity, &hashes[*idx]))
+                .collect();
+            let pending = match runtime.cache.as_ref() {
+                Some(cache) => match cache.get_many(&cache_keys) {
+                    Ok(found) => {
+                        let mut missing = Vec::new();
+                        for ((idx, key), hit) in pending.into_iter().zip(&cache_keys).zip(found) {
+                            match hit {
+                                Some(embedding) => {
+                                    diff.embedding_cache.hits += 1;
+                                    embeddings[idx] = Some(embedding);
+                                }
+                                None => {
+                                    diff.embedding_cache.misses += 1;
+                                    missing.push((idx, key.clone()));
+                                }
+                            }
+                        }
+                        missing
+                    }
+                    Err(err) => {
+                        warn!("Embedding cache lookup failed: {err:#}");
+                        pending.into_iter().zip(cache_keys).collect()
+                    }
+                },
+                None => pending.into_iter().zip(cache_keys).collect(),
+            };
+
+            diff.computed_embeddings += pending.len();
+            if !pending.is_empty() {
+                let texts: Vec<&str> = pending
+                    .iter()
+                    .map(|(idx, _)| chunk[*idx].text.as_str())
+                    .collect();
+                let computed = runtime.embed_texts(&texts).with_context(|| {
+                    format!(
+                        "Failed generating embeddings for frames starting at {}",
+                        chunk[pending[0].0].uri
+                    )
+                })?;
+                if let Some(cache) = runtime.cache.as_ref() {
+                    let entries: Vec<(String, &[f32])> = pending
+                        .iter()
+                        .zip(&computed)
+                        .map(|((_, key), embedding)| (key.clone(), embedding.as_slice()))
+                        .collect();
+                    if let Err(err) = cache.put_many(&identity, &entries) {
+                        warn!("Embedding cache store failed: {err:#}");
+                    }
+                }
+                for ((idx, _), embedding) in pending.into_iter().zip(computed) {
+                    embeddings[idx] = Some(embedding);
+                }
+            }
+        }
+
+        for ((doc, hash), embedding) in chunk.iter().zip(hashes).zip(embeddings) {
+            let mut builder = PutOptions::builder()
                 .title(doc.title.clone())
                 .uri(doc.uri.clone())
                 .search_text(doc.text.clone())
                 .tag("track".to_string(), doc.track.clone())
                 .tag("label".to_string(), doc.label.clone())
-                .tag("semantic".to_string(), "true".to_string())
-                .tag(
-                    MEMVID_EMBEDDING_PROVIDER_KEY.to_string(),
-                    runtime.provider.as_str().to_string(),
-                )
-                .tag(
-                    MEMVID_EMBEDDING_MODEL_KEY.to_string(),
-                    runtime.model.clone(),
-                )
-                .tag(
-                    MEMVID_EMBEDDING_DIMENSION_KEY.to_string(),
-                    embedding_dims.to_string(),
-                );
-            let mut options_with_identity = options_with_identity;
-            for tag in &doc.tags {
-                if let Some((k, v)) = tag.split_once('=') {
-                    options_with_identity = options_with_identity.tag(k.to_string(), v.to_string());
+                .tag("semantic".to_string(), semantic_enabled.to_string())
+                .tag(CONTENT_HASH_TAG.to_string(), hash);
+
+            match (embedding_config.as_ref(), embedding) {
+                (Some(runtime), Some(embedding)) => {
+                    builder = builder
+                        .tag(
+                            MEMVID_EMBEDDING_PROVIDER_KEY.to_string(),
+                            runtime.provider.as_str().to_string(),
+                        )
+                        .tag(
+                            MEMVID_EMBEDDING_MODEL_KEY.to_string(),
+                            runtime.model.clone(),
+                        )
+                        .tag(
+                            MEMVID_EMBEDDING_DIMENSION_KEY.to_string(),
+                            embedding.len().to_string(),
+                        )
+                        .tag(
+                            EMBEDDING_IDENTITY_TAG.to_string(),
+                            identity_fingerprint.clone(),
+                        );
+                    for tag in &doc.tags {
+                        if let Some((k, v)) = tag.split_once('=') {
+                            builder = builder.tag(k.to_string(), v.to_string());
+                        }
+                    }
+                    mem.put_with_embedding_and_options(
+                        doc.text.as_bytes(),
+                        embedding,
+                        builder.build(),
+                    )
+                    .with_context(|| format!("Failed writing embedded frame {}", doc.uri))?;
+                }
+                _ => {
+                    for tag in &doc.tags {
+                        if let Some((k, v)) = tag.split_once('=') {
+                            builder = builder.tag(k.to_string(), v.to_string());
+                        }
+                    }
+                    mem.put_bytes_with_options(doc.text.as_bytes(), builder.build())
+                        .with_context(|| format!("Failed writing frame {}", doc.uri))?;
                 }
             }
-            let options_with_identity = options_with_identity.build();
-
-            mem.put_with_embedding_and_options(
-                doc.text.as_bytes(),
-                embedding,
-                options_with_identity,
-            )
-            .with_context(|| format!("Failed writing embedded frame {}", doc.uri))?;
-        } else {
-            mem.put_bytes_with_options(doc.text.as_bytes(), options)
-                .with_context(|| format!("Failed writing frame {}", doc.uri))?;
+            written += 1;
+            on_progress(written, total)?;
         }
-        on_progress(idx + 1, total);
     }
 
     mem.commit()
         .with_context(|| format!("Failed to commit {}", path.display()))?;
-    Ok(())
+    Ok(diff)
+}
+
+/// Whether a `memvid` executable is on `PATH`, i.e. whether the CLI
+/// fallback in [`write_mv2`] can work.
+pub fn memvid_cli_available() -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| {
+            std::fs::metadata(dir.join("memvid")).is_ok_and(|meta| {
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    meta.is_file() && meta.permissions().mode() & 0o111 != 0
+                }
+                #[cfg(not(unix))]
+                {
+                    meta.is_file()
+                }
+            })
+        })
+    })
 }
 
-fn write_with_memvid_cli<F>(path: &Path, docs: &[FrameDocument], on_progress: &mut F) -> Result<()>
+fn write_with_memvid_cli<F>(
+    path: &Path,
+    docs: &[FrameDocument],
+    on_progress: &mut F,
+) -> Result<ExportDiff>
 where
-    F: FnMut(usize, usize),
+    F: FnMut(usize, usize) -> Result<()>,
 {
     if path.exists() {
         std::fs::remove_file(path)
@@ -180,7 +406,9 @@ where
             .arg("--track")
             .arg(&doc.track)
             .arg("--tag")
-            .arg(format!("label={}", doc.label));
+            .arg(format!("label={}", doc.label))
+            .arg("--tag")
+            .arg(format!("{CONTENT_HASH_TAG}={}", content_hash(&doc.text)));
 
         for tag in &doc.tags {
             command.arg("--tag").arg(tag);
@@ -206,8 +434,139 @@ where
             let stderr = String::from_utf8_lossy(&output.stderr);
             anyhow::bail!("`memvid put` failed for {}: {}", doc.uri, stderr);
         }
-        on_progress(idx + 1, total);
+        on_progress(idx + 1, total)?;
+    }
+
+    Ok(ExportDiff {
+        total_frames: docs.len(),
+        ..ExportDiff::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use serde_json::Value;
+
+    fn docs(text: impl Fn(&str) -> String) -> Vec<FrameDocument> {
+        ["alpha", "beta"]
+            .iter()
+            .map(|id| FrameDocument {
+                title: id.to_string(),
+                label: "Function".to_string(),
+                text: text(id),
+                uri: format!("mv2://nodes/{id}"),
+                track: "nodes".to_string(),
+                tags: Vec::new(),
+                metadata: Value::Null,
+            })
+            .collect()
     }
 
-    Ok(())
+    fn file_names(dir: &TempDir) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn capsule_is_renamed_into_place_with_its_digest() {
+        let dir = TempDir::new("writer-rename");
+        let path = dir.path().join("demo.mv2");
+        let (_, verified) = write_mv2_core_only(
+            &path,
+            &docs(|id| format!("fn {id}() {{}}")),
+            false,
+            None,
+            None,
+            |_, _| Ok(()),
+        )
+        .unwrap();
+        assert_eq!(verified.sha256, file_sha256(&path).unwrap());
+        assert_eq!(file_names(&dir), vec!["demo.mv2".to_string()]);
+    }
+
+    #[test]
+    fn verification_counts_documents_not_chunk_frames() {
+        let dir = TempDir::new("writer-verify");
+        let path = dir.path().join("demo.mv2");
+        // Long enough for memvid-core to add chunk frames.
+        let docs = docs(|id| format!("{id} body ").repeat(400));
+        let (_, verified) =
+            write_mv2_core_only(&path, &docs, false, None, None, |_, _| Ok(())).unwrap();
+        assert_eq!(verified.frame_count, 2);
+
+        let err = verify_capsule(&path, 3).unwrap_err();
+        assert!(err.to_string().contains("has 2 frames, expected 3"));
+    }
+
+    #[test]
+    fn files_that_are_not_capsules_fail_verification() {
+        let dir = TempDir::new("writer-garbage");
+        let path = dir.path().join("demo.mv2");
+        std::fs::write(&path, b"not a capsule").unwrap();
+        let err = verify_capsule(&path, 0).unwrap_err();
+        assert!(err.to_string().contains("does not open"));
+    }
+
+    #[test]
+    fn progress_is_reported_per_frame() {
+        let dir = TempDir::new("writer-progress");
+        let mut reported = Vec::new();
+        write_mv2_core_only(
+            &dir.path().join("demo.mv2"),
+            &docs(|id| id.to_string()),
+            false,
+            None,
+            None,
+            |written, total| {
+                reported.push((written, total));
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(reported, vec![(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn failed_writes_leave_no_files_behind() {
+        let dir = TempDir::new("writer-failed");
+        // Semantic exports need an embedding runtime.
+        let err = write_mv2_core_only(
+            &dir.path().join("demo.mv2"),
+            &docs(|id| id.to_string()),
+            true,
+            None,
+            None,
+            |_, _| Ok(()),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("requires valid embedding runtime"));
+        assert!(file_names(&dir).is_empty());
+    }
+
+    #[test]
+    fn interrupted_writes_stop_without_the_cli_fallback() {
+        let dir = TempDir::new("writer-interrupted");
+        let mut calls = 0;
+        let err = write_mv2(
+            &dir.path().join("demo.mv2"),
+            &docs(|id| id.to_string()),
+            false,
+            None,
+            None,
+            |_, _| {
+                calls += 1;
+                Err(WriteInterrupted.into())
+            },
+        )
+        .unwrap_err();
+        assert!(err.is::<WriteInterrupted>());
+        assert_eq!(calls, 1);
+        assert!(file_names(&dir).is_empty());
+    }
 }
@@ -0,0 +1,445 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Write as _},
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use crate::{models::JobState, AppState};
+
+const STAGE_BUCKETS: &[f64] = &[
+    0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0,
+];
+const EMBEDDING_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+#[derive(Debug, Clone)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(idx) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[idx] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Recorded {
+    stage_seconds: BTreeMap<String, Histogram>,
+    embedding_seconds: BTreeMap<String, Histogram>,
+    embedding_errors: BTreeMap<String, u64>,
+    runpod_transitions: BTreeMap<(String, String), u64>,
+    mcp_calls: BTreeMap<(String, String), u64>,
+    query_cache_hits: u64,
+    query_cache_misses: u64,
+    rate_limited: BTreeMap<(String, String), u64>,
+}
+
+/// Counters and histograms recorded as work happens. Gauges (queue depth,
+/// jobs by state, loaded indexes) are read from `AppState` at scrape time.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    recorded: Mutex<Recorded>,
+}
+
+/// Process-wide registry, so code without an `AppState` (embedding clients,
+/// the Runpod runner) can record too.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
+
+impl Metrics {
+    fn with<R>(&self, f: impl FnOnce(&mut Recorded) -> R) -> R {
+        let mut recorded = self.recorded.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut recorded)
+    }
+
+    pub fn observe_stage(&self, stage: &str, elapsed: Duration) {
+        self.with(|r| {
+            r.stage_seconds
+                .entry(stage.to_string())
+                .or_insert_with(|| Histogram::new(STAGE_BUCKETS))
+                .observe(elapsed.as_secs_f64())
+        });
+    }
+
+    pub fn observe_embedding(&self, provider: &str, elapsed: Duration, ok: bool) {
+        self.with(|r| {
+            r.embedding_seconds
+                .entry(provider.to_string())
+                .or_insert_with(|| Histogram::new(EMBEDDING_BUCKETS))
+                .observe(elapsed.as_secs_f64());
+            if !ok {
+                *r.embedding_errors.entry(provider.to_string()).or_default() += 1;
+            }
+        });
+    }
+
+    pub fn runpod_transition(&self, from: &str, to: &str) {
+        let from = if from.is_empty() { "none" } else { from };
+        self.with(|r| {
+            *r.runpod_transitions
+                .entry((from.to_string(), to.to_string()))
+                .or_default() += 1
+        });
+    }
+
+    pub fn mcp_call(&self, tool: &str, code: &str) {
+        self.with(|r| {
+            *r.mcp_calls
+                .entry((tool.to_string(), code.to_string()))
+                .or_default() += 1
+        });
+    }
+
+    pub fn query_cache(&self, hit: bool) {
+        self.with(|r| {
+            if hit {
+                r.query_cache_hits += 1;
+            } else {
+                r.query_cache_misses += 1;
+            }
+        });
+    }
+
+    pub fn rate_limited(&self, scope: &str, code: &str) {
+        self.with(|r| {
+            *r.rate_limited
+                .entry((scope.to_string(), code.to_string()))
+                .or_default() += 1
+        });
+    }
+
+    fn render_recorded(&self, out: &mut Exposition) {
+        let r = self.with(|r| r.clone());
+
+        out.header(
+            "memvid_export_stage_duration_seconds",
+            "histogram",
+            "Time export jobs spent in each stage.",
+        );
+        for (stage, histogram) in &r.stage_seconds {
+            out.histogram(
+                "memvid_export_stage_duration_seconds",
+                &[("stage", stage)],
+                histogram,
+            );
+        }
+
+        out.header(
+            "memvid_embedding_request_duration_seconds",
+            "histogram",
+            "Embedding batch latency by provider, including retries.",
+        );
+        for (provider, histogram) in &r.embedding_seconds {
+            out.histogram(
+                "memvid_embedding_request_duration_seconds",
+                &[("provider", provider)],
+                histogram,
+            );
+        }
+
+        out.header(
+            "memvid_embedding_errors_total",
+            "counter",
+            "Embedding batches that failed after retries, by provider.",
+        );
+        for (provider, count) in &r.embedding_errors {
+            out.sample(
+                "memvid_embedding_errors_total",
+                &[("provider", provider)],
+                count,
+            );
+        }
+
+        out.header(
+            "memvid_runpod_status_transitions_total",
+            "counter",
+            "Runpod job status changes observed while polling.",
+        );
+        for ((from, to), count) in &r.runpod_transitions {
+            out.sample(
+                "memvid_runpod_status_transitions_total",
+                &[("from", from), ("to", to)],
+                count,
+            );
+        }
+
+        out.header(
+            "memvid_mcp_tool_calls_total",
+            "counter",
+            "MCP tool calls by tool and result code.",
+        );
+        for ((tool, code), count) in &r.mcp_calls {
+            out.sample(
+                "memvid_mcp_tool_calls_total",
+                &[("tool", tool), ("code", code)],
+                count,
+            );
+        }
+
+        out.header(
+            "memvid_mcp_query_cache_requests_total",
+            "counter",
+            "MCP query cache lookups by result.",
+        );
+        out.sample(
+            "memvid_mcp_query_cache_requests_total",
+            &[("result", "hit")],
+            r.query_cache_hits,
+        );
+        out.sample(
+            "memvid_mcp_query_cache_requests_total",
+            &[("result", "miss")],
+            r.query_cache_misses,
+        );
+
+        out.header(
+            "memvid_rate_limit_rejections_total",
+            "counter",
+            "Requests rejected by rate limits or quotas.",
+        );
+        for ((scope, code), count) in &r.rate_limited {
+            out.sample(
+                "memvid_rate_limit_rejections_total",
+                &[("scope", scope), ("code", code)],
+                count,
+            );
+        }
+    }
+}
+
+/// Prometheus text exposition format, version 0.0.4.
+#[derive(Debug, Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {name} {help}");
+        let _ = writeln!(self.text, "# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            self.text.push('{');
+            for (idx, (label, raw)) in labels.iter().enumerate() {
+                if idx > 0 {
+                    self.text.push(',');
+                }
+                let escaped = raw
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                let _ = write!(self.text, "{label}=\"{escaped}\"");
+            }
+            self.text.push('}');
+        }
+        let _ = writeln!(self.text, " {value}");
+    }
+
+    fn histogram(&mut self, name: &str, labels: &[(&str, &str)], histogram: &Histogram) {
+        let bucket = format!("{name}_bucket");
+        let mut cumulative = 0;
+        for (bound, count) in histogram.bounds.iter().zip(&histogram.counts) {
+            cumulative += count;
+            let le = bound.to_string();
+            let mut with_le = labels.to_vec();
+            with_le.push(("le", &le));
+            self.sample(&bucket, &with_le, cumulative);
+        }
+        let mut with_le = labels.to_vec();
+        with_le.push(("le", "+Inf"));
+        self.sample(&bucket, &with_le, histogram.count);
+        self.sample(&format!("{name}_sum"), labels, histogram.sum);
+        self.sample(&format!("{name}_count"), labels, histogram.count);
+    }
+}
+
+pub async fn metrics_endpoint(State(state): State<AppState>) -> Response {
+    let mut out = Exposition::default();
+
+    out.header(
+        "memvid_export_queue_depth",
+        "gauge",
+        "Export jobs waiting in the worker queue.",
+    );
+    let depth = state.queue_tx.max_capacity() - state.queue_tx.capacity();
+    out.sample("memvid_export_queue_depth", &[], depth);
+
+    let mut by_state: BTreeMap<&'static str, usize> = [
+        JobState::Queued,
+        JobState::Running,
+        JobState::Completed,
+        JobState::Failed,
+        JobState::Canceled,
+        JobState::Expired,
+    ]
+    .iter()
+    .map(|job_state| (job_state.as_str(), 0))
+    .collect();
+    for job in state.jobs.read().await.values() {
+        *by_state.entry(job.status.as_str()).or_default() += 1;
+    }
+    out.header(
+        "memvid_export_jobs",
+        "gauge",
+        "Export jobs currently tracked, by state.",
+    );
+    for (job_state, count) in &by_state {
+        out.sample("memvid_export_jobs", &[("state", job_state)], count);
+    }
+
+    let indexes = state.mcp_indexes.lock().await.stats();
+    out.header(
+        "memvid_mcp_loaded_indexes",
+        "gauge",
+        "Capsule indexes held in the MCP index cache, by kind.",
+    );
+    out.sample(
+        "memvid_mcp_loaded_indexes",
+        &[("kind", "graph")],
+        indexes.graph,
+    );
+    out.sample(
+        "memvid_mcp_loaded_indexes",
+        &[("kind", "semantic")],
+        indexes.semantic,
+    );
+    out.header(
+        "memvid_mcp_index_cache_bytes",
+        "gauge",
+        "Approximate memory used by the MCP index cache.",
+    );
+    out.sample("memvid_mcp_index_cache_bytes", &[], indexes.bytes);
+
+    metrics().render_recorded(&mut out);
+
+    (
+        StatusCode::OK,
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        out.text,
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{job_record, test_config, test_state, TempDir};
+
+    fn rendered(metrics: &Metrics) -> String {
+        let mut out = Exposition::default();
+        metrics.render_recorded(&mut out);
+        out.text
+    }
+
+    #[test]
+    fn histograms_are_cumulative_with_sum_and_count() {
+        let metrics = Metrics::default();
+        metrics.observe_embedding("openai", Duration::from_millis(80), true);
+        metrics.observe_embedding("openai", Duration::from_secs(3), true);
+        metrics.observe_embedding("openai", Duration::from_secs(90), true);
+        let text = rendered(&metrics);
+
+        let bucket = |le: &str| {
+            format!("memvid_embedding_request_duration_seconds_bucket{{provider=\"openai\",le=\"{le}\"}}")
+        };
+        assert!(text.contains(&format!("{} 1\n", bucket("0.1"))));
+        assert!(text.contains(&format!("{} 2\n", bucket("5"))));
+        assert!(text.contains(&format!("{} 2\n", bucket("60"))));
+        assert!(text.contains(&format!("{} 3\n", bucket("+Inf"))));
+        assert!(text.contains(
+            "memvid_embedding_request_duration_seconds_sum{provider=\"openai\"} 93.08\n"
+        ));
+        assert!(text
+            .contains("memvid_embedding_request_duration_seconds_count{provider=\"openai\"} 3\n"));
+    }
+
+    #[test]
+    fn counters_are_labelled_by_their_dimensions() {
+        let metrics = Metrics::default();
+        metrics.observe_embedding("voyage", Duration::from_secs(1), false);
+        metrics.mcp_call("text_search", "ok");
+        metrics.mcp_call("text_search", "ok");
+        metrics.rate_limited("export", "RATE_LIMITED");
+        metrics.runpod_transition("", "IN_QUEUE");
+        metrics.query_cache(true);
+        let text = rendered(&metrics);
+
+        assert!(text.contains("memvid_embedding_errors_total{provider=\"voyage\"} 1\n"));
+        assert!(text.contains("memvid_mcp_tool_calls_total{tool=\"text_search\",code=\"ok\"} 2\n"));
+        assert!(text.contains(
+            "memvid_rate_limit_rejections_total{scope=\"export\",code=\"RATE_LIMITED\"} 1\n"
+        ));
+        assert!(text
+            .contains("memvid_runpod_status_transitions_total{from=\"none\",to=\"IN_QUEUE\"} 1\n"));
+        assert!(text.contains("memvid_mcp_query_cache_requests_total{result=\"hit\"} 1\n"));
+        assert!(text.contains("memvid_mcp_query_cache_requests_total{result=\"miss\"} 0\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut out = Exposition::default();
+        out.sample("m", &[("tool", "a\"b\\c\nd")], 1);
+        assert_eq!(out.text, "m{tool=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+
+    #[tokio::test]
+    async fn endpoint_reports_queue_depth_and_jobs_by_state() {
+        let dir = TempDir::new("metrics-endpoint");
+        let (state, _rx) = test_state(test_config(dir.path()));
+        for (id, status) in [
+            ("a", JobState::Running),
+            ("b", JobState::Running),
+            ("c", JobState::Failed),
+        ] {
+            state
+                .jobs
+                .write()
+                .await
+                .insert(id.to_string(), job_record(id, status));
+        }
+        state.queue_tx.send("a".to_string()).await.unwrap();
+
+        let response = metrics_endpoint(State(state)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("memvid_export_queue_depth 1\n"));
+        assert!(text.contains("memvid_export_jobs{state=\"running\"} 2\n"));
+        assert!(text.contains("memvid_export_jobs{state=\"failed\"} 1\n"));
+        assert!(text.contains("memvid_export_jobs{state=\"queued\"} 0\n"));
+        assert!(text.contains("memvid_mcp_loaded_indexes{kind=\"graph\"} 0\n"));
+    }
+}
@@ -78,6 +78,15 @@ pub struct ExportRequest {
     pub relationships: Vec<GraphRelationship>,
     pub file_contents: std::collections::HashMap<String, String>,
     pub options: ExportOptions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_export: Option<BaseExportRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BaseExportRef {
+    pub job_id: Option<String>,
+    pub capsule_path: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,7 +100,21 @@ pub enum JobState {
     Expired,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Canceled => "canceled",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// Declared in pipeline order, so stages compare by how far a job got.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 #[serde(rename_all = "snake_case")]
 pub enum ExportStage {
     Queued,
@@ -106,6 +129,23 @@ pub enum ExportStage {
     Expired,
 }
 
+impl ExportStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Transform => "transform",
+            Self::FramePrep => "frame_prep",
+            Self::WriteCapsule => "write_capsule",
+            Self::BuildSidecar => "build_sidecar",
+            Self::Finalize => "finalize",
+            Self::DownloadReady => "download_ready",
+            Self::Failed => "failed",
+            Self::Canceled => "canceled",
+            Self::Expired => "expired",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum ExportEventType {
@@ -155,6 +195,36 @@ pub struct ExportArtifact {
     pub download_url: String,
     pub expires_at: DateTime<Utc>,
     pub size_bytes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidecar_download_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle_download_url: Option<String>,
+    /// Hex SHA-256 of the capsule, recorded after it was reopened and its
+    /// frame count checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl ExportArtifact {
+    pub fn new(
+        job_id: &str,
+        file_name: String,
+        expires_at: DateTime<Utc>,
+        size_bytes: u64,
+        has_sidecar: bool,
+        sha256: Option<String>,
+    ) -> Self {
+        Self {
+            file_name,
+            download_url: format!("/v1/exports/{job_id}/download"),
+            expires_at,
+            size_bytes,
+            sidecar_download_url: has_sidecar
+                .then(|| format!("/v1/exports/{job_id}/download/sidecar")),
+            bundle_download_url: Some(format!("/v1/exports/{job_id}/download/bundle")),
+            sha256,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -215,7 +285,8 @@ pub struct FrameDocument {
     pub metadata: Value,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct JobRecord {
     pub job_id: String,
     pub created_at: DateTime<Utc>,
@@ -223,16 +294,39 @@ pub struct JobRecord {
     pub status: JobState,
     pub progress: f64,
     pub message: Option<String>,
+    #[serde(skip)]
     pub request: Option<ExportRequest>,
     pub artifact: Option<ExportArtifact>,
     pub error: Option<ExportErrorPayload>,
     pub artifact_path: Option<PathBuf>,
+    #[serde(skip)]
     pub events: VecDeque<ExportLogEvent>,
     pub next_seq: u64,
     pub current_stage: ExportStage,
     pub stage_progress: f64,
+    /// When `current_stage` was entered in this process; `None` after a
+    /// restart, so restored stages are not timed.
+    #[serde(skip)]
+    pub stage_started_at: Option<DateTime<Utc>>,
     pub last_event_at: DateTime<Utc>,
     pub metadata: Option<JobBackendMetadata>,
+    /// API key id that created the job; `None` for jobs created before keys
+    /// had ids.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_key_id: Option<String>,
+    /// Node and relationship frames the request asked for; counted against
+    /// the owner's daily frame quota.
+    #[serde(default)]
+    pub planned_frames: u64,
+    /// Size of the artifact the job produced. Unlike `artifact`, it is kept
+    /// once the artifact expires, so it keeps counting against the owner's
+    /// daily byte quota.
+    #[serde(default)]
+    pub produced_bytes: u64,
+    /// W3C `traceparent` of the request that created the job, so worker and
+    /// Runpod spans join the same trace (also after a restart).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_parent: Option<String>,
 }
 
 impl JobRecord {
@@ -266,6 +360,10 @@ pub struct JobBackendMetadata {
     pub payload_ref: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub artifact_ref: Option<String>,
+    /// Base capsule handed to the Runpod worker for an incremental export,
+    /// kept so a resubmission after a restart stays incremental.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_capsule_ref: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub worker_metrics: Option<Value>,
 }
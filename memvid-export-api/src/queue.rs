@@ -1,34 +1,51 @@
 use std::{
-    path::PathBuf,
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
     sync::{
         atomic::{AtomicBool, AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex as StdMutex,
     },
     time::Duration,
 };
 
 use anyhow::{Context, Result};
 use chrono::{Duration as ChronoDuration, Utc};
+use reqwest::Url;
 use serde_json::{json, Value};
-use tokio::{fs, sync::mpsc, time};
-use tracing::{error, info, warn};
+use tokio::{
+    fs,
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
+    time,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument, Span};
 
 use crate::{
-    artifact_store::{build_job_file_name, delete_file_if_exists, ensure_job_dir, job_output_path},
-    config::ExportBackendMode,
-    mcp_index::build_and_persist_from_request,
-    memvid_writer::write_mv2,
+    artifact_store::{
+        build_job_file_name, delete_artifact_set, ensure_job_dir, ensure_local_artifact,
+        job_output_path, publish_artifact_set,
+    },
+    config::{Config, ExportBackendMode},
+    http_client::without_query,
+    job_store::JobSnapshot,
+    mcp_index::{build_and_persist_from_request, sidecar_path_for_capsule},
+    memvid_writer::{file_sha256, write_mv2, WriteInterrupted},
+    metrics::metrics,
     models::{
-        ExportArtifact, ExportErrorPayload, ExportEventType, ExportLogEvent, ExportRequest,
-        ExportStage, JobState,
+        BaseExportRef, ExportArtifact, ExportErrorPayload, ExportEventType, ExportLogEvent,
+        ExportRequest, ExportStage, JobRecord, JobState,
     },
-    runpod::{RunpodClient, RunpodJobInput, RunpodPolicy, RunpodRunRequest},
+    runpod::{RunpodClient, RunpodJobInput, RunpodPolicy, RunpodRunRequest, WorkerProgress},
+    telemetry,
     transform::build_frame_documents,
     AppState,
 };
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(2);
 const MAX_JOB_EVENTS: usize = 5_000;
+/// Where Runpod jobs stand once a worker picks them up; worker progress
+/// updates move on from here.
+const RUNPOD_WORKER_START: f64 = 18.0;
 const WRITE_STAGE_START: f64 = 60.0;
 const WRITE_STAGE_END: f64 = 79.0;
 const SIDECAR_STAGE_START: f64 = 79.0;
@@ -61,6 +78,7 @@ fn lerp_stage_progress(stage_progress: f64, start: f64, end: f64) -> f64 {
     (start + (end - start) * t).clamp(0.0, 100.0)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn append_job_event(
     state: &AppState,
     job_id: &str,
@@ -74,7 +92,7 @@ pub async fn append_job_event(
     let message = message.into();
     let now = Utc::now();
 
-    let event = {
+    let (event, snapshot) = {
         let mut jobs = state.jobs.write().await;
         let Some(job) = jobs.get_mut(job_id) else {
             return Ok(None);
@@ -95,6 +113,13 @@ pub async fn append_job_event(
         let progress = progress.clamp(0.0, 100.0);
 
         job.progress = progress;
+        if job.current_stage != stage {
+            if let Some(started) = job.stage_started_at {
+                let elapsed = (now - started).to_std().unwrap_or_default();
+                metrics().observe_stage(job.current_stage.as_str(), elapsed);
+            }
+            job.stage_started_at = Some(now);
+        }
         job.current_stage = stage.clone();
         job.stage_progress = stage_progress;
         job.message = Some(message.clone());
@@ -118,9 +143,28 @@ pub async fn append_job_event(
         while job.events.len() > MAX_JOB_EVENTS {
             job.events.pop_front();
         }
-        event
+        // Heartbeats only refresh progress within a stage. They stay in memory
+        // and on the event stream; the next stage change or the final state
+        // persists the record.
+        let snapshot = (!matches!(event.event_type, ExportEventType::StageHeartbeat))
+            .then(|| JobSnapshot::from_record(job));
+        (event, snapshot)
     };
 
+    match snapshot {
+        Some(Ok(snapshot)) => {
+            if let Err(err) = state
+                .job_store
+                .append_event(snapshot, &event, MAX_JOB_EVENTS)
+                .await
+            {
+                warn!(job_id = %job_id, "Failed to persist job event: {err:#}");
+            }
+        }
+        Some(Err(err)) => warn!(job_id = %job_id, "Failed to snapshot job record: {err:#}"),
+        None => {}
+    }
+
     let sender = {
         let buses = state.event_buses.read().await;
         buses.get(job_id).cloned()
@@ -142,54 +186,456 @@ pub async fn append_job_event(
     Ok(Some(event))
 }
 
-pub fn spawn_export_worker(state: AppState, mut queue_rx: mpsc::Receiver<String>) {
-    tokio::spawn(async move {
-        while let Some(job_id) = queue_rx.recv().await {
-            info!(job_id = %job_id, "Worker picked export job");
-            let process_result = match state.config.backend_mode {
-                ExportBackendMode::LegacyVps => {
-                    process_export_job_legacy(state.clone(), &job_id).await
+pub async fn persist_job(state: &AppState, job_id: &str) {
+    let snapshot = {
+        let jobs = state.jobs.read().await;
+        let Some(job) = jobs.get(job_id) else {
+            return;
+        };
+        JobSnapshot::from_record(job)
+    };
+
+    let result = match snapshot {
+        Ok(snapshot) => state.job_store.save_job(snapshot).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        warn!(job_id = %job_id, "Failed to persist job record: {err:#}");
+    }
+}
+
+/// Rebuilds the in-memory job table from the job store and returns the job
+/// ids that should be pushed back onto the export queue.
+///
+/// Jobs that were `Running` restart from the beginning when their request
+/// payload is still stored, or staged on disk by the legacy writer. Runpod
+/// jobs that already staged or submitted their payload are re-attached in the
+/// background; anything else is marked failed.
+pub async fn restore_persisted_jobs(state: &AppState) -> Result<Vec<String>> {
+    let mut records = state.job_store.load_all(MAX_JOB_EVENTS).await?;
+    for record in &mut records {
+        if record.request.is_none()
+            && matches!(record.status, JobState::Queued | JobState::Running)
+            && is_legacy_job(record)
+        {
+            record.request = load_staged_request(state, &record.job_id).await;
+        }
+    }
+    let mut requeue = Vec::new();
+    let mut runpod_resume = Vec::new();
+    let mut interrupted = Vec::new();
+
+    {
+        let mut jobs = state.jobs.write().await;
+        let mut buses = state.event_buses.write().await;
+        for record in records {
+            let job_id = record.job_id.clone();
+            match record.status {
+                JobState::Queued | JobState::Running if record.request.is_some() => {
+                    requeue.push((job_id.clone(), matches!(record.status, JobState::Running)));
                 }
-                ExportBackendMode::RunpodQueue => {
-                    process_export_job_runpod(state.clone(), &job_id).await
+                JobState::Running if is_resumable_runpod_job(&record) => {
+                    runpod_resume.push(job_id.clone());
                 }
-            };
+                JobState::Queued | JobState::Running => interrupted.push(job_id.clone()),
+                _ => {}
+            }
+            if !matches!(record.status, JobState::Expired) {
+                let (sender, _) = tokio::sync::broadcast::channel(512);
+                buses.insert(job_id.clone(), sender);
+            }
+            jobs.insert(job_id, record);
+        }
+        info!(
+            jobs = jobs.len(),
+            requeued = requeue.len(),
+            runpod_resumed = runpod_resume.len(),
+            interrupted = interrupted.len(),
+            "Restored persisted export jobs"
+        );
+    }
 
-            if let Err(err) = process_result {
-                error!("Export job {job_id} failed: {err:#}");
-                let error_message = err.to_string();
-                {
-                    let mut jobs = state.jobs.write().await;
-                    if let Some(job) = jobs.get_mut(&job_id) {
-                        job.status = JobState::Failed;
-                        job.progress = 100.0;
-                        job.current_stage = ExportStage::Failed;
-                        job.stage_progress = 100.0;
-                        job.updated_at = Utc::now();
-                        job.error = Some(ExportErrorPayload {
-                            code: "EXPORT_FAILED".to_string(),
-                            message: error_message.clone(),
-                        });
-                        job.message = Some("Export failed".to_string());
-                        job.request = None;
-                    }
+    for job_id in &interrupted {
+        mark_job_interrupted(state, job_id).await;
+    }
+
+    for job_id in runpod_resume {
+        spawn_runpod_resume(state.clone(), job_id);
+    }
+
+    let mut queued_ids = Vec::with_capacity(requeue.len());
+    for (job_id, was_running) in requeue {
+        if was_running {
+            {
+                let mut jobs = state.jobs.write().await;
+                if let Some(job) = jobs.get_mut(&job_id) {
+                    reset_job_to_queued(job);
                 }
-                let _ = append_job_event(
-                    &state,
-                    &job_id,
-                    ExportEventType::JobFailed,
-                    ExportStage::Failed,
-                    100.0,
-                    Some(100.0),
-                    "Export failed",
-                    Some(json!({ "error": error_message })),
-                )
-                .await;
             }
+            let _ = append_job_event(
+                state,
+                &job_id,
+                ExportEventType::StageProgress,
+                ExportStage::Queued,
+                0.0,
+                Some(0.0),
+                "Requeued after server restart",
+                Some(json!({ "resumed": true })),
+            )
+            .await;
+        }
+        queued_ids.push(job_id);
+    }
+
+    Ok(queued_ids)
+}
+
+fn is_legacy_job(record: &JobRecord) -> bool {
+    record
+        .metadata
+        .as_ref()
+        .is_some_and(|meta| meta.backend == ExportBackendMode::LegacyVps.as_str())
+}
+
+fn is_resumable_runpod_job(record: &JobRecord) -> bool {
+    !is_legacy_job(record)
+        && record
+            .metadata
+            .as_ref()
+            .is_some_and(|meta| meta.runpod_job_id.is_some() || meta.payload_ref.is_some())
+}
+
+async fn load_staged_request(state: &AppState, job_id: &str) -> Option<ExportRequest> {
+    let path = staged_payload_path(state, job_id);
+    let raw = match fs::read(&path).await {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            warn!(job_id = %job_id, "Failed to read staged payload {}: {err}", path.display());
+            return None;
+        }
+    };
+    match serde_json::from_slice(&raw) {
+        Ok(request) => Some(request),
+        Err(err) => {
+            warn!(job_id = %job_id, "Staged payload is unreadable: {err}");
+            None
+        }
+    }
+}
+
+fn spawn_runpod_resume(state: AppState, job_id: String) {
+    let shutdown = state.shutdown.clone();
+    shutdown.spawn_job(async move {
+        let Ok(permit) = Arc::clone(&state.export_limits.workers)
+            .acquire_owned()
+            .await
+        else {
+            return;
+        };
+        let mut worker = WorkerSlot {
+            permit: Some(permit),
+            cancel: state.job_cancellations.register(&job_id),
+        };
+        let span = job_span(&state, &job_id).await;
+        async {
+            info!(job_id = %job_id, "Re-attaching to Runpod export job");
+            if let Err(err) = resume_export_job_runpod(&state, &job_id, &mut worker).await {
+                record_unless_stopped(&state, &job_id, &worker, err).await;
+            }
+        }
+        .instrument(span)
+        .await;
+        state.job_cancellations.remove(&job_id);
+    });
+}
+
+/// Span for a job's worker run, continuing the trace of the request that
+/// created the job.
+async fn job_span(state: &AppState, job_id: &str) -> Span {
+    let trace_parent = state
+        .jobs
+        .read()
+        .await
+        .get(job_id)
+        .and_then(|job| job.trace_parent.clone());
+    let span = info_span!("export_job", job_id = %job_id);
+    telemetry::set_parent(&span, trace_parent.as_deref());
+    span
+}
+
+fn reset_job_to_queued(job: &mut JobRecord) {
+    job.status = JobState::Queued;
+    job.progress = 0.0;
+    job.current_stage = ExportStage::Queued;
+    job.stage_progress = 0.0;
+    job.message = Some("Requeued after server restart".to_string());
+    job.error = None;
+    job.updated_at = Utc::now();
+}
+
+async fn mark_job_interrupted(state: &AppState, job_id: &str) {
+    let message = "Export was interrupted by a server restart";
+    {
+        let mut jobs = state.jobs.write().await;
+        let Some(job) = jobs.get_mut(job_id) else {
+            return;
+        };
+        job.status = JobState::Failed;
+        job.progress = 100.0;
+        job.current_stage = ExportStage::Failed;
+        job.stage_progress = 100.0;
+        job.updated_at = Utc::now();
+        job.error = Some(ExportErrorPayload {
+            code: "EXPORT_INTERRUPTED".to_string(),
+            message: message.to_string(),
+        });
+        job.message = Some(message.to_string());
+        job.request = None;
+    }
+    let _ = append_job_event(
+        state,
+        job_id,
+        ExportEventType::JobFailed,
+        ExportStage::Failed,
+        100.0,
+        Some(100.0),
+        message,
+        None,
+    )
+    .await;
+    persist_job(state, job_id).await;
+}
+
+/// Cancellation tokens of the jobs workers are currently running, so a
+/// cancel request can wake a job that is waiting for a slot, and shutdown can
+/// stop every running job. Tokens are children of one shutdown token, so jobs
+/// registered after [`JobCancellations::cancel_all`] start out cancelled.
+#[derive(Clone, Default)]
+pub struct JobCancellations {
+    tokens: Arc<StdMutex<HashMap<String, CancellationToken>>>,
+    all: CancellationToken,
+}
+
+impl JobCancellations {
+    fn register(&self, job_id: &str) -> CancellationToken {
+        let token = self.all.child_token();
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.insert(job_id.to_string(), token.clone());
+        }
+        token
+    }
+
+    fn remove(&self, job_id: &str) {
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.remove(job_id);
+        }
+    }
+
+    pub fn cancel(&self, job_id: &str) {
+        if let Some(token) = self
+            .tokens
+            .lock()
+            .ok()
+            .and_then(|tokens| tokens.get(job_id).cloned())
+        {
+            token.cancel();
+        }
+    }
+
+    pub fn cancel_all(&self) {
+        self.all.cancel();
+    }
+}
+
+pub struct ExportLimits {
+    workers: Arc<Semaphore>,
+    local_writes: Arc<Semaphore>,
+    runpod_jobs: Arc<Semaphore>,
+}
+
+impl ExportLimits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            workers: Arc::new(Semaphore::new(config.worker_concurrency)),
+            local_writes: Arc::new(Semaphore::new(config.local_write_concurrency)),
+            runpod_jobs: Arc::new(Semaphore::new(config.runpod_concurrency)),
+        }
+    }
+}
+
+/// Jobs still in the channel when shutdown starts stay `Queued` in the job
+/// store and are requeued on the next start.
+pub fn spawn_export_worker(state: AppState, mut queue_rx: mpsc::Receiver<String>) {
+    tokio::spawn(async move {
+        loop {
+            let next = tokio::select! {
+                _ = state.shutdown.draining() => None,
+                job_id = queue_rx.recv() => job_id,
+            };
+            let Some(job_id) = next else {
+                break;
+            };
+            let permit = tokio::select! {
+                _ = state.shutdown.draining() => break,
+                permit = Arc::clone(&state.export_limits.workers).acquire_owned() => permit,
+            };
+            let Ok(permit) = permit else {
+                break;
+            };
+            let task_state = state.clone();
+            state.shutdown.spawn_job(async move {
+                let mut worker = WorkerSlot {
+                    permit: Some(permit),
+                    cancel: task_state.job_cancellations.register(&job_id),
+                };
+                let span = job_span(&task_state, &job_id).await;
+                run_export_job(&task_state, &job_id, &mut worker)
+                    .instrument(span)
+                    .await;
+                task_state.job_cancellations.remove(&job_id);
+            });
         }
     });
 }
 
+/// A job's place in the worker pool: its pool permit, given up while the job
+/// waits for a backend slot, and its cancellation.
+struct WorkerSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    cancel: CancellationToken,
+}
+
+async fn run_export_job(state: &AppState, job_id: &str, worker: &mut WorkerSlot) {
+    info!(job_id = %job_id, "Worker picked export job");
+    let process_result = match state.config.backend_mode {
+        ExportBackendMode::LegacyVps => {
+            process_export_job_legacy(state.clone(), job_id, worker).await
+        }
+        ExportBackendMode::RunpodQueue => {
+            process_export_job_runpod(state.clone(), job_id, worker).await
+        }
+    };
+
+    if let Err(err) = process_result {
+        record_unless_stopped(state, job_id, worker, err).await;
+    }
+
+    if matches!(state.config.backend_mode, ExportBackendMode::LegacyVps) {
+        let finished = state
+            .jobs
+            .read()
+            .await
+            .get(job_id)
+            .is_none_or(|job| !matches!(job.status, JobState::Queued | JobState::Running));
+        if finished {
+            let path = staged_payload_path(state, job_id);
+            if let Err(err) = fs::remove_file(&path).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!(job_id = %job_id, "Failed to remove staged payload: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Records a job error as a failure unless the job was stopped on purpose: a
+/// cancelled job keeps its `Canceled` state and a job interrupted by shutdown
+/// stays `Running` so it resumes on the next start.
+async fn record_unless_stopped(
+    state: &AppState,
+    job_id: &str,
+    worker: &WorkerSlot,
+    err: anyhow::Error,
+) {
+    if worker.cancel.is_cancelled() {
+        info!(job_id = %job_id, "Export job stopped: {err:#}");
+        return;
+    }
+    record_job_failure(state, job_id, err).await;
+}
+
+async fn record_job_failure(state: &AppState, job_id: &str, err: anyhow::Error) {
+    error!("Export job {job_id} failed: {err:#}");
+    let error_message = err.to_string();
+    {
+        let mut jobs = state.jobs.write().await;
+        if let Some(job) = jobs.get_mut(job_id) {
+            job.status = JobState::Failed;
+            job.progress = 100.0;
+            job.current_stage = ExportStage::Failed;
+            job.stage_progress = 100.0;
+            job.updated_at = Utc::now();
+            job.error = Some(ExportErrorPayload {
+                code: "EXPORT_FAILED".to_string(),
+                message: error_message.clone(),
+            });
+            job.message = Some("Export failed".to_string());
+            job.request = None;
+        }
+    }
+    let _ = append_job_event(
+        state,
+        job_id,
+        ExportEventType::JobFailed,
+        ExportStage::Failed,
+        100.0,
+        Some(100.0),
+        "Export failed",
+        Some(json!({ "error": error_message })),
+    )
+    .await;
+    persist_job(state, job_id).await;
+}
+
+/// Takes a permit from a backend limit, reporting the wait on the job's event
+/// stream when the limit is currently saturated. The job gives its pool
+/// permit back while it waits, so jobs for other backends keep running, and
+/// takes it again once the backend slot is free. Returns `None` when the job
+/// is cancelled while waiting.
+async fn acquire_backend_slot(
+    state: &AppState,
+    job_id: &str,
+    worker: &mut WorkerSlot,
+    semaphore: &Arc<Semaphore>,
+    stage: ExportStage,
+    progress: f64,
+    message: &str,
+) -> Result<Option<OwnedSemaphorePermit>> {
+    if let Ok(permit) = Arc::clone(semaphore).try_acquire_owned() {
+        return Ok(Some(permit));
+    }
+
+    let _ = append_job_event(
+        state,
+        job_id,
+        ExportEventType::StageProgress,
+        stage,
+        progress,
+        Some(0.0),
+        message,
+        None,
+    )
+    .await?;
+
+    let held_pool_permit = worker.permit.take().is_some();
+    let permit = tokio::select! {
+        _ = worker.cancel.cancelled() => return Ok(None),
+        permit = Arc::clone(semaphore).acquire_owned() => {
+            permit.context("Export backend limit closed")?
+        }
+    };
+    if held_pool_permit {
+        worker.permit = Some(tokio::select! {
+            _ = worker.cancel.cancelled() => return Ok(None),
+            permit = Arc::clone(&state.export_limits.workers).acquire_owned() => {
+                permit.context("Export worker pool closed")?
+            }
+        });
+    }
+    Ok(Some(permit))
+}
+
 pub fn spawn_cleanup_worker(state: AppState) {
     tokio::spawn(async move {
         let mut interval = time::interval(Duration::from_secs(60));
@@ -198,6 +644,19 @@ pub fn spawn_cleanup_worker(state: AppState) {
             if let Err(err) = cleanup_expired_artifacts(&state).await {
                 warn!("Cleanup worker error: {err:#}");
             }
+            if let Err(err) = crate::uploads::cleanup_expired_uploads(&state).await {
+                warn!("Upload cleanup error: {err:#}");
+            }
+            if let Some(cache) = state.embedding_cache.clone() {
+                match tokio::task::spawn_blocking(move || cache.prune()).await {
+                    Ok(Ok(removed)) if removed > 0 => {
+                        info!(removed, "Pruned embedding cache entries");
+                    }
+                    Ok(Ok(_)) => {}
+                    Ok(Err(err)) => warn!("Embedding cache prune error: {err:#}"),
+                    Err(err) => warn!("Embedding cache prune task join error: {err}"),
+                }
+            }
         }
     });
 }
@@ -209,7 +668,17 @@ async fn is_canceled(state: &AppState, job_id: &str) -> bool {
         .unwrap_or(true)
 }
 
-async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()> {
+/// Whether a worker should stop the job: it was cancelled, or shutdown is
+/// interrupting running jobs.
+async fn should_stop(state: &AppState, job_id: &str, worker: &WorkerSlot) -> bool {
+    worker.cancel.is_cancelled() || is_canceled(state, job_id).await
+}
+
+async fn process_export_job_legacy(
+    state: AppState,
+    job_id: &str,
+    worker: &mut WorkerSlot,
+) -> Result<()> {
     let request = {
         let mut jobs = state.jobs.write().await;
         let Some(job) = jobs.get_mut(job_id) else {
@@ -244,6 +713,19 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
+    // Once the payload is on disk the job store no longer needs to carry it;
+    // a restart requeues the job from the staged copy.
+    let payload_path = write_staged_payload(&state, job_id, &request).await?;
+    {
+        let mut jobs = state.jobs.write().await;
+        if let Some(job) = jobs.get_mut(job_id) {
+            if let Some(meta) = job.metadata.as_mut() {
+                meta.payload_ref = Some(format!("file://{}", payload_path.display()));
+            }
+            job.request = None;
+        }
+    }
+
     info!(
         job_id = %job_id,
         session_id = %request.session_id,
@@ -260,7 +742,7 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     let output_path = job_output_path(&state.config.export_root, job_id, &file_name);
     ensure_job_dir(&output_path).await?;
 
-    if is_canceled(&state, job_id).await {
+    if should_stop(&state, job_id, worker).await {
         return Ok(());
     }
 
@@ -291,7 +773,25 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
-    if is_canceled(&state, job_id).await {
+    if should_stop(&state, job_id, worker).await {
+        return Ok(());
+    }
+
+    let Some(write_permit) = acquire_backend_slot(
+        &state,
+        job_id,
+        worker,
+        &state.export_limits.local_writes,
+        ExportStage::FramePrep,
+        45.0,
+        "Waiting for a capsule writer slot",
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    if should_stop(&state, job_id, worker).await {
         return Ok(());
     }
 
@@ -341,42 +841,69 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
         }
     });
 
+    let base_capsule = match request.base_export.as_ref() {
+        Some(base) => match resolve_base_capsule(&state, base).await {
+            Ok(path) => Some(path),
+            Err(err) => {
+                warn!(job_id = %job_id, "Base export unavailable, running full export: {err:#}");
+                None
+            }
+        },
+        None => None,
+    };
+
     let output_path_for_write = output_path.clone();
     let docs_for_write = docs.clone();
     let semantic_enabled = request.options.semantic_enabled;
     let embedding_config = if semantic_enabled {
-        Some(state.config.embedding_runtime_config()?)
+        Some(
+            state
+                .config
+                .embedding_runtime_config(state.http.clone())?
+                .with_cache(state.embedding_cache.clone()),
+        )
     } else {
         None
     };
     let written_for_write = Arc::clone(&written_frames);
+    let cancel_for_write = worker.cancel.clone();
 
+    let write_span = Span::current();
     let write_result = tokio::task::spawn_blocking(move || {
-        write_mv2(
-            &output_path_for_write,
-            &docs_for_write,
-            semantic_enabled,
-            embedding_config,
-            move |written, _total| {
-                written_for_write.store(written, Ordering::Relaxed);
-            },
-        )
+        write_span.in_scope(|| {
+            write_mv2(
+                &output_path_for_write,
+                &docs_for_write,
+                semantic_enabled,
+                embedding_config,
+                base_capsule.as_deref(),
+                move |written, _total| {
+                    written_for_write.store(written, Ordering::Relaxed);
+                    if cancel_for_write.is_cancelled() {
+                        return Err(WriteInterrupted.into());
+                    }
+                    Ok(())
+                },
+            )
+        })
     })
     .await;
 
     write_done.store(true, Ordering::Relaxed);
     let _ = write_heartbeat.await;
 
-    match write_result {
-        Ok(Ok(())) => {}
+    let (diff, verified) = match write_result {
+        Ok(Ok(written)) => written,
         Ok(Err(err)) => return Err(err),
         Err(err) => return Err(anyhow::anyhow!("MV2 writer task join error: {err}")),
-    }
+    };
 
     info!(
         job_id = %job_id,
         output_path = %output_path.display(),
-        "MV2 artifact write finished"
+        frame_count = verified.frame_count,
+        sha256 = %verified.sha256,
+        "MV2 artifact written and verified"
     );
 
     let _ = append_job_event(
@@ -386,13 +913,28 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
         ExportStage::WriteCapsule,
         WRITE_STAGE_END,
         Some(100.0),
-        "Capsule write complete",
-        None,
+        if diff.base_capsule.is_some() {
+            format!(
+                "Capsule write complete ({} unchanged, {} changed, {} added, {} removed frames; {} embeddings reused)",
+                diff.unchanged_frames,
+                diff.changed_frames,
+                diff.added_frames,
+                diff.removed_frames,
+                diff.reused_embeddings
+            )
+        } else {
+            "Capsule write complete".to_string()
+        },
+        Some(json!({
+            "diff": diff,
+            "frameCount": verified.frame_count,
+            "sha256": verified.sha256,
+        })),
     )
     .await?;
 
-    if is_canceled(&state, job_id).await {
-        delete_file_if_exists(&output_path).await?;
+    if should_stop(&state, job_id, worker).await {
+        delete_artifact_set(state.artifacts.as_ref(), &output_path).await?;
         return Ok(());
     }
 
@@ -478,8 +1020,10 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
-    if is_canceled(&state, job_id).await {
-        delete_file_if_exists(&output_path).await?;
+    drop(write_permit);
+
+    if should_stop(&state, job_id, worker).await {
+        delete_artifact_set(state.artifacts.as_ref(), &output_path).await?;
         return Ok(());
     }
 
@@ -498,6 +1042,9 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     let metadata = fs::metadata(&output_path)
         .await
         .with_context(|| format!("Failed to stat {}", output_path.display()))?;
+    let has_sidecar = fs::try_exists(sidecar_path_for_capsule(&output_path))
+        .await
+        .unwrap_or(false);
 
     let now = Utc::now();
     let expires_at = now + ChronoDuration::seconds(state.config.retention_seconds as i64);
@@ -514,20 +1061,26 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
+    publish_artifact_set(state.artifacts.as_ref(), &output_path)
+        .await
+        .with_context(|| format!("Failed to store artifact in {}", state.artifacts.name()))?;
+
     {
         let mut jobs = state.jobs.write().await;
         if let Some(job) = jobs.get_mut(job_id) {
             if matches!(job.status, JobState::Canceled) {
-                delete_file_if_exists(&output_path).await?;
+                delete_artifact_set(state.artifacts.as_ref(), &output_path).await?;
                 return Ok(());
             }
 
-            let artifact = ExportArtifact {
-                file_name: file_name.clone(),
-                download_url: format!("/v1/exports/{job_id}/download"),
+            let artifact = ExportArtifact::new(
+                job_id,
+                file_name.clone(),
                 expires_at,
-                size_bytes: metadata.len(),
-            };
+                metadata.len(),
+                has_sidecar,
+                Some(verified.sha256.clone()),
+            );
 
             job.status = JobState::Completed;
             job.progress = 100.0;
@@ -535,7 +1088,15 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
             job.stage_progress = 100.0;
             job.message = Some("Export completed".to_string());
             job.updated_at = now;
+            if let Some(meta) = job.metadata.as_mut() {
+                meta.worker_metrics = Some(json!({
+                    "backend": "legacy_vps",
+                    "diff": diff,
+                    "embeddingCache": diff.embedding_cache,
+                }));
+            }
             job.artifact = Some(artifact);
+            job.produced_bytes = metadata.len();
             job.artifact_path = Some(output_path.clone());
             job.error = None;
             job.request = None;
@@ -567,7 +1128,60 @@ async fn process_export_job_legacy(state: AppState, job_id: &str) -> Result<()>
     Ok(())
 }
 
-fn runpod_client_from_state(state: &AppState) -> Result<RunpodClient> {
+/// Resolves `ExportRequest.baseExport` to the capsule of a previous export.
+pub async fn resolve_base_capsule(state: &AppState, base: &BaseExportRef) -> Result<PathBuf> {
+    if let Some(job_id) = &base.job_id {
+        let jobs = state.jobs.read().await;
+        let job = jobs
+            .get(job_id)
+            .with_context(|| format!("Unknown base export job {job_id}"))?;
+        if !matches!(job.status, JobState::Completed) {
+            anyhow::bail!("Base export job {job_id} is not completed");
+        }
+        let path = job
+            .artifact_path
+            .clone()
+            .with_context(|| format!("Base export job {job_id} has no artifact"))?;
+        drop(jobs);
+        if !ensure_local_artifact(state.artifacts.as_ref(), &path).await? {
+            anyhow::bail!("Base export artifact for job {job_id} is missing");
+        }
+        return Ok(path);
+    }
+
+    if let Some(capsule_path) = &base.capsule_path {
+        let path = PathBuf::from(capsule_path);
+        let resolved = if path.is_absolute() {
+            path
+        } else {
+            state.config.export_root.join(path)
+        };
+        if !state.config.mcp_allow_external_capsules {
+            let canonical_root = state
+                .config
+                .export_root
+                .canonicalize()
+                .unwrap_or_else(|_| state.config.export_root.clone());
+            let canonical_candidate = resolved.canonicalize().unwrap_or_else(|_| resolved.clone());
+            if !canonical_candidate.starts_with(&canonical_root) {
+                anyhow::bail!(
+                    "baseExport.capsulePath must be inside export root unless MEMVID_MCP_ALLOW_EXTERNAL_CAPSULES=true"
+                );
+            }
+        }
+        if !resolved.exists() {
+            anyhow::bail!(
+                "baseExport.capsulePath does not exist: {}",
+                resolved.display()
+            );
+        }
+        return Ok(resolved);
+    }
+
+    anyhow::bail!("baseExport requires jobId or capsulePath")
+}
+
+pub fn runpod_client_from_state(state: &AppState) -> Result<RunpodClient> {
     let endpoint_id = state
         .config
         .runpod_endpoint_id
@@ -579,25 +1193,121 @@ fn runpod_client_from_state(state: &AppState) -> Result<RunpodClient> {
         .clone()
         .context("RUNPOD_API_KEY must be set when backend mode is runpod_queue")?;
     Ok(RunpodClient::new(
+        state.http.clone(),
         state.config.runpod_api_base.clone(),
         endpoint_id,
         api_key,
     ))
 }
 
+fn staged_output_dir(state: &AppState, job_id: &str) -> PathBuf {
+    state.config.staging_root.join("outputs").join(job_id)
+}
+
+/// Local path of a capsule the worker uploaded rather than wrote to the
+/// shared volume. Presigned uploads are keyed by it in the artifact store.
+fn staged_capsule_path(state: &AppState, job_id: &str) -> PathBuf {
+    staged_output_dir(state, job_id).join(format!("{job_id}.mv2"))
+}
+
+/// How long refs handed to a Runpod worker must stay valid: the job may
+/// wait in the queue for its TTL and then run for the execution timeout.
+fn runpod_ref_ttl(state: &AppState) -> Duration {
+    Duration::from_millis(state.config.runpod_ttl_ms + state.config.runpod_execution_timeout_ms)
+}
+
+/// A ref the worker can read `path` through: a presigned URL when the file is
+/// in an object store, otherwise a `file://` path on the shared volume.
+fn runpod_input_ref(state: &AppState, path: &Path, file_name: &str) -> Result<String> {
+    Ok(
+        match state
+            .artifacts
+            .presigned_get(path, file_name, runpod_ref_ttl(state))?
+        {
+            Some(url) => url,
+            None => format!("file://{}", path.display()),
+        },
+    )
+}
+
+/// Where a Runpod worker writes its output.
+struct RunpodOutput {
+    prefix: String,
+    artifact_upload_url: Option<String>,
+    sidecar_upload_url: Option<String>,
+}
+
+/// `RUNPOD_OUTPUT_PREFIX` wins, then presigned uploads into the artifact
+/// store, then the staging directory on the shared volume.
+fn runpod_output(state: &AppState, job_id: &str) -> Result<RunpodOutput> {
+    if let Some(prefix) = &state.config.runpod_output_prefix {
+        return Ok(RunpodOutput {
+            prefix: format!("{prefix}/{job_id}"),
+            artifact_upload_url: None,
+            sidecar_upload_url: None,
+        });
+    }
+
+    let capsule_path = staged_capsule_path(state, job_id);
+    let ttl = runpod_ref_ttl(state);
+    if let Some(artifact_url) = state.artifacts.presigned_put(&capsule_path, ttl)? {
+        let sidecar_url = state
+            .artifacts
+            .presigned_put(&sidecar_path_for_capsule(&capsule_path), ttl)?;
+        let prefix = without_query(&artifact_url)
+            .rsplit_once('/')
+            .map(|(dir, _)| dir.to_string())
+            .unwrap_or_default();
+        return Ok(RunpodOutput {
+            prefix,
+            artifact_upload_url: Some(artifact_url),
+            sidecar_upload_url: sidecar_url,
+        });
+    }
+
+    Ok(RunpodOutput {
+        prefix: format!("file://{}", staged_output_dir(state, job_id).display()),
+        artifact_upload_url: None,
+        sidecar_upload_url: None,
+    })
+}
+
+fn staged_payload_path(state: &AppState, job_id: &str) -> PathBuf {
+    state
+        .config
+        .staging_root
+        .join("payloads")
+        .join(format!("{job_id}.json"))
+}
+
+async fn write_staged_payload(
+    state: &AppState,
+    job_id: &str,
+    request: &ExportRequest,
+) -> Result<PathBuf> {
+    let payload_path = staged_payload_path(state, job_id);
+    if let Some(payload_dir) = payload_path.parent() {
+        fs::create_dir_all(payload_dir).await.with_context(|| {
+            format!(
+                "Failed to create payload staging dir {}",
+                payload_dir.display()
+            )
+        })?;
+    }
+    let payload_bytes =
+        serde_json::to_vec(request).context("Failed to serialize export payload")?;
+    fs::write(&payload_path, payload_bytes)
+        .await
+        .with_context(|| format!("Failed to write staged payload {}", payload_path.display()))?;
+    Ok(payload_path)
+}
+
 async fn stage_request_payload(
     state: &AppState,
     job_id: &str,
     request: &ExportRequest,
-) -> Result<(String, String, PathBuf)> {
-    let payload_dir = state.config.staging_root.join("payloads");
-    let output_dir = state.config.staging_root.join("outputs").join(job_id);
-    fs::create_dir_all(&payload_dir).await.with_context(|| {
-        format!(
-            "Failed to create payload staging dir {}",
-            payload_dir.display()
-        )
-    })?;
+) -> Result<(String, RunpodOutput)> {
+    let output_dir = staged_output_dir(state, job_id);
     fs::create_dir_all(&output_dir).await.with_context(|| {
         format!(
             "Failed to create output staging dir {}",
@@ -605,32 +1315,118 @@ async fn stage_request_payload(
         )
     })?;
 
-    let payload_path = payload_dir.join(format!("{job_id}.json"));
-    let payload_bytes =
-        serde_json::to_vec(request).context("Failed to serialize export payload")?;
-    fs::write(&payload_path, payload_bytes)
-        .await
-        .with_context(|| format!("Failed to write staged payload {}", payload_path.display()))?;
+    let payload_path = write_staged_payload(state, job_id, request).await?;
 
-    let payload_ref = format!("file://{}", payload_path.display());
-    let output_prefix = format!("file://{}", output_dir.display());
-    Ok((payload_ref, output_prefix, output_dir))
+    // Without a shared volume the worker reads the payload through a
+    // presigned URL; it has to stay valid while the job waits in the queue.
+    state.artifacts.put(&payload_path).await?;
+    let payload_ref = runpod_input_ref(state, &payload_path, &format!("{job_id}.json"))?;
+    Ok((payload_ref, runpod_output(state, job_id)?))
 }
 
-fn resolve_runpod_artifact_path(output: &Value) -> Option<PathBuf> {
-    output
-        .get("artifactPath")
+/// Local path of the finished capsule. Workers on the shared volume report
+/// `artifactPath`; workers that uploaded their output report an HTTP(S)
+/// `artifactRef` (and `sidecarRef`), which is fetched from the artifact store
+/// or downloaded. The flag is set when the capsule is already in the store.
+/// Local path the worker reported for its capsule. Only paths under the
+/// staging or export root are trusted, so a worker cannot make this API
+/// publish an arbitrary file.
+fn trusted_artifact_path(state: &AppState, value: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(value.strip_prefix("file://").unwrap_or(value));
+    let inside_roots = path.is_absolute()
+        && !path
+            .components()
+            .any(|part| matches!(part, Component::ParentDir))
+        && (path.starts_with(&state.config.staging_root)
+            || path.starts_with(&state.config.export_root));
+    if !inside_roots {
+        anyhow::bail!("Runpod artifactPath {value} is outside the staging and export roots");
+    }
+    Ok(path)
+}
+
+/// Whether `url` is output this job's worker was told to write: under
+/// `RUNPOD_OUTPUT_PREFIX/{job_id}/`, or the presigned object for `local_path`.
+/// Anything else is not downloaded, so a worker cannot make this API fetch
+/// arbitrary URLs.
+fn is_job_output_url(state: &AppState, job_id: &str, url: &str, local_path: &Path) -> Result<bool> {
+    let Ok(parsed) = Url::parse(url) else {
+        return Ok(false);
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Ok(false);
+    }
+    if let Some(prefix) = &state.config.runpod_output_prefix {
+        let job_prefix = Url::parse(&format!("{}/{job_id}/", prefix.trim_end_matches('/')));
+        return Ok(
+            job_prefix.is_ok_and(|job_prefix| parsed.as_str().starts_with(job_prefix.as_str()))
+        );
+    }
+    Ok(state
+        .artifacts
+        .presigned_put(local_path, runpod_ref_ttl(state))?
+        .is_some_and(|presigned| without_query(&presigned) == without_query(parsed.as_str())))
+}
+
+async fn resolve_runpod_artifact_path(
+    state: &AppState,
+    job_id: &str,
+    output: &Value,
+) -> Result<(PathBuf, bool)> {
+    if let Some(value) = output.get("artifactPath").and_then(|v| v.as_str()) {
+        return Ok((trusted_artifact_path(state, value)?, false));
+    }
+
+    let artifact_ref = output
+        .get("artifactRef")
         .and_then(|v| v.as_str())
-        .map(|value| {
-            if let Some(stripped) = value.strip_prefix("file://") {
-                PathBuf::from(stripped)
-            } else {
-                PathBuf::from(value)
-            }
-        })
+        .filter(|v| v.starts_with("http://") || v.starts_with("https://"))
+        .context("Runpod output missing artifactPath or an HTTP(S) artifactRef")?;
+    let capsule_path = staged_capsule_path(state, job_id);
+    if ensure_local_artifact(state.artifacts.as_ref(), &capsule_path).await? {
+        return Ok((capsule_path, true));
+    }
+
+    if !is_job_output_url(state, job_id, artifact_ref, &capsule_path)? {
+        anyhow::bail!("Runpod artifactRef {artifact_ref} is not this job's output location");
+    }
+    if !state
+        .http
+        .download_file(artifact_ref, &capsule_path)
+        .await?
+    {
+        anyhow::bail!("Runpod artifact not found at {artifact_ref}");
+    }
+    if let Some(sidecar_ref) = output.get("sidecarRef").and_then(|v| v.as_str()) {
+        let sidecar_path = sidecar_path_for_capsule(&capsule_path);
+        if !is_job_output_url(state, job_id, sidecar_ref, &sidecar_path)? {
+            warn!(job_id = %job_id, "Ignoring Runpod sidecarRef outside this job's output location: {sidecar_ref}");
+        } else if !state.http.download_file(sidecar_ref, &sidecar_path).await? {
+            warn!(job_id = %job_id, "Runpod sidecar not found at {sidecar_ref}");
+        }
+    }
+    Ok((capsule_path, false))
 }
 
-async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()> {
+async fn process_export_job_runpod(
+    state: AppState,
+    job_id: &str,
+    worker: &mut WorkerSlot,
+) -> Result<()> {
+    let Some(_runpod_permit) = acquire_backend_slot(
+        &state,
+        job_id,
+        worker,
+        &state.export_limits.runpod_jobs,
+        ExportStage::Queued,
+        0.0,
+        "Waiting for a Runpod submission slot",
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
     let request = {
         let mut jobs = state.jobs.write().await;
         let Some(job) = jobs.get_mut(job_id) else {
@@ -662,8 +1458,7 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
-    let (payload_ref, output_prefix, _output_dir) =
-        stage_request_payload(&state, job_id, &request).await?;
+    let (payload_ref, output) = stage_request_payload(&state, job_id, &request).await?;
 
     {
         let mut jobs = state.jobs.write().await;
@@ -676,16 +1471,133 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
         }
     }
 
+    let base_capsule_ref = match request.base_export.as_ref() {
+        Some(base) => match resolve_base_capsule(&state, base).await {
+            Ok(path) => match runpod_input_ref(&state, &path, "base.mv2") {
+                Ok(base_ref) => Some(base_ref),
+                Err(err) => {
+                    warn!(job_id = %job_id, "Base export unavailable, running full export: {err:#}");
+                    None
+                }
+            },
+            Err(err) => {
+                warn!(job_id = %job_id, "Base export unavailable, running full export: {err:#}");
+                None
+            }
+        },
+        None => None,
+    };
+    if base_capsule_ref.is_some() {
+        let mut jobs = state.jobs.write().await;
+        if let Some(meta) = jobs.get_mut(job_id).and_then(|job| job.metadata.as_mut()) {
+            meta.base_capsule_ref = base_capsule_ref.clone();
+        }
+    }
+
     let client = runpod_client_from_state(&state)?;
+    submit_runpod_job(
+        &state,
+        &client,
+        job_id,
+        payload_ref,
+        output,
+        base_capsule_ref,
+    )
+    .await?;
+    poll_runpod_job(&state, &client, job_id, &worker.cancel).await
+}
+
+/// Picks a Runpod job back up after a restart: polls it when it was already
+/// submitted, or resubmits the staged payload when the process stopped
+/// between staging and submission.
+async fn resume_export_job_runpod(
+    state: &AppState,
+    job_id: &str,
+    worker: &mut WorkerSlot,
+) -> Result<()> {
+    // The job resumes where it was checkpointed; its stage does not go back.
+    let (meta, stage, progress) = state
+        .jobs
+        .read()
+        .await
+        .get(job_id)
+        .and_then(|job| {
+            job.metadata
+                .clone()
+                .map(|meta| (meta, job.current_stage.clone(), job.progress))
+        })
+        .context("Missing backend metadata for Runpod job")?;
+
+    let Some(_runpod_permit) = acquire_backend_slot(
+        state,
+        job_id,
+        worker,
+        &state.export_limits.runpod_jobs,
+        stage.clone(),
+        progress,
+        "Waiting for a Runpod slot to re-attach",
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let client = runpod_client_from_state(state)?;
+    match meta.runpod_job_id {
+        Some(runpod_job_id) => {
+            let _ = append_job_event(
+                state,
+                job_id,
+                ExportEventType::StageProgress,
+                stage,
+                progress,
+                None,
+                format!("Re-attached to Runpod job ({runpod_job_id}) after server restart"),
+                Some(json!({ "runpodJobId": runpod_job_id, "resumed": true })),
+            )
+            .await?;
+        }
+        None => {
+            let payload_ref = meta
+                .payload_ref
+                .context("Missing staged payload for Runpod job")?;
+            let output = runpod_output(state, job_id)?;
+            submit_runpod_job(
+                state,
+                &client,
+                job_id,
+                payload_ref,
+                output,
+                meta.base_capsule_ref,
+            )
+            .await?;
+        }
+    }
+
+    poll_runpod_job(state, &client, job_id, &worker.cancel).await
+}
+
+async fn submit_runpod_job(
+    state: &AppState,
+    client: &RunpodClient,
+    job_id: &str,
+    payload_ref: String,
+    output: RunpodOutput,
+    base_capsule_ref: Option<String>,
+) -> Result<()> {
     let run_request = RunpodRunRequest {
         input: RunpodJobInput {
             job_id: job_id.to_string(),
-            payload_ref: payload_ref.clone(),
-            output_prefix,
+            payload_ref,
+            output_prefix: output.prefix,
             embedding_mode: state.config.embedding_mode.as_str().to_string(),
             embedding_provider: state.config.embedding_provider.clone(),
             embedding_model: state.config.embedding_model.clone(),
             ollama_host: state.config.ollama_host.clone(),
+            base_capsule_ref,
+            artifact_upload_url: output.artifact_upload_url,
+            sidecar_upload_url: output.sidecar_upload_url,
+            traceparent: telemetry::traceparent(&Span::current()),
         },
         policy: RunpodPolicy {
             execution_timeout: state.config.runpod_execution_timeout_ms,
@@ -694,10 +1606,10 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
     };
 
     let _ = append_job_event(
-        &state,
+        state,
         job_id,
         ExportEventType::StageProgress,
-        ExportStage::WriteCapsule,
+        ExportStage::Transform,
         8.0,
         Some(0.0),
         "Submitting Runpod queue job",
@@ -718,10 +1630,10 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
     }
 
     let _ = append_job_event(
-        &state,
+        state,
         job_id,
         ExportEventType::StageProgress,
-        ExportStage::WriteCapsule,
+        ExportStage::Transform,
         12.0,
         Some(5.0),
         format!("Runpod job submitted ({})", submitted.id),
@@ -729,9 +1641,95 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
     )
     .await?;
 
+    Ok(())
+}
+
+/// Overall progress range of each stage a Runpod worker reports, matching
+/// the ranges of the legacy writer.
+fn worker_stage_range(stage: &ExportStage) -> (f64, f64) {
+    match stage {
+        ExportStage::Transform => (RUNPOD_WORKER_START, 20.0),
+        ExportStage::FramePrep => (20.0, 45.0),
+        ExportStage::WriteCapsule => (WRITE_STAGE_START, WRITE_STAGE_END),
+        ExportStage::BuildSidecar => (SIDECAR_STAGE_START, SIDECAR_STAGE_END),
+        _ => (SIDECAR_STAGE_END, 95.0),
+    }
+}
+
+/// Turns a worker progress update into job events: a `StageProgress` event
+/// when the stage changes, heartbeats with frame counts within a stage.
+/// Reports of a stage the job has already passed (e.g. after re-attaching)
+/// are dropped, so the job's stage never goes back.
+async fn report_worker_progress(
+    state: &AppState,
+    job_id: &str,
+    runpod_job_id: &str,
+    progress: &WorkerProgress,
+) {
+    let Some(current_stage) = state
+        .jobs
+        .read()
+        .await
+        .get(job_id)
+        .map(|job| job.current_stage.clone())
+    else {
+        return;
+    };
+    if progress.stage < current_stage {
+        return;
+    }
+    let (start, end) = worker_stage_range(&progress.stage);
+    let event_type = if progress.stage == current_stage {
+        ExportEventType::StageHeartbeat
+    } else {
+        ExportEventType::StageProgress
+    };
+    let message = match (
+        &progress.stage,
+        progress.written_frames,
+        progress.total_frames,
+    ) {
+        (ExportStage::WriteCapsule, Some(written), Some(total)) => {
+            format!("Writing capsule frames {written}/{total}")
+        }
+        (ExportStage::Transform, ..) => "Runpod worker loading payload".to_string(),
+        (ExportStage::FramePrep, ..) => "Runpod worker preparing frame documents".to_string(),
+        (ExportStage::WriteCapsule, ..) => "Runpod worker writing capsule".to_string(),
+        (ExportStage::BuildSidecar, ..) => "Runpod worker building sidecar index".to_string(),
+        _ => "Runpod worker finalizing artifact".to_string(),
+    };
+    let _ = append_job_event(
+        state,
+        job_id,
+        event_type,
+        progress.stage.clone(),
+        lerp_stage_progress(progress.stage_progress, start, end),
+        Some(progress.stage_progress),
+        message,
+        Some(json!({
+            "runpodJobId": runpod_job_id,
+            "writtenFrames": progress.written_frames,
+            "totalFrames": progress.total_frames,
+            "stageTimingsMs": progress.stage_timings_ms,
+            "elapsedMs": progress.elapsed_ms,
+        })),
+    )
+    .await;
+}
+
+/// Polls until the Runpod job finishes. When the job's token is cancelled
+/// without the job being `Canceled`, shutdown is interrupting it: polling
+/// stops and the remote job is left running to be re-attached on restart.
+async fn poll_runpod_job(
+    state: &AppState,
+    client: &RunpodClient,
+    job_id: &str,
+    cancel: &CancellationToken,
+) -> Result<()> {
     let mut last_status = String::new();
+    let mut last_progress: Option<WorkerProgress> = None;
     loop {
-        if is_canceled(&state, job_id).await {
+        if is_canceled(state, job_id).await {
             if let Some(runpod_job_id) = {
                 let jobs = state.jobs.read().await;
                 jobs.get(job_id)
@@ -742,6 +1740,9 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
             }
             return Ok(());
         }
+        if cancel.is_cancelled() {
+            return Ok(());
+        }
 
         let runpod_job_id = {
             let jobs = state.jobs.read().await;
@@ -753,22 +1754,34 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
 
         let status = client.get_status(&runpod_job_id).await?;
         if status.status != last_status {
-            let (progress, stage_progress, msg) = match status.status.as_str() {
-                "IN_QUEUE" => (15.0, 10.0, "Runpod job in queue"),
-                "IN_PROGRESS" => (45.0, 55.0, "Runpod worker processing export"),
-                "COMPLETED" => (95.0, 100.0, "Runpod worker completed export"),
-                "CANCELLED" => (100.0, 100.0, "Runpod worker canceled export"),
-                "FAILED" => (100.0, 100.0, "Runpod worker failed export"),
-                "TIMED_OUT" => (100.0, 100.0, "Runpod worker timed out"),
-                _ => (40.0, 40.0, "Runpod status update"),
+            metrics().runpod_transition(&last_status, &status.status);
+            let (progress, msg) = match status.status.as_str() {
+                "IN_QUEUE" => (15.0, "Runpod job in queue"),
+                "IN_PROGRESS" => (RUNPOD_WORKER_START, "Runpod worker processing export"),
+                "COMPLETED" => (95.0, "Runpod worker completed export"),
+                "CANCELLED" => (100.0, "Runpod worker canceled export"),
+                "FAILED" => (100.0, "Runpod worker failed export"),
+                "TIMED_OUT" => (100.0, "Runpod worker timed out"),
+                _ => (40.0, "Runpod status update"),
+            };
+            // Status changes say nothing about the worker's stage; the job
+            // stays where it is until the worker reports progress.
+            let Some((stage, current_progress)) = state
+                .jobs
+                .read()
+                .await
+                .get(job_id)
+                .map(|job| (job.current_stage.clone(), job.progress))
+            else {
+                return Ok(());
             };
             let _ = append_job_event(
-                &state,
+                state,
                 job_id,
                 ExportEventType::StageHeartbeat,
-                ExportStage::WriteCapsule,
-                progress,
-                Some(stage_progress),
+                stage,
+                progress.max(current_progress),
+                None,
                 msg,
                 Some(json!({
                     "runpodStatus": status.status,
@@ -779,23 +1792,63 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
             last_status = status.status.clone();
         }
 
+        // Workers report progress as the status output until they complete.
+        let progress = status
+            .output
+            .clone()
+            .filter(|_| status.status == "IN_PROGRESS")
+            .and_then(|output| serde_json::from_value::<WorkerProgress>(output).ok());
+        if let Some(progress) = progress {
+            if last_progress.as_ref() != Some(&progress) {
+                report_worker_progress(state, job_id, &runpod_job_id, &progress).await;
+                last_progress = Some(progress);
+            }
+        }
+
         match status.status.as_str() {
             "IN_QUEUE" | "IN_PROGRESS" => {
-                time::sleep(Duration::from_secs(
-                    state.config.runpod_poll_interval_seconds,
-                ))
-                .await;
+                tokio::select! {
+                    _ = cancel.cancelled() => {}
+                    _ = time::sleep(Duration::from_secs(
+                        state.config.runpod_poll_interval_seconds,
+                    )) => {}
+                }
             }
             "COMPLETED" => {
                 let output = status
                     .output
                     .clone()
                     .context("Runpod completed without output payload")?;
-                let artifact_path = resolve_runpod_artifact_path(&output)
-                    .context("Runpod output missing artifactPath")?;
+                let (artifact_path, stored) =
+                    resolve_runpod_artifact_path(state, job_id, &output).await?;
                 let metadata = fs::metadata(&artifact_path)
                     .await
                     .with_context(|| format!("Failed to stat {}", artifact_path.display()))?;
+                // The worker verified the capsule before reporting its digest;
+                // re-hashing here catches a truncated or stale copy on the
+                // shared volume.
+                let path_for_hash = artifact_path.clone();
+                let sha256 = tokio::task::spawn_blocking(move || file_sha256(&path_for_hash))
+                    .await
+                    .context("Artifact hash task join error")??;
+                if let Some(reported) = output.get("sha256").and_then(|v| v.as_str()) {
+                    if reported != sha256 {
+                        anyhow::bail!(
+                            "Runpod artifact {} has SHA-256 {sha256}, worker reported {reported}",
+                            artifact_path.display()
+                        );
+                    }
+                }
+                let has_sidecar = fs::try_exists(sidecar_path_for_capsule(&artifact_path))
+                    .await
+                    .unwrap_or(false);
+                if !stored {
+                    publish_artifact_set(state.artifacts.as_ref(), &artifact_path)
+                        .await
+                        .with_context(|| {
+                            format!("Failed to store artifact in {}", state.artifacts.name())
+                        })?;
+                }
                 let file_name = output
                     .get("fileName")
                     .and_then(|v| v.as_str())
@@ -824,6 +1877,9 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
                                 "embeddingMode": state.config.embedding_mode.as_str(),
                                 "embeddingProvider": state.config.embedding_provider.as_str(),
                                 "embeddingModel": state.config.embedding_model.as_str(),
+                                "diff": output.get("diff").cloned().unwrap_or(Value::Null),
+                                "embeddingCache": output.get("embeddingCache").cloned().unwrap_or(Value::Null),
+                                "stageTimingsMs": output.get("stageTimingsMs").cloned().unwrap_or(Value::Null),
                             }));
                         }
                         job.status = JobState::Completed;
@@ -832,19 +1888,22 @@ async fn process_export_job_runpod(state: AppState, job_id: &str) -> Result<()>
                         job.stage_progress = 100.0;
                         job.message = Some("Export completed".to_string());
                         job.updated_at = now;
-                        job.artifact = Some(ExportArtifact {
-                            file_name: file_name.clone(),
-                            download_url: format!("/v1/exports/{job_id}/download"),
+                        job.artifact = Some(ExportArtifact::new(
+                            job_id,
+                            file_name.clone(),
                             expires_at,
-                            size_bytes: metadata.len(),
-                        });
+                            metadata.len(),
+                            has_sidecar,
+                            Some(sha256.clone()),
+                        ));
+                        job.produced_bytes = metadata.len();
                         job.artifact_path = Some(artifact_path.clone());
                         job.error = None;
                     }
                 }
 
                 let _ = append_job_event(
-                    &state,
+                    state,
                     job_id,
                     ExportEventType::JobCompleted,
                     ExportStage::DownloadReady,
@@ -921,7 +1980,7 @@ async fn cleanup_expired_artifacts(state: &AppState) -> Result<()> {
     }
 
     for path in files_to_delete {
-        if let Err(err) = delete_file_if_exists(&path).await {
+        if let Err(err) = delete_artifact_set(state.artifacts.as_ref(), &path).await {
             warn!(
                 "Failed to delete expired artifact {}: {err:#}",
                 path.display()
@@ -929,6 +1988,13 @@ async fn cleanup_expired_artifacts(state: &AppState) -> Result<()> {
         }
     }
 
+    for job_id in &expired_job_ids {
+        persist_job(state, job_id).await;
+        if let Err(err) = state.job_store.clear_events(job_id).await {
+            warn!(job_id = %job_id, "Failed to clear persisted job events: {err:#}");
+        }
+    }
+
     if !expired_job_ids.is_empty() {
         let mut buses = state.event_buses.write().await;
         for job_id in expired_job_ids {
@@ -938,3 +2004,535 @@ async fn cleanup_expired_artifacts(state: &AppState) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{job_record, sample_request, test_config, test_state, TempDir};
+
+    async fn save(state: &AppState, record: &JobRecord) {
+        state
+            .job_store
+            .save_job(JobSnapshot::from_record(record).unwrap())
+            .await
+            .unwrap();
+    }
+
+    async fn persisted(state: &AppState, job_id: &str) -> JobRecord {
+        state
+            .job_store
+            .load_all(MAX_JOB_EVENTS)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|record| record.job_id == job_id)
+            .unwrap()
+    }
+
+    fn limited_state(dir: &TempDir) -> AppState {
+        let mut config = test_config(dir.path());
+        config.worker_concurrency = 2;
+        config.local_write_concurrency = 1;
+        config.runpod_concurrency = 1;
+        test_state(config).0
+    }
+
+    fn pool_slot(state: &AppState, job_id: &str) -> WorkerSlot {
+        WorkerSlot {
+            permit: Some(
+                Arc::clone(&state.export_limits.workers)
+                    .try_acquire_owned()
+                    .expect("free pool permit"),
+            ),
+            cancel: state.job_cancellations.register(job_id),
+        }
+    }
+
+    fn spawn_write_wait(
+        state: &AppState,
+        job_id: &str,
+    ) -> tokio::task::JoinHandle<(WorkerSlot, Option<OwnedSemaphorePermit>)> {
+        let state = state.clone();
+        let job_id = job_id.to_string();
+        let mut worker = pool_slot(&state, &job_id);
+        tokio::spawn(async move {
+            let permit = acquire_backend_slot(
+                &state,
+                &job_id,
+                &mut worker,
+                &state.export_limits.local_writes,
+                ExportStage::FramePrep,
+                45.0,
+                "Waiting for a capsule writer slot",
+            )
+            .await
+            .unwrap();
+            (worker, permit)
+        })
+    }
+
+    #[tokio::test]
+    async fn local_writes_are_serialized_while_other_backends_keep_running() {
+        let dir = TempDir::new("queue-slots");
+        let state = limited_state(&dir);
+        let limits = &state.export_limits;
+
+        let mut first = pool_slot(&state, "job-a");
+        let first_write = acquire_backend_slot(
+            &state,
+            "job-a",
+            &mut first,
+            &limits.local_writes,
+            ExportStage::FramePrep,
+            45.0,
+            "",
+        )
+        .await
+        .unwrap()
+        .expect("free write slot");
+
+        let second = spawn_write_wait(&state, "job-b");
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+        // The waiting job gave its pool permit back.
+        assert_eq!(limits.workers.available_permits(), 1);
+
+        let mut runpod = pool_slot(&state, "job-c");
+        let runpod_slot = acquire_backend_slot(
+            &state,
+            "job-c",
+            &mut runpod,
+            &limits.runpod_jobs,
+            ExportStage::Queued,
+            0.0,
+            "",
+        )
+        .await
+        .unwrap();
+        assert!(runpod_slot.is_some());
+        drop((runpod, runpod_slot));
+
+        drop(first_write);
+        let (second, second_write) = time::timeout(Duration::from_secs(5), second)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(second_write.is_some());
+        assert!(second.permit.is_some());
+        assert_eq!(limits.local_writes.available_permits(), 0);
+        drop(first);
+        assert_eq!(limits.workers.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn cancel_wakes_a_job_waiting_for_a_backend_slot() {
+        let dir = TempDir::new("queue-slot-cancel");
+        let state = limited_state(&dir);
+        let _held = Arc::clone(&state.export_limits.local_writes)
+            .try_acquire_owned()
+            .unwrap();
+
+        let waiting = spawn_write_wait(&state, "job-b");
+        time::sleep(Duration::from_millis(50)).await;
+        state.job_cancellations.cancel("job-b");
+
+        let (worker, permit) = time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(permit.is_none());
+        assert!(worker.permit.is_none());
+        assert_eq!(state.export_limits.workers.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn heartbeats_are_persisted_with_the_next_stage_change() {
+        let dir = TempDir::new("queue-heartbeat");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let job = job_record("job-1", JobState::Running);
+        state.jobs.write().await.insert(job.job_id.clone(), job);
+
+        for (event_type, stage, progress) in [
+            (
+                ExportEventType::StageProgress,
+                ExportStage::WriteCapsule,
+                60.0,
+            ),
+            (
+                ExportEventType::StageHeartbeat,
+                ExportStage::WriteCapsule,
+                70.0,
+            ),
+        ] {
+            append_job_event(&state, "job-1", event_type, stage, progress, None, "", None)
+                .await
+                .unwrap();
+        }
+        let stored = persisted(&state, "job-1").await;
+        assert_eq!(stored.events.len(), 1);
+        assert_eq!(stored.progress, 60.0);
+        assert_eq!(state.jobs.read().await["job-1"].events.len(), 2);
+
+        append_job_event(
+            &state,
+            "job-1",
+            ExportEventType::StageProgress,
+            ExportStage::BuildSidecar,
+            79.0,
+            None,
+            "",
+            None,
+        )
+        .await
+        .unwrap();
+        let stored = persisted(&state, "job-1").await;
+        let seqs: Vec<u64> = stored.events.iter().map(|event| event.seq).collect();
+        assert_eq!(seqs, vec![1, 3]);
+        assert_eq!(stored.progress, 79.0);
+    }
+
+    /// Runpod API double: records `/run` bodies and reports `rp-running` as
+    /// in progress with its worker loading the payload, every other job as
+    /// queued.
+    async fn spawn_runpod_mock() -> (String, Arc<StdMutex<Vec<Value>>>) {
+        use axum::{
+            extract::Path as UrlPath,
+            routing::{get, post},
+            Json, Router,
+        };
+        let submitted = Arc::new(StdMutex::new(Vec::new()));
+        let recorder = Arc::clone(&submitted);
+        let app = Router::new()
+            .route(
+                "/ep/run",
+                post(move |Json(body): Json<Value>| async move {
+                    recorder.lock().unwrap().push(body);
+                    Json(json!({ "id": "rp-new", "status": "IN_QUEUE" }))
+                }),
+            )
+            .route(
+                "/ep/status/{id}",
+                get(|UrlPath(id): UrlPath<String>| async move {
+                    if id == "rp-running" {
+                        let progress = json!({
+                            "stage": "transform",
+                            "stageProgress": 50.0,
+                            "elapsedMs": 10,
+                        });
+                        return Json(
+                            json!({ "id": id, "status": "IN_PROGRESS", "output": progress }),
+                        );
+                    }
+                    Json(json!({ "id": id, "status": "IN_QUEUE" }))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), submitted)
+    }
+
+    fn runpod_state(dir: &TempDir, api_base: String) -> AppState {
+        let mut config = test_config(dir.path());
+        config.backend_mode = ExportBackendMode::RunpodQueue;
+        config.runpod_api_base = api_base;
+        config.runpod_endpoint_id = Some("ep".to_string());
+        config.runpod_api_key = Some("rp-key".to_string());
+        test_state(config).0
+    }
+
+    fn runpod_record(job_id: &str) -> JobRecord {
+        let mut job = job_record(job_id, JobState::Running);
+        job.metadata.as_mut().unwrap().backend = ExportBackendMode::RunpodQueue.as_str().into();
+        job
+    }
+
+    async fn wait_for_event(state: &AppState, job_id: &str, needle: &str) {
+        for _ in 0..100 {
+            let found = state.jobs.read().await[job_id]
+                .events
+                .iter()
+                .any(|event| event.message.contains(needle));
+            if found {
+                return;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("{job_id} never reported {needle:?}");
+    }
+
+    #[tokio::test]
+    async fn restore_reattaches_requeues_or_interrupts_by_job_state() {
+        let dir = TempDir::new("queue-restore");
+        let (api_base, _) = spawn_runpod_mock().await;
+        let state = runpod_state(&dir, api_base);
+
+        let mut submitted = runpod_record("submitted");
+        submitted.metadata.as_mut().unwrap().runpod_job_id = Some("rp-running".into());
+        save(&state, &submitted).await;
+
+        let mut queued = job_record("queued", JobState::Queued);
+        queued.request = Some(sample_request());
+        state
+            .job_store
+            .insert_job(
+                JobSnapshot::from_record(&queued).unwrap(),
+                queued.request.as_ref().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        save(&state, &runpod_record("orphaned")).await;
+
+        let requeued = restore_persisted_jobs(&state).await.unwrap();
+        assert_eq!(requeued, vec!["queued".to_string()]);
+
+        wait_for_event(
+            &state,
+            "submitted",
+            "Re-attached to Runpod job (rp-running)",
+        )
+        .await;
+        let jobs = state.jobs.read().await;
+        assert!(matches!(jobs["submitted"].status, JobState::Running));
+        assert!(matches!(jobs["queued"].status, JobState::Queued));
+        assert!(matches!(jobs["orphaned"].status, JobState::Failed));
+        assert_eq!(
+            jobs["orphaned"].error.as_ref().map(|e| e.code.as_str()),
+            Some("EXPORT_INTERRUPTED")
+        );
+    }
+
+    #[tokio::test]
+    async fn resubmitted_runpod_jobs_keep_their_base_capsule() {
+        let dir = TempDir::new("queue-resubmit");
+        let (api_base, submitted) = spawn_runpod_mock().await;
+        let state = runpod_state(&dir, api_base);
+
+        let mut staged = runpod_record("staged");
+        let meta = staged.metadata.as_mut().unwrap();
+        meta.payload_ref = Some("file:///staging/payloads/staged.json".into());
+        meta.base_capsule_ref = Some("file:///exports/base/base.mv2".into());
+        save(&state, &staged).await;
+
+        restore_persisted_jobs(&state).await.unwrap();
+        wait_for_event(&state, "staged", "Runpod job submitted (rp-new)").await;
+
+        let bodies = submitted.lock().unwrap().clone();
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(
+            bodies[0]["input"]["payloadRef"],
+            "file:///staging/payloads/staged.json"
+        );
+        assert_eq!(
+            bodies[0]["input"]["baseCapsuleRef"],
+            "file:///exports/base/base.mv2"
+        );
+    }
+
+    #[test]
+    fn artifact_paths_must_stay_inside_the_roots() {
+        let dir = TempDir::new("queue-artifact-path");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let staged = staged_capsule_path(&state, "job-1");
+        assert_eq!(
+            trusted_artifact_path(&state, &format!("file://{}", staged.display())).unwrap(),
+            staged
+        );
+        let exported = state.config.export_root.join("job-1/demo.mv2");
+        assert_eq!(
+            trusted_artifact_path(&state, exported.to_str().unwrap()).unwrap(),
+            exported
+        );
+
+        let escaping = state.config.staging_root.join("../../../etc/passwd");
+        for value in [
+            "/etc/passwd".to_string(),
+            "exports/job-1/demo.mv2".to_string(),
+            escaping.display().to_string(),
+        ] {
+            let err = trusted_artifact_path(&state, &value).unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("outside the staging and export roots"));
+        }
+    }
+
+    #[tokio::test]
+    async fn artifact_refs_are_only_downloaded_from_the_job_output_prefix() {
+        use axum::{routing::get, Router};
+        let app = Router::new()
+            .route("/out/job-1/demo.mv2", get(|| async { "capsule" }))
+            .route("/secret", get(|| async { "secret" }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let dir = TempDir::new("queue-artifact-ref");
+        let mut config = test_config(dir.path());
+        config.runpod_output_prefix = Some(format!("{base}/out/"));
+        let (state, _queue_rx) = test_state(config);
+
+        let output = json!({ "artifactRef": format!("{base}/out/job-1/demo.mv2") });
+        let (path, stored) = resolve_runpod_artifact_path(&state, "job-1", &output)
+            .await
+            .unwrap();
+        assert!(!stored);
+        assert_eq!(std::fs::read(&path).unwrap(), b"capsule");
+        std::fs::remove_file(&path).unwrap();
+
+        for artifact_ref in [
+            format!("{base}/secret"),
+            format!("{base}/out/job-2/demo.mv2"),
+            format!("{base}/out/job-1/../../secret"),
+            "http://169.254.169.254/out/job-1/demo.mv2".to_string(),
+        ] {
+            let output = json!({ "artifactRef": artifact_ref });
+            let err = resolve_runpod_artifact_path(&state, "job-1", &output)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("not this job's output location"));
+        }
+        assert!(!staged_capsule_path(&state, "job-1").exists());
+    }
+
+    #[test]
+    fn presigned_objects_of_the_job_are_trusted_refs() {
+        use crate::s3::{S3ArtifactStore, S3Config};
+        let dir = TempDir::new("queue-presigned-ref");
+        let (mut state, _queue_rx) = test_state(test_config(dir.path()));
+        state.artifacts = Arc::new(
+            S3ArtifactStore::new(
+                state.http.clone(),
+                S3Config {
+                    endpoint: "https://s3.example.com".to_string(),
+                    region: "us-east-1".to_string(),
+                    bucket: "artifacts".to_string(),
+                    access_key_id: "key".to_string(),
+                    secret_access_key: "secret".to_string(),
+                    prefix: String::new(),
+                    path_style: true,
+                },
+                state.config.export_root.clone(),
+                state.config.staging_root.clone(),
+            )
+            .unwrap(),
+        );
+        let capsule = staged_capsule_path(&state, "job-1");
+        let uploaded = runpod_output(&state, "job-1")
+            .unwrap()
+            .artifact_upload_url
+            .unwrap();
+        assert!(is_job_output_url(&state, "job-1", without_query(&uploaded), &capsule).unwrap());
+        let other = staged_capsule_path(&state, "job-2");
+        assert!(!is_job_output_url(&state, "job-1", &uploaded, &other).unwrap());
+        assert!(!is_job_output_url(
+            &state,
+            "job-1",
+            "https://s3.example.com/artifacts",
+            &capsule
+        )
+        .unwrap());
+    }
+
+    #[tokio::test]
+    async fn runpod_status_changes_keep_the_stage_until_the_worker_reports() {
+        let dir = TempDir::new("queue-poll-stage");
+        let (api_base, _) = spawn_runpod_mock().await;
+        let state = runpod_state(&dir, api_base);
+        let mut job = runpod_record("job-1");
+        job.metadata.as_mut().unwrap().runpod_job_id = Some("rp-running".into());
+        job.current_stage = ExportStage::Transform;
+        job.progress = 12.0;
+        state.jobs.write().await.insert("job-1".to_string(), job);
+
+        let cancel = CancellationToken::new();
+        let poll = {
+            let state = state.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                let client = runpod_client_from_state(&state).unwrap();
+                poll_runpod_job(&state, &client, "job-1", &cancel).await
+            })
+        };
+        wait_for_event(&state, "job-1", "Runpod worker loading payload").await;
+        cancel.cancel();
+        time::timeout(Duration::from_secs(5), poll)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let jobs = state.jobs.read().await;
+        let stages: Vec<&ExportStage> = jobs["job-1"].events.iter().map(|e| &e.stage).collect();
+        assert_eq!(
+            stages,
+            vec![&ExportStage::Transform, &ExportStage::Transform]
+        );
+        assert_eq!(
+            jobs["job-1"].events[0].message,
+            "Runpod worker processing export"
+        );
+        assert!(jobs["job-1"].progress >= RUNPOD_WORKER_START);
+    }
+
+    #[tokio::test]
+    async fn worker_reports_of_passed_stages_are_dropped() {
+        let dir = TempDir::new("queue-worker-stage");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let mut job = runpod_record("job-1");
+        job.current_stage = ExportStage::BuildSidecar;
+        job.progress = 80.0;
+        state.jobs.write().await.insert("job-1".to_string(), job);
+        let report = |stage: ExportStage| WorkerProgress {
+            stage,
+            stage_progress: 50.0,
+            written_frames: None,
+            total_frames: None,
+            stage_timings_ms: Default::default(),
+            elapsed_ms: 10,
+        };
+
+        report_worker_progress(&state, "job-1", "rp-1", &report(ExportStage::Transform)).await;
+        assert!(state.jobs.read().await["job-1"].events.is_empty());
+
+        report_worker_progress(&state, "job-1", "rp-1", &report(ExportStage::BuildSidecar)).await;
+        report_worker_progress(&state, "job-1", "rp-1", &report(ExportStage::Finalize)).await;
+        let jobs = state.jobs.read().await;
+        let events: Vec<(&ExportEventType, &ExportStage)> = jobs["job-1"]
+            .events
+            .iter()
+            .map(|event| (&event.event_type, &event.stage))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (&ExportEventType::StageHeartbeat, &ExportStage::BuildSidecar),
+                (&ExportEventType::StageProgress, &ExportStage::Finalize),
+            ]
+        );
+        assert_eq!(jobs["job-1"].current_stage, ExportStage::Finalize);
+    }
+
+    #[tokio::test]
+    async fn restore_requeues_legacy_jobs_from_their_staged_payload() {
+        let dir = TempDir::new("queue-staged");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let mut job = job_record("job-1", JobState::Running);
+        let payload_path = write_staged_payload(&state, "job-1", &sample_request())
+            .await
+            .unwrap();
+        job.metadata.as_mut().unwrap().payload_ref =
+            Some(format!("file://{}", payload_path.display()));
+        save(&state, &job).await;
+
+        let queued = restore_persisted_jobs(&state).await.unwrap();
+        assert_eq!(queued, vec!["job-1".to_string()]);
+        let jobs = state.jobs.read().await;
+        assert!(matches!(jobs["job-1"].status, JobState::Queued));
+        assert_eq!(
+            jobs["job-1"].request.as_ref().map(|r| r.nodes.len()),
+            Some(2)
+        );
+    }
+}
@@ -1,6 +1,29 @@
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
 
-use tokio::sync::Mutex;
+use anyhow::{Context, Result};
+use axum::{
+    body::Body,
+    http::{header::RETRY_AFTER, response::Builder, HeaderMap, HeaderValue, Response, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Days, Utc};
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use serde_json::json;
+use tracing::warn;
+
+use crate::{
+    config::Config,
+    metrics::metrics,
+    models::{JobRecord, JobState},
+};
 
 #[derive(Debug, Clone)]
 pub struct RateLimitHeaders {
@@ -15,61 +38,280 @@ pub struct RateLimitDecision {
     pub headers: RateLimitHeaders,
 }
 
+pub const RATE_LIMIT_DB_FILE_NAME: &str = "ratelimits.v1.sqlite";
+
+/// Idle buckets are swept at most this often.
+const SWEEP_INTERVAL_SECONDS: f64 = 60.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BucketSpec {
+    pub capacity: f64,
+    pub refill_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BucketOutcome {
+    pub allowed: bool,
+    pub tokens: f64,
+    /// When the bucket will be full again. A bucket idle past this point is
+    /// indistinguishable from a new one, so backends may drop it.
+    pub full_at: f64,
+}
+
+impl BucketSpec {
+    /// Refills a stored `(tokens, updated_at)` bucket, or starts a full one,
+    /// and takes one token if available.
+    pub fn take(&self, stored: Option<(f64, f64)>, now: f64) -> BucketOutcome {
+        let mut tokens = match stored {
+            Some((tokens, updated_at)) => {
+                let elapsed = (now - updated_at).max(0.0);
+                (tokens + elapsed * self.refill_per_sec).min(self.capacity)
+            }
+            None => self.capacity,
+        };
+        let allowed = tokens >= 1.0;
+        if allowed {
+            tokens -= 1.0;
+        }
+        BucketOutcome {
+            allowed,
+            tokens,
+            full_at: now + (self.capacity - tokens).max(0.0) / self.refill_per_sec,
+        }
+    }
+}
+
+pub type BackendFuture<'a> = Pin<Box<dyn Future<Output = Result<BucketOutcome>> + Send + 'a>>;
+
+/// Where token buckets live. The in-memory backend is per process; a shared
+/// backend makes limits hold across replicas behind a load balancer.
+pub trait RateLimitBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Atomically refills `key`'s bucket and takes one token if available.
+    fn acquire<'a>(&'a self, key: &'a str, spec: BucketSpec, now: f64) -> BackendFuture<'a>;
+}
+
+#[derive(Debug, Default)]
+struct MemoryBuckets {
+    buckets: HashMap<String, MemoryBucket>,
+    last_sweep: f64,
+}
+
 #[derive(Debug)]
-struct BucketState {
+struct MemoryBucket {
     tokens: f64,
-    last_refill: Instant,
+    updated_at: f64,
+    full_at: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryRateLimitBackend {
+    state: StdMutex<MemoryBuckets>,
+}
+
+impl MemoryRateLimitBackend {
+    fn acquire_now(&self, key: &str, spec: BucketSpec, now: f64) -> Result<BucketOutcome> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Rate limit bucket lock poisoned"))?;
+        if now - state.last_sweep >= SWEEP_INTERVAL_SECONDS {
+            state.buckets.retain(|_, bucket| bucket.full_at > now);
+            state.last_sweep = now;
+        }
+
+        let stored = state
+            .buckets
+            .get(key)
+            .map(|bucket| (bucket.tokens, bucket.updated_at));
+        let outcome = spec.take(stored, now);
+        state.buckets.insert(
+            key.to_string(),
+            MemoryBucket {
+                tokens: outcome.tokens,
+                updated_at: now,
+                full_at: outcome.full_at,
+            },
+        );
+        Ok(outcome)
+    }
+
+    #[cfg(test)]
+    fn bucket_count(&self) -> usize {
+        self.state.lock().unwrap().buckets.len()
+    }
+}
+
+impl RateLimitBackend for MemoryRateLimitBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn acquire<'a>(&'a self, key: &'a str, spec: BucketSpec, now: f64) -> BackendFuture<'a> {
+        Box::pin(async move { self.acquire_now(key, spec, now) })
+    }
 }
 
 #[derive(Debug)]
+struct SqliteBuckets {
+    conn: Connection,
+    last_sweep: f64,
+}
+
+/// Token buckets in a SQLite database in WAL mode. Every replica that opens
+/// the same file (on a shared volume) draws from the same buckets; each
+/// update runs in an immediate transaction so replicas serialize on it.
+#[derive(Debug, Clone)]
+pub struct SqliteRateLimitBackend {
+    state: Arc<StdMutex<SqliteBuckets>>,
+}
+
+impl SqliteRateLimitBackend {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed creating rate limit directory {}", parent.display())
+            })?;
+        }
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed opening rate limit database {}", path.display()))?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute_batch(
+            "
+            PRAGMA journal_mode = WAL;
+            PRAGMA synchronous = NORMAL;
+            CREATE TABLE IF NOT EXISTS rate_buckets (
+                bucket_key TEXT PRIMARY KEY,
+                tokens REAL NOT NULL,
+                updated_at REAL NOT NULL,
+                full_at REAL NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_rate_buckets_full_at ON rate_buckets(full_at);
+            ",
+        )
+        .context("Failed initializing rate limit schema")?;
+        Ok(Self {
+            state: Arc::new(StdMutex::new(SqliteBuckets {
+                conn,
+                last_sweep: 0.0,
+            })),
+        })
+    }
+
+    fn acquire_blocking(&self, key: &str, spec: BucketSpec, now: f64) -> Result<BucketOutcome> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Rate limit connection lock poisoned"))?;
+        if now - state.last_sweep >= SWEEP_INTERVAL_SECONDS {
+            state
+                .conn
+                .execute("DELETE FROM rate_buckets WHERE full_at<=?1", params![now])?;
+            state.last_sweep = now;
+        }
+
+        let tx = state
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let stored = tx
+            .query_row(
+                "SELECT tokens, updated_at FROM rate_buckets WHERE bucket_key=?1",
+                params![key],
+                |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+            )
+            .optional()?;
+        let outcome = spec.take(stored, now);
+        tx.execute(
+            "INSERT INTO rate_buckets(bucket_key, tokens, updated_at, full_at) VALUES(?1, ?2, ?3, ?4)
+             ON CONFLICT(bucket_key) DO UPDATE SET tokens=excluded.tokens, updated_at=excluded.updated_at, full_at=excluded.full_at",
+            params![key, outcome.tokens, now, outcome.full_at],
+        )?;
+        tx.commit()?;
+        Ok(outcome)
+    }
+}
+
+impl RateLimitBackend for SqliteRateLimitBackend {
+    fn name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn acquire<'a>(&'a self, key: &'a str, spec: BucketSpec, now: f64) -> BackendFuture<'a> {
+        let backend = self.clone();
+        let key = key.to_string();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || backend.acquire_blocking(&key, spec, now))
+                .await
+                .context("Rate limit task failed")?
+        })
+    }
+}
+
+/// A token-bucket limit over one backend. `scope` keeps the buckets of
+/// different limiters (MCP, export creation) apart in a shared backend.
 pub struct RateLimiter {
+    scope: &'static str,
     per_minute: u32,
     burst: u32,
-    buckets: Mutex<HashMap<String, BucketState>>,
+    backend: Arc<dyn RateLimitBackend>,
 }
 
 impl RateLimiter {
-    pub fn new(per_minute: u32, burst: u32) -> Self {
+    pub fn new(
+        scope: &'static str,
+        per_minute: u32,
+        burst: u32,
+        backend: Arc<dyn RateLimitBackend>,
+    ) -> Self {
         Self {
+            scope,
             per_minute: per_minute.max(1),
             burst: burst.max(1),
-            buckets: Mutex::new(HashMap::new()),
+            backend,
         }
     }
 
     pub async fn check(&self, key: &str) -> RateLimitDecision {
-        let now = Instant::now();
-        let refill_per_sec = f64::from(self.per_minute) / 60.0;
-        let capacity = f64::from(self.burst.max(self.per_minute));
+        let spec = BucketSpec {
+            capacity: f64::from(self.burst.max(self.per_minute)),
+            refill_per_sec: f64::from(self.per_minute) / 60.0,
+        };
+        let now = Utc::now().timestamp_micros() as f64 / 1_000_000.0;
+        let bucket_key = format!("{}|{key}", self.scope);
 
-        let mut guard = self.buckets.lock().await;
-        let bucket = guard.entry(key.to_string()).or_insert(BucketState {
-            tokens: capacity,
-            last_refill: now,
-        });
+        let outcome = match self.backend.acquire(&bucket_key, spec, now).await {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                // Fail open: an unreachable shared store should not take the
+                // API down with it.
+                warn!(
+                    backend = self.backend.name(),
+                    scope = self.scope,
+                    "Rate limit backend failed; allowing request: {err:#}"
+                );
+                BucketOutcome {
+                    allowed: true,
+                    tokens: spec.capacity - 1.0,
+                    full_at: now,
+                }
+            }
+        };
 
-        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
-        if elapsed > 0.0 {
-            let restored = elapsed * refill_per_sec;
-            bucket.tokens = (bucket.tokens + restored).min(capacity);
-            bucket.last_refill = now;
+        if !outcome.allowed {
+            metrics().rate_limited(self.scope, "RATE_LIMITED");
         }
 
-        let allowed = bucket.tokens >= 1.0;
-        if allowed {
-            bucket.tokens -= 1.0;
-        }
-
-        let remaining = bucket.tokens.floor().max(0.0) as u32;
-        let deficit = (1.0 - bucket.tokens).max(0.0);
+        let remaining = outcome.tokens.floor().max(0.0) as u32;
+        let deficit = (1.0 - outcome.tokens).max(0.0);
         let reset_seconds = if deficit <= 0.0 {
             0
         } else {
-            (deficit / refill_per_sec).ceil() as u64
+            (deficit / spec.refill_per_sec).ceil() as u64
         };
 
         RateLimitDecision {
-            allowed,
+            allowed: outcome.allowed,
             headers: RateLimitHeaders {
                 limit: self.per_minute,
                 remaining,
@@ -78,3 +320,381 @@ impl RateLimiter {
         }
     }
 }
+
+fn rate_header_values(headers: &RateLimitHeaders) -> [(&'static str, HeaderValue); 3] {
+    [
+        ("X-RateLimit-Limit", HeaderValue::from(headers.limit)),
+        (
+            "X-RateLimit-Remaining",
+            HeaderValue::from(headers.remaining),
+        ),
+        (
+            "X-RateLimit-Reset",
+            HeaderValue::from(headers.reset_seconds),
+        ),
+    ]
+}
+
+pub fn attach_rate_headers(mut builder: Builder, headers: RateLimitHeaders) -> Builder {
+    for (name, value) in rate_header_values(&headers) {
+        builder = builder.header(name, value);
+    }
+    builder
+}
+
+pub fn insert_rate_headers(target: &mut HeaderMap, headers: &RateLimitHeaders) {
+    for (name, value) in rate_header_values(headers) {
+        target.insert(name, value);
+    }
+}
+
+/// `429` in the REST error envelope, carrying the same `X-RateLimit-*`
+/// headers as MCP responses plus `Retry-After` when a wait time is known.
+pub fn rate_limited_response(
+    code: &str,
+    message: impl Into<String>,
+    headers: RateLimitHeaders,
+) -> Response<Body> {
+    let retry_after_ms = headers.reset_seconds.saturating_mul(1000);
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(json!({
+            "error": {
+                "code": code,
+                "message": message.into(),
+                "retryable": true,
+                "retryAfterMs": retry_after_ms,
+            }
+        })),
+    )
+        .into_response();
+    insert_rate_headers(response.headers_mut(), &headers);
+    if headers.reset_seconds > 0 {
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(headers.reset_seconds));
+    }
+    response
+}
+
+/// Per-key limits on export creation. A zero limit disables that check.
+#[derive(Debug, Clone, Copy)]
+pub struct ExportQuota {
+    pub max_active_jobs: usize,
+    pub daily_frames: u64,
+    pub daily_bytes: u64,
+}
+
+/// One key's current export usage. Daily totals count jobs created since
+/// UTC midnight; bytes are the sizes of the artifacts those jobs produced,
+/// including artifacts that have since expired.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportUsage {
+    pub active_jobs: usize,
+    pub frames_today: u64,
+    pub bytes_today: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaViolation {
+    pub code: &'static str,
+    pub message: String,
+    pub retry_after_seconds: u64,
+}
+
+impl ExportUsage {
+    pub fn for_key<'a>(
+        jobs: impl IntoIterator<Item = &'a JobRecord>,
+        key_id: &str,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let day_start = day_start(now);
+        let mut usage = Self::default();
+        for job in jobs {
+            if job.owner_key_id.as_deref() != Some(key_id) {
+                continue;
+            }
+            if matches!(job.status, JobState::Queued | JobState::Running) {
+                usage.active_jobs += 1;
+            }
+            if job.created_at >= day_start {
+                usage.frames_today += job.planned_frames;
+                // Records written before `produced_bytes` only have the artifact.
+                let artifact_bytes = job.artifact.as_ref().map_or(0, |a| a.size_bytes);
+                usage.bytes_today += job.produced_bytes.max(artifact_bytes);
+            }
+        }
+        usage
+    }
+}
+
+impl ExportQuota {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_active_jobs: config.export_max_active_jobs_per_key,
+            daily_frames: config.export_daily_frame_quota,
+            daily_bytes: config.export_daily_byte_quota,
+        }
+    }
+
+    /// Checks whether a new job with `planned_frames` frames fits. The
+    /// submitting job's own frames count towards the daily frame cap.
+    pub fn check(
+        &self,
+        usage: &ExportUsage,
+        planned_frames: u64,
+        now: DateTime<Utc>,
+    ) -> Result<(), QuotaViolation> {
+        let until_tomorrow = (day_start(now) + Days::new(1) - now).num_seconds().max(1) as u64;
+
+        if self.max_active_jobs > 0 && usage.active_jobs >= self.max_active_jobs {
+            return Err(QuotaViolation {
+                code: "ACTIVE_JOB_QUOTA_EXCEEDED",
+                message: format!(
+                    "This API key already has {} queued or running exports (limit {}).",
+                    usage.active_jobs, self.max_active_jobs
+                ),
+                retry_after_seconds: 0,
+            });
+        }
+        if self.daily_frames > 0 && usage.frames_today + planned_frames > self.daily_frames {
+            return Err(QuotaViolation {
+                code: "DAILY_FRAME_QUOTA_EXCEEDED",
+                message: format!(
+                    "Export would use {} frames; {} of the daily {} are already used.",
+                    planned_frames, usage.frames_today, self.daily_frames
+                ),
+                retry_after_seconds: until_tomorrow,
+            });
+        }
+        if self.daily_bytes > 0 && usage.bytes_today >= self.daily_bytes {
+            return Err(QuotaViolation {
+                code: "DAILY_BYTE_QUOTA_EXCEEDED",
+                message: format!(
+                    "Daily export volume of {} bytes is used up.",
+                    self.daily_bytes
+                ),
+                retry_after_seconds: until_tomorrow,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn day_start(now: DateTime<Utc>) -> DateTime<Utc> {
+    now.date_naive()
+        .and_hms_opt(0, 0, 0)
+        .map(|start| start.and_utc())
+        .unwrap_or(now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{job_record, TempDir};
+    use chrono::TimeZone;
+
+    const SPEC: BucketSpec = BucketSpec {
+        capacity: 2.0,
+        refill_per_sec: 1.0,
+    };
+
+    /// Backend whose store is always unreachable.
+    struct FailingBackend;
+
+    impl RateLimitBackend for FailingBackend {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn acquire<'a>(&'a self, _key: &'a str, _spec: BucketSpec, _now: f64) -> BackendFuture<'a> {
+            Box::pin(async { Err(anyhow::anyhow!("store unreachable")) })
+        }
+    }
+
+    #[test]
+    fn buckets_start_full_and_refill_up_to_capacity() {
+        let first = SPEC.take(None, 100.0);
+        assert!(first.allowed);
+        assert_eq!(first.tokens, 1.0);
+        assert_eq!(first.full_at, 101.0);
+
+        let empty = SPEC.take(Some((0.5, 100.0)), 100.0);
+        assert!(!empty.allowed);
+        assert_eq!(empty.full_at, 101.5);
+
+        let refilled = SPEC.take(Some((0.0, 100.0)), 500.0);
+        assert!(refilled.allowed);
+        assert_eq!(refilled.tokens, 1.0);
+    }
+
+    #[tokio::test]
+    async fn buckets_are_per_key_and_report_headers() {
+        let limiter = RateLimiter::new("test", 1, 1, Arc::new(MemoryRateLimitBackend::default()));
+        let first = limiter.check("a").await;
+        assert!(first.allowed);
+        assert_eq!(first.headers.remaining, 0);
+        assert_eq!(first.headers.reset_seconds, 60);
+
+        let denied = limiter.check("a").await;
+        assert!(!denied.allowed);
+        assert!(denied.headers.reset_seconds > 0);
+        assert!(limiter.check("b").await.allowed);
+    }
+
+    #[tokio::test]
+    async fn idle_memory_buckets_are_swept() {
+        let memory = MemoryRateLimitBackend::default();
+        memory.acquire("a", SPEC, 100.0).await.unwrap();
+        memory.acquire("b", SPEC, 150.0).await.unwrap();
+        assert_eq!(memory.bucket_count(), 2);
+        // Both buckets are full again by 161, so the sweep drops them.
+        memory.acquire("c", SPEC, 161.0).await.unwrap();
+        assert_eq!(memory.bucket_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn sqlite_buckets_are_shared_between_replicas() {
+        let dir = TempDir::new("ratelimit-shared");
+        let path = dir.path().join(RATE_LIMIT_DB_FILE_NAME);
+        let replica_a = SqliteRateLimitBackend::open(&path).unwrap();
+        let replica_b = SqliteRateLimitBackend::open(&path).unwrap();
+        assert!(replica_a.acquire("k", SPEC, 10.0).await.unwrap().allowed);
+        assert!(replica_b.acquire("k", SPEC, 10.0).await.unwrap().allowed);
+        assert!(!replica_a.acquire("k", SPEC, 10.0).await.unwrap().allowed);
+        assert!(replica_b.acquire("k", SPEC, 11.0).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn full_sqlite_buckets_are_swept() {
+        let dir = TempDir::new("ratelimit-sweep");
+        let backend = SqliteRateLimitBackend::open(&dir.path().join("buckets.sqlite")).unwrap();
+        backend.acquire("a", SPEC, 100.0).await.unwrap();
+        backend.acquire("b", SPEC, 200.0).await.unwrap();
+        let count: i64 = backend
+            .state
+            .lock()
+            .unwrap()
+            .conn
+            .query_row("SELECT COUNT(*) FROM rate_buckets", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test]
+    async fn unreachable_backends_fail_open() {
+        let limiter = RateLimiter::new("test", 60, 5, Arc::new(FailingBackend));
+        for _ in 0..10 {
+            assert!(limiter.check("a").await.allowed);
+        }
+    }
+
+    #[test]
+    fn rejections_carry_rate_headers_and_retry_after() {
+        let response = rate_limited_response(
+            "RATE_LIMITED",
+            "slow down",
+            RateLimitHeaders {
+                limit: 60,
+                remaining: 0,
+                reset_seconds: 7,
+            },
+        );
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers["X-RateLimit-Limit"], "60");
+        assert_eq!(headers["X-RateLimit-Remaining"], "0");
+        assert_eq!(headers[RETRY_AFTER], "7");
+    }
+
+    const QUOTA: ExportQuota = ExportQuota {
+        max_active_jobs: 2,
+        daily_frames: 1_000,
+        daily_bytes: 5_000,
+    };
+    const USAGE: ExportUsage = ExportUsage {
+        active_jobs: 1,
+        frames_today: 600,
+        bytes_today: 4_000,
+    };
+
+    fn late_evening() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 23, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn quota_admits_jobs_within_every_limit() {
+        assert!(QUOTA.check(&USAGE, 400, late_evening()).is_ok());
+        let unlimited = ExportQuota {
+            max_active_jobs: 0,
+            daily_frames: 0,
+            daily_bytes: 0,
+        };
+        let heavy = ExportUsage {
+            active_jobs: 50,
+            frames_today: u64::MAX / 2,
+            bytes_today: u64::MAX,
+        };
+        assert!(unlimited
+            .check(&heavy, u64::MAX / 2, late_evening())
+            .is_ok());
+    }
+
+    #[test]
+    fn active_job_limit_is_checked_first_without_retry_after() {
+        let busy = ExportUsage {
+            active_jobs: 2,
+            bytes_today: 9_000,
+            ..USAGE
+        };
+        let violation = QUOTA.check(&busy, 1, late_evening()).unwrap_err();
+        assert_eq!(violation.code, "ACTIVE_JOB_QUOTA_EXCEEDED");
+        assert_eq!(violation.retry_after_seconds, 0);
+    }
+
+    #[test]
+    fn daily_caps_retry_after_utc_midnight() {
+        let frames = QUOTA.check(&USAGE, 401, late_evening()).unwrap_err();
+        assert_eq!(frames.code, "DAILY_FRAME_QUOTA_EXCEEDED");
+        assert_eq!(frames.retry_after_seconds, 3_600);
+
+        let spent = ExportUsage {
+            bytes_today: 5_000,
+            ..USAGE
+        };
+        let bytes = QUOTA.check(&spent, 1, late_evening()).unwrap_err();
+        assert_eq!(bytes.code, "DAILY_BYTE_QUOTA_EXCEEDED");
+        assert_eq!(bytes.retry_after_seconds, 3_600);
+    }
+
+    #[test]
+    fn usage_counts_the_keys_jobs_from_today_including_expired_artifacts() {
+        let now = late_evening();
+        let job = |id: &str, owner: &str, status: JobState, produced_bytes: u64| {
+            let mut job = job_record(id, status);
+            job.owner_key_id = Some(owner.to_string());
+            job.created_at = now - chrono::Duration::hours(1);
+            job.planned_frames = 10;
+            job.produced_bytes = produced_bytes;
+            job
+        };
+        let mut yesterday = job("old", "ci", JobState::Completed, 1_000);
+        yesterday.created_at = now - chrono::Duration::days(1);
+        let jobs = [
+            job("running", "ci", JobState::Running, 0),
+            job("expired", "ci", JobState::Expired, 700),
+            job("other", "agent", JobState::Queued, 500),
+            yesterday,
+        ];
+
+        let usage = ExportUsage::for_key(&jobs, "ci", now);
+        assert_eq!(
+            usage,
+            ExportUsage {
+                active_jobs: 1,
+                frames_today: 20,
+                bytes_today: 700,
+            }
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::{Context, Result};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::{
+    config::ExportBackendMode, memvid_writer::memvid_cli_available,
+    queue::runpod_client_from_state, AppState,
+};
+
+const RUNPOD_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Check {
+    fn from_result(name: &'static str, result: Result<()>) -> Self {
+        match result {
+            Ok(()) => Self {
+                name,
+                status: CheckStatus::Pass,
+                reason: None,
+            },
+            Err(err) => Self {
+                name,
+                status: CheckStatus::Fail,
+                reason: Some(format!("{err:#}")),
+            },
+        }
+    }
+
+    fn skip(name: &'static str, reason: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Skip,
+            reason: Some(reason.to_string()),
+        }
+    }
+}
+
+/// Creates and removes a probe file, so read-only mounts and permission
+/// problems show up even when the directory exists.
+async fn probe_writable(dir: &Path) -> Result<()> {
+    let probe = dir.join(format!(".readyz-{}", Uuid::new_v4()));
+    fs::write(&probe, b"ok")
+        .await
+        .with_context(|| format!("{} is not writable", dir.display()))?;
+    fs::remove_file(&probe)
+        .await
+        .with_context(|| format!("Failed to remove probe file in {}", dir.display()))
+}
+
+async fn check_export_root(state: &AppState) -> Check {
+    let root = &state.config.export_root;
+    if state.config.export_root_is_fallback {
+        return Check::from_result(
+            "exportRoot",
+            Err(anyhow::anyhow!(
+                "Configured MEMVID_EXPORT_ROOT was not writable at startup; running on fallback {}",
+                root.display()
+            )),
+        );
+    }
+    Check::from_result("exportRoot", probe_writable(root).await)
+}
+
+async fn check_runpod(state: &AppState) -> Check {
+    if !matches!(state.config.backend_mode, ExportBackendMode::RunpodQueue) {
+        return Check::skip("runpod", "Backend mode is legacy_vps");
+    }
+    let result = async {
+        let client = runpod_client_from_state(state)?;
+        client.health(RUNPOD_PROBE_TIMEOUT).await?;
+        Ok(())
+    }
+    .await;
+    Check::from_result("runpod", result)
+}
+
+fn check_memvid_cli(state: &AppState) -> Check {
+    if !matches!(state.config.backend_mode, ExportBackendMode::LegacyVps) {
+        return Check::skip("memvidCli", "Capsules are written by the Runpod worker");
+    }
+    let result = if memvid_cli_available() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "`memvid` is not on PATH; exports cannot fall back to the CLI when memvid-core fails"
+        ))
+    };
+    Check::from_result("memvidCli", result)
+}
+
+/// Readiness probe. Unlike `/healthz`, this checks storage, embedding and
+/// backend configuration and answers 503 when any required check fails.
+pub async fn readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let (export_root, staging_root, artifact_store, runpod) = tokio::join!(
+        check_export_root(&state),
+        probe_writable(&state.config.staging_root),
+        state.artifacts.probe(),
+        check_runpod(&state),
+    );
+    let embedding = state
+        .config
+        .embedding_runtime_config(state.http.clone())
+        .map(|_| ());
+
+    let accepting = if state.shutdown.is_draining() {
+        Err(anyhow::anyhow!("Server is shutting down"))
+    } else {
+        Ok(())
+    };
+
+    let checks = vec![
+        Check::from_result("acceptingExports", accepting),
+        export_root,
+        Check::from_result("stagingRoot", staging_root),
+        Check::from_result("artifactStore", artifact_store),
+        Check::from_result("embedding", embedding),
+        check_memvid_cli(&state),
+        runpod,
+    ];
+    let ready = checks.iter().all(|check| check.status != CheckStatus::Fail);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "ready": ready,
+            "timestamp": Utc::now(),
+            "checks": checks,
+        })),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_config, test_state, TempDir};
+    use serde_json::Value;
+
+    async fn ready_body(state: AppState) -> (StatusCode, Value) {
+        let response = readyz(State(state)).await.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn check<'a>(body: &'a Value, name: &str) -> &'a Value {
+        body["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|check| check["name"] == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn writable_probe_leaves_no_files_behind() {
+        let dir = TempDir::new("readyz-probe");
+        probe_writable(dir.path()).await.unwrap();
+        assert!(std::fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_dirs_fail_with_a_reason() {
+        let dir = TempDir::new("readyz-missing");
+        let check = Check::from_result(
+            "stagingRoot",
+            probe_writable(&dir.path().join("missing")).await,
+        );
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.reason.unwrap().contains("is not writable"));
+    }
+
+    #[tokio::test]
+    async fn fallback_export_root_makes_the_instance_unready() {
+        let dir = TempDir::new("readyz-fallback");
+        let mut config = test_config(dir.path());
+        config.export_root_is_fallback = true;
+        std::fs::create_dir_all(&config.export_root).unwrap();
+        std::fs::create_dir_all(&config.staging_root).unwrap();
+        let (state, _rx) = test_state(config);
+
+        let (status, body) = ready_body(state).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["ready"], false);
+        assert_eq!(check(&body, "exportRoot")["status"], "fail");
+        assert!(check(&body, "exportRoot")["reason"]
+            .as_str()
+            .unwrap()
+            .contains("fallback"));
+        assert_eq!(check(&body, "stagingRoot")["status"], "pass");
+        assert_eq!(check(&body, "artifactStore")["status"], "pass");
+        assert_eq!(check(&body, "acceptingExports")["status"], "pass");
+        assert_eq!(check(&body, "runpod")["status"], "skip");
+    }
+}
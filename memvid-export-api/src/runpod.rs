@@ -1,7 +1,11 @@
+use std::{collections::BTreeMap, time::Duration};
+
 use anyhow::{Context, Result};
+use reqwest::Method;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tokio::process::Command;
+
+use crate::{http_client::HttpClient, models::ExportStage};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +25,35 @@ pub struct RunpodJobInput {
     pub embedding_model: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ollama_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_capsule_ref: Option<String>,
+    /// Presigned `PUT` URLs for the capsule and its sidecar, used instead of
+    /// object names under `output_prefix` when it points at an object store.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact_upload_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sidecar_upload_url: Option<String>,
+    /// W3C trace context of the export job, passed to `runpod-execute`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+}
+
+/// Progress reported by `runpod-execute` while the job is `IN_PROGRESS`. The
+/// worker handler forwards it as a Runpod progress update, which `/status`
+/// returns as `output` until the job completes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerProgress {
+    pub stage: ExportStage,
+    pub stage_progress: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written_frames: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_frames: Option<usize>,
+    /// Durations of the stages finished so far, keyed by stage name.
+    #[serde(default)]
+    pub stage_timings_ms: BTreeMap<String, u64>,
+    pub elapsed_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,14 +83,16 @@ pub struct RunpodStatusResponse {
 
 #[derive(Debug, Clone)]
 pub struct RunpodClient {
+    http: HttpClient,
     base_url: String,
     endpoint_id: String,
     api_key: String,
 }
 
 impl RunpodClient {
-    pub fn new(base_url: String, endpoint_id: String, api_key: String) -> Self {
+    pub fn new(http: HttpClient, base_url: String, endpoint_id: String, api_key: String) -> Self {
         Self {
+            http,
             base_url: base_url.trim_end_matches('/').to_string(),
             endpoint_id,
             api_key,
@@ -66,9 +101,12 @@ impl RunpodClient {
 
     pub async fn submit_job(&self, payload: &RunpodRunRequest) -> Result<RunpodRunResponse> {
         let url = format!("{}/{}/run", self.base_url, self.endpoint_id);
-        let body =
-            serde_json::to_string(payload).context("Failed to encode Runpod /run payload")?;
-        let parsed = execute_curl_json("POST", &url, &self.api_key, Some(body)).await?;
+        let body = serde_json::to_value(payload).context("Failed to encode Runpod /run payload")?;
+        let parsed = self
+            .http
+            .send_json(Method::POST, &url, Some(&self.api_key), Some(&body), None)
+            .await
+            .context("Runpod /run request failed")?;
         serde_json::from_value::<RunpodRunResponse>(parsed)
             .context("Failed to decode Runpod /run response")
     }
@@ -78,17 +116,32 @@ impl RunpodClient {
             "{}/{}/status/{}",
             self.base_url, self.endpoint_id, runpod_job_id
         );
-        let parsed = execute_curl_json("GET", &url, &self.api_key, None).await?;
+        let parsed = self
+            .http
+            .send_json(Method::GET, &url, Some(&self.api_key), None, None)
+            .await
+            .context("Runpod /status request failed")?;
         serde_json::from_value::<RunpodStatusResponse>(parsed)
             .context("Failed to decode Runpod /status response")
     }
 
+    /// Endpoint worker and job counts; used as a reachability probe.
+    pub async fn health(&self, timeout: Duration) -> Result<Value> {
+        let url = format!("{}/{}/health", self.base_url, self.endpoint_id);
+        self.http
+            .send_json(Method::GET, &url, Some(&self.api_key), None, Some(timeout))
+            .await
+            .context("Runpod /health request failed")
+    }
+
     pub async fn cancel_job(&self, runpod_job_id: &str) -> Result<Value> {
         let url = format!(
             "{}/{}/cancel/{}",
             self.base_url, self.endpoint_id, runpod_job_id
         );
-        let payload = execute_curl_json("POST", &url, &self.api_key, None)
+        let payload = self
+            .http
+            .send_json(Method::POST, &url, Some(&self.api_key), None, None)
             .await
             .unwrap_or_else(|_| {
                 json!({
@@ -99,43 +152,3 @@ impl RunpodClient {
         Ok(payload)
     }
 }
-
-async fn execute_curl_json(
-    method: &str,
-    url: &str,
-    api_key: &str,
-    body: Option<String>,
-) -> Result<Value> {
-    let mut command = Command::new("curl");
-    command
-        .arg("-sS")
-        .arg("-X")
-        .arg(method)
-        .arg(url)
-        .arg("-H")
-        .arg(format!("Authorization: Bearer {}", api_key))
-        .arg("-H")
-        .arg("Content-Type: application/json");
-
-    if let Some(body) = body {
-        command.arg("-d").arg(body);
-    }
-
-    let output = command
-        .output()
-        .await
-        .with_context(|| format!("Failed to execute curl for {}", url))?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("Runpod curl request failed: {}", stderr.trim());
-    }
-
-    let stdout = String::from_utf8(output.stdout).context("Runpod response was not valid UTF-8")?;
-    serde_json::from_str::<Value>(&stdout).with_context(|| {
-        format!(
-            "Failed to parse Runpod JSON response for {}: {}",
-            url,
-            stdout.trim()
-        )
-    })
-}
@@ -1,17 +1,25 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use chrono::Utc;
 use serde_json::json;
-use tokio::process::Command;
+use tracing::{info_span, Instrument, Span};
 
 use crate::{
-    artifact_store::{build_job_file_name, ensure_job_dir},
+    artifact_store::{build_job_file_name, delete_dir_if_exists, ensure_job_dir},
     config::Config,
     embedding::{default_model_for_provider, EmbeddingRuntimeConfig},
-    mcp_index::build_and_persist_from_request,
+    http_client::{without_query, HttpClient},
+    mcp_index::{build_and_persist_from_request, sidecar_path_for_capsule},
     memvid_writer::write_mv2_core_only,
-    models::ExportRequest,
+    models::{ExportRequest, ExportStage},
+    runpod::WorkerProgress,
+    telemetry,
     transform::build_frame_documents,
 };
 
@@ -23,6 +31,108 @@ struct RunnerArgs {
     embedding_mode: String,
     embedding_provider: String,
     embedding_model: String,
+    base_capsule: Option<String>,
+    artifact_upload_url: Option<String>,
+    sidecar_upload_url: Option<String>,
+    progress_file: Option<PathBuf>,
+    traceparent: Option<String>,
+}
+
+/// Frame updates are written at most this often; stage changes always are.
+const PROGRESS_WRITE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Tracks the current stage and per-stage timings, and writes each update
+/// to `--progress-file` for the Runpod handler to forward.
+struct ProgressReporter {
+    path: Option<PathBuf>,
+    started: Instant,
+    stage_started: Instant,
+    last_write: Option<Instant>,
+    progress: WorkerProgress,
+}
+
+impl ProgressReporter {
+    fn new(path: Option<PathBuf>) -> Self {
+        let now = Instant::now();
+        Self {
+            path,
+            started: now,
+            stage_started: now,
+            last_write: None,
+            progress: WorkerProgress {
+                stage: ExportStage::Transform,
+                stage_progress: 0.0,
+                written_frames: None,
+                total_frames: None,
+                stage_timings_ms: BTreeMap::new(),
+                elapsed_ms: 0,
+            },
+        }
+    }
+
+    fn stage(&mut self, stage: ExportStage) {
+        self.close_stage();
+        self.progress.stage = stage;
+        self.progress.stage_progress = 0.0;
+        self.progress.written_frames = None;
+        self.progress.total_frames = None;
+        self.write();
+    }
+
+    fn frames(&mut self, written: usize, total: usize) {
+        self.progress.written_frames = Some(written);
+        self.progress.total_frames = Some(total);
+        self.progress.stage_progress =
+            (written as f64 / total.max(1) as f64 * 100.0).clamp(0.0, 100.0);
+        let due = self
+            .last_write
+            .is_none_or(|at| at.elapsed() >= PROGRESS_WRITE_INTERVAL);
+        if due || written >= total {
+            self.write();
+        }
+    }
+
+    /// Closes the current stage and returns the timings of all stages.
+    fn finish(&mut self) -> BTreeMap<String, u64> {
+        self.close_stage();
+        self.progress.stage_timings_ms.clone()
+    }
+
+    fn close_stage(&mut self) {
+        let elapsed = self.stage_started.elapsed().as_millis() as u64;
+        *self
+            .progress
+            .stage_timings_ms
+            .entry(self.progress.stage.as_str().to_string())
+            .or_default() += elapsed;
+        self.stage_started = Instant::now();
+    }
+
+    /// Replaces the file atomically so the handler never reads a torn write.
+    /// Progress is best effort: failures are logged, not returned.
+    fn write(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.progress.elapsed_ms = self.started.elapsed().as_millis() as u64;
+        self.last_write = Some(Instant::now());
+        let tmp_path = path.with_extension("json.tmp");
+        let result = serde_json::to_vec(&self.progress)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(std::fs::write(&tmp_path, bytes)?))
+            .and_then(|_| Ok(std::fs::rename(&tmp_path, path)?));
+        if let Err(err) = result {
+            tracing::warn!("Failed to write progress file {}: {err:#}", path.display());
+        }
+    }
+}
+
+/// Where the finished capsule goes: a directory (shared volume) or an
+/// HTTP(S) prefix the capsule and sidecar are uploaded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OutputTarget {
+    Local(PathBuf),
+    Http(String),
 }
 
 pub async fn maybe_run_from_cli(args: &[String]) -> Result<bool> {
@@ -31,12 +141,53 @@ pub async fn maybe_run_from_cli(args: &[String]) -> Result<bool> {
     }
 
     let parsed = parse_args(args).context("Invalid runpod-execute arguments")?;
-    let payload = load_payload(&parsed.payload_ref).await?;
+    // Continues the control plane's export trace across the Runpod hop.
+    let span = info_span!("runpod_execute", job_id = %parsed.job_id);
+    telemetry::set_parent(&span, parsed.traceparent.as_deref());
+    execute(parsed).instrument(span).await?;
+    Ok(true)
+}
+
+async fn execute(parsed: RunnerArgs) -> Result<()> {
+    // Remote output and base capsules are staged here and removed afterwards.
+    let scratch_dir = std::env::temp_dir()
+        .join("memvid-runpod-execute")
+        .join(&parsed.job_id);
+    let result = execute_in(&parsed, &scratch_dir).await;
+    if let Err(err) = delete_dir_if_exists(&scratch_dir).await {
+        tracing::warn!("Failed to remove {}: {err:#}", scratch_dir.display());
+    }
+    result
+}
+
+async fn execute_in(parsed: &RunnerArgs, scratch_dir: &Path) -> Result<()> {
+    let progress = Arc::new(Mutex::new(ProgressReporter::new(
+        parsed.progress_file.clone(),
+    )));
+    let report_stage = |stage| {
+        progress
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .stage(stage)
+    };
+    report_stage(ExportStage::Transform);
+
+    let env_config = Config::from_env().context("Failed to load embedding env config")?;
+    let http = HttpClient::from_config(&env_config)?;
+    let payload = load_payload(&http, &parsed.payload_ref).await?;
     let request: ExportRequest =
         serde_json::from_slice(&payload).context("Failed to decode staged payload JSON")?;
 
+    report_stage(ExportStage::FramePrep);
     let docs = build_frame_documents(&request);
-    let output_dir = resolve_output_prefix(&parsed.output_prefix)?;
+    let target = resolve_output_prefix(
+        &parsed.output_prefix,
+        parsed.artifact_upload_url.is_some() && parsed.sidecar_upload_url.is_some(),
+    )?;
+    let output_dir = match &target {
+        OutputTarget::Local(dir) => dir.clone(),
+        OutputTarget::Http(_) => scratch_dir.join("output"),
+    };
     tokio::fs::create_dir_all(&output_dir)
         .await
         .with_context(|| format!("Failed to create output directory {}", output_dir.display()))?;
@@ -45,37 +196,73 @@ pub async fn maybe_run_from_cli(args: &[String]) -> Result<bool> {
     let file_name = build_job_file_name(&request.source.base_name, &date_stamp);
     let output_path = output_dir.join(&file_name);
     ensure_job_dir(&output_path).await?;
-    let env_config = Config::from_env().context("Failed to load embedding env config")?;
     let embedding_config = if request.options.semantic_enabled {
-        Some(EmbeddingRuntimeConfig::new(
-            &parsed.embedding_mode,
-            &parsed.embedding_provider,
-            &parsed.embedding_model,
-            env_config.nvidia_api_key.clone(),
-            env_config.openai_api_key.clone(),
-            env_config.voyage_api_key.clone(),
-            env_config.ollama_host.clone(),
-            env_config.nvidia_embed_base_url.clone(),
-            env_config.openai_embed_base_url.clone(),
-            env_config.voyage_embed_base_url.clone(),
-            env_config.voyage_input_type.clone(),
-            env_config.voyage_output_dimension,
-            env_config.voyage_output_dtype.clone(),
-            env_config.voyage_truncation,
-            env_config.embed_request_timeout_seconds,
-        )?)
+        Some(
+            EmbeddingRuntimeConfig::new(
+                &parsed.embedding_mode,
+                &parsed.embedding_provider,
+                &parsed.embedding_model,
+                env_config.nvidia_api_key.clone(),
+                env_config.openai_api_key.clone(),
+                env_config.voyage_api_key.clone(),
+                env_config.ollama_host.clone(),
+                env_config.nvidia_embed_base_url.clone(),
+                env_config.openai_embed_base_url.clone(),
+                env_config.voyage_embed_base_url.clone(),
+                env_config.voyage_input_type.clone(),
+                env_config.voyage_output_dimension,
+                env_config.voyage_output_dtype.clone(),
+                env_config.voyage_truncation,
+                env_config.embed_request_timeout_seconds,
+                env_config.embed_batch_size,
+                env_config.embed_max_retries,
+                env_config.embed_max_concurrency,
+                http.clone(),
+            )?
+            .with_cache(env_config.open_embedding_cache().unwrap_or_else(|err| {
+                tracing::warn!("Embedding cache unavailable: {err:#}");
+                None
+            })),
+        )
     } else {
         None
     };
 
-    write_mv2_core_only(
-        &output_path,
-        &docs,
-        request.options.semantic_enabled,
-        embedding_config,
-        |_written, _total| {},
-    )?;
+    let base_capsule = match parsed.base_capsule.as_deref() {
+        Some(base_ref) => Some(load_base_capsule(&http, base_ref, scratch_dir).await?),
+        None => None,
+    };
+    report_stage(ExportStage::WriteCapsule);
+    // The writer blocks (and drives embedding requests) on its own thread.
+    let ((diff, verified), docs) = {
+        let output_path = output_path.clone();
+        let semantic_enabled = request.options.semantic_enabled;
+        let span = Span::current();
+        let progress = Arc::clone(&progress);
+        tokio::task::spawn_blocking(move || {
+            span.in_scope(|| {
+                write_mv2_core_only(
+                    &output_path,
+                    &docs,
+                    semantic_enabled,
+                    embedding_config,
+                    base_capsule.as_deref(),
+                    |written, total| {
+                        progress
+                            .lock()
+                            .unwrap_or_else(|err| err.into_inner())
+                            .frames(written, total);
+                        Ok(())
+                    },
+                )
+            })
+            .map(|written| (written, docs))
+        })
+        .await
+        .context("Capsule writer task join error")??
+    };
 
+    report_stage(ExportStage::BuildSidecar);
     let sidecar_status = match build_and_persist_from_request(&request, &docs, &output_path) {
         Ok(index) => json!({
             "status": "ready",
@@ -89,24 +276,46 @@ pub async fn maybe_run_from_cli(args: &[String]) -> Result<bool> {
         }),
     };
 
+    report_stage(ExportStage::Finalize);
     let artifact_meta = tokio::fs::metadata(&output_path)
         .await
         .with_context(|| format!("Failed to stat {}", output_path.display()))?;
 
-    let result = json!({
+    let mut result = json!({
         "backend": "rust-memvid-core",
         "jobId": parsed.job_id,
         "fileName": file_name,
-        "artifactPath": output_path,
-        "artifactRef": format!("file://{}", output_path.display()),
         "sizeBytes": artifact_meta.len(),
+        "frameCount": verified.frame_count,
+        "sha256": verified.sha256,
         "embeddingMode": parsed.embedding_mode,
         "embeddingProvider": parsed.embedding_provider,
         "embeddingModel": parsed.embedding_model,
-        "sidecar": sidecar_status
+        "sidecar": sidecar_status,
+        "diff": diff,
+        "embeddingCache": diff.embedding_cache,
+        "traceId": telemetry::trace_id(&Span::current())
     });
+    match &target {
+        OutputTarget::Local(_) => {
+            result["artifactPath"] = json!(output_path);
+            result["artifactRef"] = json!(format!("file://{}", output_path.display()));
+        }
+        OutputTarget::Http(prefix) => {
+            let (artifact_ref, sidecar_ref) =
+                upload_outputs(&http, parsed, prefix, &output_path, &file_name).await?;
+            result["artifactRef"] = json!(artifact_ref);
+            if let Some(sidecar_ref) = sidecar_ref {
+                result["sidecarRef"] = json!(sidecar_ref);
+            }
+        }
+    }
+    result["stageTimingsMs"] = json!(progress
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .finish());
     println!("{}", serde_json::to_string(&result)?);
-    Ok(true)
+    Ok(())
 }
 
 fn parse_args(args: &[String]) -> Result<RunnerArgs> {
@@ -116,6 +325,11 @@ fn parse_args(args: &[String]) -> Result<RunnerArgs> {
     let mut embedding_mode = None;
     let mut embedding_provider = None;
     let mut embedding_model = None;
+    let mut base_capsule = None;
+    let mut artifact_upload_url = None;
+    let mut sidecar_upload_url = None;
+    let mut progress_file = None;
+    let mut traceparent = std::env::var("TRACEPARENT").ok();
 
     let mut i = 2usize;
     while i < args.len() {
@@ -146,6 +360,26 @@ fn parse_args(args: &[String]) -> Result<RunnerArgs> {
                 embedding_model = Some(v);
                 i += 2;
             }
+            ("--base-capsule", Some(v)) => {
+                base_capsule = Some(v);
+                i += 2;
+            }
+            ("--artifact-upload-url", Some(v)) => {
+                artifact_upload_url = Some(v);
+                i += 2;
+            }
+            ("--sidecar-upload-url", Some(v)) => {
+                sidecar_upload_url = Some(v);
+                i += 2;
+            }
+            ("--progress-file", Some(v)) => {
+                progress_file = Some(PathBuf::from(v));
+                i += 2;
+            }
+            ("--traceparent", Some(v)) => {
+                traceparent = Some(v);
+                i += 2;
+            }
             _ => {
                 anyhow::bail!("Unknown or incomplete argument near `{}`", key);
             }
@@ -168,6 +402,11 @@ fn parse_args(args: &[String]) -> Result<RunnerArgs> {
         embedding_mode,
         embedding_provider,
         embedding_model,
+        base_capsule,
+        artifact_upload_url,
+        sidecar_upload_url,
+        progress_file,
+        traceparent,
     })
 }
 
@@ -179,19 +418,14 @@ fn resolve_file_ref_path(file_ref: &str) -> PathBuf {
     }
 }
 
-async fn load_payload(payload_ref: &str) -> Result<Vec<u8>> {
-    if payload_ref.starts_with("http://") || payload_ref.starts_with("https://") {
-        let output = Command::new("curl")
-            .arg("-sS")
-            .arg(payload_ref)
-            .output()
-            .await
-            .with_context(|| format!("Failed to download payload from {payload_ref}"))?;
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Payload download failed: {}", stderr.trim());
-        }
-        return Ok(output.stdout);
+async fn load_payload(http: &HttpClient, payload_ref: &str) -> Result<Vec<u8>> {
+    if is_http_url(payload_ref) {
+        return http.get_bytes(payload_ref).await.with_context(|| {
+            format!(
+                "Failed to download payload from {}",
+                without_query(payload_ref)
+            )
+        });
     }
 
     let payload_path = resolve_file_ref_path(payload_ref);
@@ -200,17 +434,265 @@ async fn load_payload(payload_ref: &str) -> Result<Vec<u8>> {
         .with_context(|| format!("Failed reading staged payload {}", payload_path.display()))
 }
 
-fn resolve_output_prefix(prefix: &str) -> Result<PathBuf> {
-    let path = if prefix.starts_with("http://") || prefix.starts_with("https://") {
-        anyhow::bail!("HTTP output prefixes are not supported in runpod-execute mode");
-    } else if prefix.starts_with("file://") {
-        resolve_file_ref_path(prefix)
+/// Downloads an HTTP(S) base capsule into the scratch directory; other refs
+/// are read in place.
+async fn load_base_capsule(
+    http: &HttpClient,
+    base_ref: &str,
+    scratch_dir: &Path,
+) -> Result<PathBuf> {
+    if !is_http_url(base_ref) {
+        return Ok(resolve_file_ref_path(base_ref));
+    }
+    let path = scratch_dir.join("base.mv2");
+    if !http.download_file(base_ref, &path).await? {
+        anyhow::bail!("Base capsule not found at {}", without_query(base_ref));
+    }
+    Ok(path)
+}
+
+/// Uploads the capsule, and the sidecar when one was built, to their
+/// presigned URLs or under `prefix`. Returns the refs without signatures.
+async fn upload_outputs(
+    http: &HttpClient,
+    parsed: &RunnerArgs,
+    prefix: &str,
+    output_path: &Path,
+    file_name: &str,
+) -> Result<(String, Option<String>)> {
+    let artifact_url = match &parsed.artifact_upload_url {
+        Some(url) => url.clone(),
+        None => object_url(prefix, file_name)?,
+    };
+    // Both URLs are resolved before anything is uploaded, so a missing
+    // presigned URL does not leave a capsule without its sidecar.
+    let sidecar_path = sidecar_path_for_capsule(output_path);
+    let sidecar_url = if tokio::fs::try_exists(&sidecar_path).await.unwrap_or(false) {
+        Some(match &parsed.sidecar_upload_url {
+            Some(url) => url.clone(),
+            None => {
+                let sidecar_name = sidecar_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .context("Sidecar path has no file name")?;
+                object_url(prefix, &sidecar_name)?
+            }
+        })
     } else {
-        PathBuf::from(prefix)
+        None
     };
 
+    http.put_file(&artifact_url, output_path).await?;
+    if let Some(sidecar_url) = &sidecar_url {
+        http.put_file(sidecar_url, &sidecar_path).await?;
+    }
+    Ok((
+        without_query(&artifact_url).to_string(),
+        sidecar_url.map(|url| without_query(&url).to_string()),
+    ))
+}
+
+/// `name` under `prefix`. A prefix with a query string is presigned for one
+/// object, so appending to it would produce a URL with a broken signature.
+fn object_url(prefix: &str, name: &str) -> Result<String> {
+    if prefix.contains('?') {
+        anyhow::bail!(
+            "Cannot upload {name} under an output prefix with a query string; pass a presigned URL for it"
+        );
+    }
+    Ok(format!("{}/{name}", prefix.trim_end_matches('/')))
+}
+
+fn is_http_url(value: &str) -> bool {
+    value.starts_with("http://") || value.starts_with("https://")
+}
+
+fn resolve_output_prefix(prefix: &str, has_upload_urls: bool) -> Result<OutputTarget> {
+    if is_http_url(prefix) {
+        // Object names are appended to the prefix, which would break a
+        // signature carried in its query string.
+        if prefix.contains('?') && !has_upload_urls {
+            anyhow::bail!(
+                "HTTP output prefixes cannot carry a query string; pass presigned URLs with --artifact-upload-url and --sidecar-upload-url"
+            );
+        }
+        return Ok(OutputTarget::Http(prefix.to_string()));
+    }
+
+    let path = resolve_file_ref_path(prefix);
     if path == Path::new("/") {
         anyhow::bail!("Refusing to write output to filesystem root");
     }
-    Ok(path)
+    Ok(OutputTarget::Local(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{body::Bytes, extract::State, http::Uri, routing::put, Router};
+
+    use super::*;
+    use crate::test_support::TempDir;
+
+    type Uploads = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    async fn spawn_upload_sink() -> (String, Uploads) {
+        let uploads = Uploads::default();
+        let app = Router::new()
+            .route(
+                "/{*key}",
+                put(
+                    |State(uploads): State<Uploads>, uri: Uri, body: Bytes| async move {
+                        uploads
+                            .lock()
+                            .unwrap()
+                            .insert(uri.to_string(), body.to_vec());
+                    },
+                ),
+            )
+            .with_state(uploads.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), uploads)
+    }
+
+    fn write_outputs(dir: &TempDir) -> PathBuf {
+        let capsule = dir.path().join("demo.mv2");
+        std::fs::write(&capsule, b"capsule").unwrap();
+        std::fs::write(sidecar_path_for_capsule(&capsule), b"sidecar").unwrap();
+        capsule
+    }
+
+    fn args(output_prefix: &str) -> RunnerArgs {
+        parse_args(&[
+            "memvid-export-api".to_string(),
+            "runpod-execute".to_string(),
+            "--job-id".to_string(),
+            "job-1".to_string(),
+            "--payload-ref".to_string(),
+            "file:///tmp/payload.json".to_string(),
+            "--output-prefix".to_string(),
+            output_prefix.to_string(),
+        ])
+        .unwrap()
+    }
+
+    fn http() -> HttpClient {
+        HttpClient::new(Duration::from_secs(5), Duration::from_secs(5), None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn outputs_upload_under_an_http_prefix() {
+        let (base, uploads) = spawn_upload_sink().await;
+        let dir = TempDir::new("runpod-execute-prefix");
+        let capsule = write_outputs(&dir);
+        let args = args(&format!("{base}/outputs/job-1/"));
+        assert_eq!(
+            resolve_output_prefix(&args.output_prefix, false).unwrap(),
+            OutputTarget::Http(args.output_prefix.clone())
+        );
+
+        let (artifact_ref, sidecar_ref) =
+            upload_outputs(&http(), &args, &args.output_prefix, &capsule, "demo.mv2")
+                .await
+                .unwrap();
+        assert_eq!(artifact_ref, format!("{base}/outputs/job-1/demo.mv2"));
+        assert_eq!(
+            sidecar_ref.as_deref(),
+            Some(format!("{base}/outputs/job-1/demo.mv2.index.v1.sqlite").as_str())
+        );
+        let uploads = uploads.lock().unwrap();
+        assert_eq!(uploads["/outputs/job-1/demo.mv2"], b"capsule");
+        assert_eq!(
+            uploads["/outputs/job-1/demo.mv2.index.v1.sqlite"],
+            b"sidecar"
+        );
+    }
+
+    #[tokio::test]
+    async fn outputs_upload_to_presigned_urls_when_given() {
+        let (base, uploads) = spawn_upload_sink().await;
+        let dir = TempDir::new("runpod-execute-presigned");
+        let capsule = write_outputs(&dir);
+        let mut args = args(&format!("{base}/outputs/job-1/"));
+        args.artifact_upload_url = Some(format!("{base}/bucket/job-1.mv2?X-Amz-Signature=abc"));
+        args.sidecar_upload_url = Some(format!(
+            "{base}/bucket/job-1.mv2.index.v1.sqlite?X-Amz-Signature=def"
+        ));
+
+        let (artifact_ref, sidecar_ref) =
+            upload_outputs(&http(), &args, "unused", &capsule, "demo.mv2")
+                .await
+                .unwrap();
+        // Refs drop the signature so they can be logged and stored.
+        assert_eq!(artifact_ref, format!("{base}/bucket/job-1.mv2"));
+        assert_eq!(
+            sidecar_ref.as_deref(),
+            Some(format!("{base}/bucket/job-1.mv2.index.v1.sqlite").as_str())
+        );
+        let uploads = uploads.lock().unwrap();
+        assert_eq!(uploads["/bucket/job-1.mv2?X-Amz-Signature=abc"], b"capsule");
+        assert_eq!(
+            uploads["/bucket/job-1.mv2.index.v1.sqlite?X-Amz-Signature=def"],
+            b"sidecar"
+        );
+        assert!(!uploads.contains_key("/outputs/job-1/demo.mv2"));
+    }
+
+    #[test]
+    fn output_prefixes_with_a_query_need_presigned_urls() {
+        assert!(resolve_output_prefix("https://host/out?sig=1", false).is_err());
+        assert!(resolve_output_prefix("https://host/out?sig=1", true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn sidecars_are_not_appended_to_a_presigned_prefix() {
+        let (base, uploads) = spawn_upload_sink().await;
+        let dir = TempDir::new("runpod-execute-query-prefix");
+        let capsule = write_outputs(&dir);
+        let prefix = format!("{base}/outputs/job-1?X-Amz-Signature=abc");
+        let mut args = args(&prefix);
+        args.artifact_upload_url = Some(format!("{base}/bucket/job-1.mv2?X-Amz-Signature=def"));
+
+        let err = upload_outputs(&http(), &args, &prefix, &capsule, "demo.mv2")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("demo.mv2.index.v1.sqlite"));
+        assert!(uploads.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn progress_file_tracks_stages_frames_and_timings() {
+        let dir = TempDir::new("runpod-progress");
+        let path = dir.path().join("progress.json");
+        let read = || -> WorkerProgress {
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap()
+        };
+
+        let mut reporter = ProgressReporter::new(Some(path.clone()));
+        reporter.stage(ExportStage::Transform);
+        reporter.stage(ExportStage::WriteCapsule);
+        let progress = read();
+        assert_eq!(progress.stage, ExportStage::WriteCapsule);
+        assert!(progress.stage_timings_ms.contains_key("transform"));
+
+        // Frame updates right after the stage change are throttled, but the
+        // last frame is always written.
+        reporter.frames(1, 4);
+        assert_eq!(read().written_frames, None);
+        reporter.frames(4, 4);
+        let progress = read();
+        assert_eq!(progress.written_frames, Some(4));
+        assert_eq!(progress.stage_progress, 100.0);
+
+        let timings = reporter.finish();
+        assert!(timings.contains_key("write_capsule"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
 }
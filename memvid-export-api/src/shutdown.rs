@@ -0,0 +1,282 @@
+use std::{future::Future, time::Duration};
+
+use serde_json::json;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+use crate::{
+    artifact_store::delete_dir_if_exists,
+    config::ExportBackendMode,
+    models::{ExportEventType, JobState},
+    queue::append_job_event,
+    AppState,
+};
+
+/// Shutdown state shared by the HTTP handlers and the export workers.
+///
+/// `draining` is cancelled as soon as a stop signal arrives: new exports are
+/// refused and workers stop taking queued jobs. `stopping` is cancelled once
+/// running jobs have finished or been checkpointed, which closes SSE streams
+/// so the HTTP server can stop.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    draining: CancellationToken,
+    stopping: CancellationToken,
+    jobs: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn is_draining(&self) -> bool {
+        self.draining.is_cancelled()
+    }
+
+    pub async fn draining(&self) {
+        self.draining.cancelled().await
+    }
+
+    pub async fn stopping(&self) {
+        self.stopping.cancelled().await
+    }
+
+    /// Runs a job task so the drain can wait for it.
+    pub fn spawn_job<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.jobs.spawn(task);
+    }
+}
+
+pub async fn stop_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            warn!(error = %err, "Failed to listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                warn!(error = %err, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Waits for a stop signal, then drains. Returns the number of checkpointed
+/// jobs.
+pub async fn drain_on_signal(state: AppState, grace: Duration) -> usize {
+    stop_signal().await;
+    drain(&state, grace).await
+}
+
+/// Running jobs get `grace` to finish. The rest are cancelled and, once their
+/// tasks (and capsule writers) have stopped, checkpointed so
+/// `restore_persisted_jobs` resumes them on the next start.
+pub async fn drain(state: &AppState, grace: Duration) -> usize {
+    let shutdown = &state.shutdown;
+    shutdown.draining.cancel();
+    shutdown.jobs.close();
+
+    let running = shutdown.jobs.len();
+    info!(
+        running,
+        grace_seconds = grace.as_secs(),
+        "Stop signal received; refusing new exports and draining running jobs"
+    );
+
+    let checkpointed = if tokio::time::timeout(grace, shutdown.jobs.wait())
+        .await
+        .is_ok()
+    {
+        info!("All running export jobs finished");
+        0
+    } else {
+        info!("Grace period over; interrupting running export jobs");
+        state.job_cancellations.cancel_all();
+        shutdown.jobs.wait().await;
+        checkpoint_running_jobs(state).await
+    };
+
+    shutdown.stopping.cancel();
+    checkpointed
+}
+
+/// Leaves unfinished jobs `Running` in the job store, which is what restore
+/// treats as resumable, and removes partially written local output. Only
+/// called once no job task is left to write to it.
+async fn checkpoint_running_jobs(state: &AppState) -> usize {
+    let running: Vec<String> = state
+        .jobs
+        .read()
+        .await
+        .values()
+        .filter(|job| matches!(job.status, JobState::Running))
+        .map(|job| job.job_id.clone())
+        .collect();
+
+    for job_id in &running {
+        warn!(job_id = %job_id, "Export job did not finish before shutdown; checkpointing for resume");
+        let Some((stage, progress)) = state
+            .jobs
+            .read()
+            .await
+            .get(job_id)
+            .map(|job| (job.current_stage.clone(), job.progress))
+        else {
+            continue;
+        };
+        // Persists the record with the job still `Running`.
+        let _ = append_job_event(
+            state,
+            job_id,
+            ExportEventType::StageProgress,
+            stage,
+            progress,
+            None,
+            "Interrupted by server shutdown; resumes on restart",
+            Some(json!({ "interrupted": true })),
+        )
+        .await;
+
+        // Runpod jobs keep running remotely and are re-attached; local writes
+        // restart from scratch, so their partial capsule is discarded.
+        if matches!(state.config.backend_mode, ExportBackendMode::LegacyVps) {
+            if let Err(err) = delete_dir_if_exists(&state.config.export_root.join(job_id)).await {
+                warn!(job_id = %job_id, "Failed to remove partial export output: {err:#}");
+            }
+        }
+    }
+    running.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use axum::{routing::post, Json, Router};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        config::EmbeddingMode,
+        job_store::JobSnapshot,
+        models::ExportStage,
+        queue::{restore_persisted_jobs, spawn_export_worker},
+        test_support::{job_record, sample_request, test_config, test_state, TempDir},
+    };
+
+    /// `/embeddings` double that takes 100ms per request, so a semantic
+    /// capsule write outlives a short grace period.
+    async fn spawn_slow_embeddings() -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        let app = Router::new().route(
+            "/embeddings",
+            post(move |Json(body): Json<Value>| async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                let data: Vec<Value> = body["input"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .enumerate()
+                    .map(|(index, _)| json!({ "index": index, "embedding": [1.0, 0.0] }))
+                    .collect();
+                Json(json!({ "data": data }))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}"), requests)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn jobs_outliving_the_grace_period_are_stopped_and_resumable() {
+        let dir = TempDir::new("shutdown-drain");
+        let (base_url, requests) = spawn_slow_embeddings().await;
+        let mut config = test_config(dir.path());
+        config.embedding_mode = EmbeddingMode::ExternalApi;
+        config.embedding_provider = "openai".to_string();
+        config.embedding_model = "text-embedding-3-small".to_string();
+        config.openai_api_key = Some("sk-test".to_string());
+        config.openai_embed_base_url = base_url;
+        config.embed_batch_size = 1;
+        config.embed_max_concurrency = 1;
+        config.embed_max_retries = 0;
+        let (state, queue_rx) = test_state(config.clone());
+        spawn_export_worker(state.clone(), queue_rx);
+
+        let mut request = sample_request();
+        request.options.semantic_enabled = true;
+        let mut job = job_record("job-1", JobState::Queued);
+        state
+            .job_store
+            .insert_job(JobSnapshot::from_record(&job).unwrap(), &request)
+            .await
+            .unwrap();
+        job.request = Some(request);
+        state.jobs.write().await.insert("job-1".to_string(), job);
+        state.queue_tx.send("job-1".to_string()).await.unwrap();
+
+        for _ in 0..100 {
+            if requests.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(
+            requests.load(Ordering::SeqCst) > 0,
+            "capsule write never started"
+        );
+
+        let checkpointed = drain(&state, Duration::from_millis(10)).await;
+        assert_eq!(checkpointed, 1);
+        // The writer stopped after the frame in flight instead of embedding
+        // the rest of the capsule, and its thread is gone.
+        let seen = requests.load(Ordering::SeqCst);
+        assert!(seen < 5, "writer kept embedding ({seen} requests)");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(requests.load(Ordering::SeqCst), seen);
+
+        {
+            let jobs = state.jobs.read().await;
+            assert!(matches!(jobs["job-1"].status, JobState::Running));
+            assert!(jobs["job-1"].error.is_none());
+            assert_eq!(
+                jobs["job-1"].events.back().unwrap().message,
+                "Interrupted by server shutdown; resumes on restart"
+            );
+        }
+        assert!(!config.export_root.join("job-1").exists());
+
+        let (mut restarted, _queue_rx) = test_state(config);
+        restarted.job_store = state.job_store.clone();
+        let requeued = restore_persisted_jobs(&restarted).await.unwrap();
+        assert_eq!(requeued, vec!["job-1".to_string()]);
+        let jobs = restarted.jobs.read().await;
+        assert!(matches!(jobs["job-1"].status, JobState::Queued));
+        assert_eq!(jobs["job-1"].current_stage, ExportStage::Queued);
+        assert!(jobs["job-1"]
+            .request
+            .as_ref()
+            .is_some_and(|request| request.options.semantic_enabled));
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    fmt::Write as _,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadTarget {
+    #[default]
+    Capsule,
+    Sidecar,
+    Bundle,
+}
+
+impl DownloadTarget {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Capsule => "capsule",
+            Self::Sidecar => "sidecar",
+            Self::Bundle => "bundle",
+        }
+    }
+
+    pub fn path(&self, job_id: &str) -> String {
+        match self {
+            Self::Capsule => format!("/v1/exports/{job_id}/download"),
+            Self::Sidecar => format!("/v1/exports/{job_id}/download/sidecar"),
+            Self::Bundle => format!("/v1/exports/{job_id}/download/bundle"),
+        }
+    }
+}
+
+/// Query parameters of a pre-signed download URL. An unsigned request has
+/// none of them and falls back to bearer authentication.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SignedUrlQuery {
+    #[serde(default)]
+    pub expires: Option<i64>,
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    #[serde(default)]
+    pub kid: Option<String>,
+    #[serde(default)]
+    pub sig: Option<String>,
+}
+
+impl SignedUrlQuery {
+    pub fn is_signed(&self) -> bool {
+        self.sig.is_some()
+    }
+}
+
+/// Everything a signature covers. The download path's job id and target are
+/// part of the message, so a URL cannot be replayed against another job.
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadGrant<'a> {
+    pub job_id: &'a str,
+    pub target: DownloadTarget,
+    pub expires_at: i64,
+    pub ip: Option<IpAddr>,
+    pub key_id: Option<&'a str>,
+}
+
+impl DownloadGrant<'_> {
+    fn mac(&self, secret: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+        let ip = self.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        for part in [
+            self.job_id,
+            self.target.as_str(),
+            &self.expires_at.to_string(),
+            &ip,
+            self.key_id.unwrap_or_default(),
+        ] {
+            mac.update(part.as_bytes());
+            mac.update(&[0u8]);
+        }
+        mac
+    }
+
+    pub fn signed_url(&self, secret: &str) -> String {
+        let sig = hex::encode(self.mac(secret).finalize().into_bytes());
+        let mut url = format!(
+            "{}?expires={}",
+            self.target.path(self.job_id),
+            self.expires_at
+        );
+        if let Some(ip) = self.ip {
+            let _ = write!(url, "&ip={}", encode_query_value(&ip.to_string()));
+        }
+        if let Some(key_id) = self.key_id {
+            let _ = write!(url, "&kid={}", encode_query_value(key_id));
+        }
+        let _ = write!(url, "&sig={sig}");
+        url
+    }
+}
+
+/// Checks the signature, expiry and IP binding of a signed download request.
+/// Key binding is checked by the caller, which has the key registry.
+pub fn verify_signed_download(
+    secret: &str,
+    job_id: &str,
+    target: DownloadTarget,
+    query: &SignedUrlQuery,
+    client_ip: IpAddr,
+    now: DateTime<Utc>,
+) -> Result<(), &'static str> {
+    let (Some(expires_at), Some(sig)) = (query.expires, query.sig.as_deref()) else {
+        return Err("Signed URL requires both expires and sig");
+    };
+    let Ok(sig) = hex::decode(sig) else {
+        return Err("Invalid download signature");
+    };
+    let grant = DownloadGrant {
+        job_id,
+        target,
+        expires_at,
+        ip: query.ip,
+        key_id: query.kid.as_deref(),
+    };
+    if grant.mac(secret).verify_slice(&sig).is_err() {
+        return Err("Invalid download signature");
+    }
+    if expires_at <= now.timestamp() {
+        return Err("Signed URL has expired");
+    }
+    if query.ip.is_some_and(|ip| ip != client_ip) {
+        return Err("Signed URL is bound to a different client IP");
+    }
+    Ok(())
+}
+
+/// The peer address, or the last `X-Forwarded-For` hop when the server runs
+/// behind a trusted reverse proxy. Proxies append to the header, so only the
+/// last hop was written by the proxy; earlier ones come from the client.
+pub fn client_ip(headers: &HeaderMap, peer: SocketAddr, trust_forwarded_for: bool) -> IpAddr {
+    if trust_forwarded_for {
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .next_back()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok());
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+    peer.ip()
+}
+
+fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn query_from(url: &str) -> SignedUrlQuery {
+        let raw = url.split_once('?').unwrap().1;
+        let mut query = SignedUrlQuery::default();
+        for pair in raw.split('&') {
+            let (name, value) = pair.split_once('=').unwrap();
+            let value = value.replace("%3A", ":");
+            match name {
+                "expires" => query.expires = value.parse().ok(),
+                "ip" => query.ip = value.parse().ok(),
+                "kid" => query.kid = Some(value),
+                "sig" => query.sig = Some(value),
+                _ => {}
+            }
+        }
+        query
+    }
+
+    fn ip() -> IpAddr {
+        "2001:db8::7".parse().unwrap()
+    }
+
+    fn grant(now: DateTime<Utc>) -> DownloadGrant<'static> {
+        DownloadGrant {
+            job_id: "job-1",
+            target: DownloadTarget::Capsule,
+            expires_at: (now + Duration::minutes(5)).timestamp(),
+            ip: Some(ip()),
+            key_id: Some("ci"),
+        }
+    }
+
+    fn verify(
+        query: &SignedUrlQuery,
+        job_id: &str,
+        target: DownloadTarget,
+        client_ip: IpAddr,
+        now: DateTime<Utc>,
+    ) -> Result<(), &'static str> {
+        verify_signed_download("secret", job_id, target, query, client_ip, now)
+    }
+
+    #[test]
+    fn signed_url_round_trips_through_its_query() {
+        let now = Utc::now();
+        let url = grant(now).signed_url("secret");
+        assert!(url.starts_with("/v1/exports/job-1/download?expires="));
+        assert!(url.contains("&ip=2001%3Adb8%3A%3A7&kid=ci&sig="));
+        let query = query_from(&url);
+        assert!(query.is_signed());
+        assert_eq!(
+            verify(&query, "job-1", DownloadTarget::Capsule, ip(), now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn signature_covers_secret_job_target_and_key() {
+        let now = Utc::now();
+        let query = query_from(&grant(now).signed_url("secret"));
+        assert!(verify_signed_download(
+            "other",
+            "job-1",
+            DownloadTarget::Capsule,
+            &query,
+            ip(),
+            now
+        )
+        .is_err());
+        assert!(verify(&query, "job-2", DownloadTarget::Capsule, ip(), now).is_err());
+        assert!(verify(&query, "job-1", DownloadTarget::Bundle, ip(), now).is_err());
+
+        let mut tampered = query.clone();
+        tampered.kid = Some("admin".to_string());
+        assert_eq!(
+            verify(&tampered, "job-1", DownloadTarget::Capsule, ip(), now),
+            Err("Invalid download signature")
+        );
+        let mut unbound = query;
+        unbound.ip = None;
+        assert!(verify(&unbound, "job-1", DownloadTarget::Capsule, ip(), now).is_err());
+    }
+
+    #[test]
+    fn expired_or_rebound_urls_are_rejected() {
+        let now = Utc::now();
+        let query = query_from(&grant(now).signed_url("secret"));
+        assert_eq!(
+            verify(
+                &query,
+                "job-1",
+                DownloadTarget::Capsule,
+                "10.0.0.1".parse().unwrap(),
+                now
+            ),
+            Err("Signed URL is bound to a different client IP")
+        );
+        assert_eq!(
+            verify(
+                &query,
+                "job-1",
+                DownloadTarget::Capsule,
+                ip(),
+                now + Duration::minutes(10)
+            ),
+            Err("Signed URL has expired")
+        );
+    }
+
+    #[test]
+    fn incomplete_or_garbled_signatures_are_rejected() {
+        let now = Utc::now();
+        let mut query = query_from(&grant(now).signed_url("secret"));
+        query.sig = Some("not-hex".to_string());
+        assert_eq!(
+            verify(&query, "job-1", DownloadTarget::Capsule, ip(), now),
+            Err("Invalid download signature")
+        );
+        query.expires = None;
+        assert_eq!(
+            verify(&query, "job-1", DownloadTarget::Capsule, ip(), now),
+            Err("Signed URL requires both expires and sig")
+        );
+        assert!(!SignedUrlQuery::default().is_signed());
+    }
+
+    #[test]
+    fn forwarded_for_is_only_trusted_when_configured() {
+        let peer: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "203.0.113.9".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, false), peer.ip());
+        assert_eq!(
+            client_ip(&headers, peer, true),
+            "203.0.113.9".parse::<IpAddr>().unwrap()
+        );
+        headers.insert("x-forwarded-for", "garbage".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, true), peer.ip());
+    }
+
+    #[test]
+    fn spoofed_leading_forwarded_for_hops_are_ignored() {
+        let peer: SocketAddr = "192.0.2.1:4000".parse().unwrap();
+        let proxy_seen: IpAddr = "198.51.100.7".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        // The client sent `203.0.113.9`; the proxy appended the real address.
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.9, 198.51.100.7".parse().unwrap(),
+        );
+        assert_eq!(client_ip(&headers, peer, true), proxy_seen);
+
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", "203.0.113.9".parse().unwrap());
+        headers.append("x-forwarded-for", "198.51.100.7".parse().unwrap());
+        assert_eq!(client_ip(&headers, peer, true), proxy_seen);
+
+        let now = Utc::now();
+        let bound = DownloadGrant {
+            ip: Some("203.0.113.9".parse().unwrap()),
+            ..grant(now)
+        };
+        let query = query_from(&bound.signed_url("secret"));
+        assert_eq!(
+            verify(
+                &query,
+                "job-1",
+                DownloadTarget::Capsule,
+                client_ip(&headers, peer, true),
+                now
+            ),
+            Err("Signed URL is bound to a different client IP")
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::{collections::HashMap, env};
+
+use anyhow::{Context, Result};
+use opentelemetry::{
+    global,
+    propagation::TextMapPropagator,
+    trace::{TraceContextExt, TracerProvider as _},
+};
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+const TRACEPARENT: &str = "traceparent";
+
+/// Owns the tracer provider; call [`Telemetry::shutdown`] before exit so
+/// batched spans are flushed (short-lived `runpod-execute` runs depend on it).
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+    exporting: bool,
+}
+
+impl Telemetry {
+    pub fn exporting(&self) -> bool {
+        self.exporting
+    }
+
+    pub fn shutdown(self) {
+        if let Err(err) = self.provider.shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {err}");
+        }
+    }
+}
+
+/// Installs the log formatter and an OpenTelemetry layer. Spans always get
+/// W3C trace ids so they can be propagated; they are exported over OTLP/HTTP
+/// only when `OTEL_EXPORTER_OTLP_ENDPOINT` or
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set. `OTEL_SERVICE_NAME` overrides
+/// `default_service_name`.
+pub fn init(default_service_name: &str) -> Result<Telemetry> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let exporting = [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|name| env::var(name).is_ok_and(|v| !v.trim().is_empty()));
+
+    let mut resource = Resource::builder();
+    if env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(default_service_name.to_string());
+    }
+    let mut builder = SdkTracerProvider::builder().with_resource(resource.build());
+    if exporting {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .build()
+            .context("Failed to build OTLP span exporter")?;
+        builder = builder.with_batch_exporter(exporter);
+    }
+    let provider = builder.build();
+    let tracer = provider.tracer("memvid-export-api");
+
+    tracing_subscriber::registry()
+        .with(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "memvid_export_api=info,tower_http=info".into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .init();
+
+    Ok(Telemetry {
+        provider,
+        exporting,
+    })
+}
+
+/// The `traceparent` header value for `span`, or `None` when the span is
+/// disabled by the log filter.
+pub fn traceparent(span: &Span) -> Option<String> {
+    let cx = span.context();
+    if !cx.span().span_context().is_valid() {
+        return None;
+    }
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&cx, &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Makes `span` a child of the remote span in `traceparent`. Missing or
+/// malformed values leave the span as a new root.
+pub fn set_parent(span: &Span, traceparent: Option<&str>) {
+    let Some(traceparent) = traceparent else {
+        return;
+    };
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    let cx = TraceContextPropagator::new().extract(&carrier);
+    if cx.span().span_context().is_valid() {
+        let _ = span.set_parent(cx);
+    }
+}
+
+/// Hex trace id of `span`, as reported to clients (MCP `traceId`).
+pub fn trace_id(span: &Span) -> Option<String> {
+    let span_context = span.context().span().span_context().clone();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REMOTE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    const REMOTE_TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    /// Runs `f` with an OpenTelemetry layer that records but exports nothing.
+    fn with_tracing(f: impl FnOnce()) {
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        tracing::subscriber::with_default(subscriber, f);
+    }
+
+    #[test]
+    fn remote_parent_sets_the_trace_id() {
+        with_tracing(|| {
+            let span = tracing::info_span!("export_job");
+            set_parent(&span, Some(REMOTE));
+            assert_eq!(trace_id(&span).as_deref(), Some(REMOTE_TRACE_ID));
+        });
+    }
+
+    #[test]
+    fn child_spans_propagate_the_trace_with_their_own_span_id() {
+        with_tracing(|| {
+            let span = tracing::info_span!("export_job");
+            set_parent(&span, Some(REMOTE));
+            let child = span.in_scope(|| tracing::info_span!("runpod_submit"));
+            let propagated = traceparent(&child).unwrap();
+            assert!(propagated.starts_with(&format!("00-{REMOTE_TRACE_ID}-")));
+            assert!(!propagated.contains("00f067aa0ba902b7"));
+        });
+    }
+
+    #[test]
+    fn missing_or_malformed_parents_start_a_new_trace() {
+        with_tracing(|| {
+            for parent in [None, Some("not-a-traceparent")] {
+                let root = tracing::info_span!("mcp");
+                set_parent(&root, parent);
+                let id = trace_id(&root).expect("root spans still get a trace id");
+                assert_ne!(id, REMOTE_TRACE_ID);
+            }
+        });
+    }
+
+    #[test]
+    fn disabled_spans_have_no_traceparent() {
+        let span = tracing::Span::none();
+        assert!(traceparent(&span).is_none());
+        assert!(trace_id(&span).is_none());
+    }
+}
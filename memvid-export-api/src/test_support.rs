@@ -0,0 +1,303 @@
+//! Fixtures shared by the unit tests.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, RwLock};
+use uuid::Uuid;
+
+use crate::{
+    artifact_store::FilesystemArtifactStore,
+    auth::KeyRegistry,
+    config::{ArtifactStoreKind, Config, ExportBackendMode},
+    embedding::EmbeddingRuntimeConfig,
+    http_client::HttpClient,
+    job_store::JobStore,
+    mcp_api::{new_index_cache, new_query_cache},
+    models::{
+        ExportOptions, ExportRequest, ExportSourceDescriptor, ExportStage, GraphNode,
+        GraphRelationship, JobBackendMetadata, JobRecord, JobState, NodeProperties,
+    },
+    queue::{ExportLimits, JobCancellations},
+    rate_limit::{MemoryRateLimitBackend, RateLimiter},
+    shutdown::Shutdown,
+    AppState,
+};
+
+/// Directory under the system temp dir that is removed when dropped, also
+/// when the test panics.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("memvid-{prefix}-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Environment defaults with every path under `root` and the filesystem
+/// artifact store.
+pub fn test_config(root: &Path) -> Config {
+    let mut config = Config::from_env().expect("config");
+    config.api_key = "test-key".to_string();
+    config.api_key_is_fallback = false;
+    config.api_keys_path = None;
+    config.export_root = root.join("exports");
+    config.staging_root = root.join("staging");
+    config.job_store_path = None;
+    config.backend_mode = ExportBackendMode::LegacyVps;
+    config.runpod_output_prefix = None;
+    config.artifact_store = ArtifactStoreKind::Filesystem;
+    config.s3 = None;
+    config.embed_cache_path = None;
+    config
+}
+
+/// Headers authenticating as the `test-key` admin key of `test_config`.
+pub fn auth_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_static("Bearer test-key"),
+    );
+    headers
+}
+
+/// App state over an in-memory job store, with the receiving end of the
+/// export queue.
+pub fn test_state(config: Config) -> (AppState, mpsc::Receiver<String>) {
+    let (queue_tx, queue_rx) = mpsc::channel(config.queue_capacity.max(1));
+    let backend = Arc::new(MemoryRateLimitBackend::default());
+    let state = AppState {
+        jobs: Arc::new(RwLock::new(HashMap::new())),
+        event_buses: Arc::new(RwLock::new(HashMap::new())),
+        queue_tx,
+        job_store: JobStore::open_in_memory().expect("job store"),
+        export_limits: Arc::new(ExportLimits::from_config(&config)),
+        job_cancellations: JobCancellations::default(),
+        http: HttpClient::from_config(&config).expect("http client"),
+        embedding_cache: None,
+        artifacts: Arc::new(FilesystemArtifactStore),
+        mcp_indexes: Arc::new(tokio::sync::Mutex::new(new_index_cache(4, 1024 * 1024))),
+        mcp_cache: Arc::new(tokio::sync::Mutex::new(new_query_cache(4))),
+        rate_limiter: Arc::new(RateLimiter::new("mcp", 60, 10, backend.clone())),
+        export_rate_limiter: Arc::new(RateLimiter::new("export", 60, 10, backend)),
+        keys: Arc::new(KeyRegistry::from_config(&config).expect("key registry")),
+        shutdown: Shutdown::default(),
+        config,
+    };
+    (state, queue_rx)
+}
+
+/// Small export request with two nodes, one relationship and one file.
+pub fn sample_request() -> ExportRequest {
+    let node = |id: &str, name: &str| GraphNode {
+        id: id.to_string(),
+        label: "Function".to_string(),
+        properties: NodeProperties {
+            name: name.to_string(),
+            file_path: "src/lib.rs".to_string(),
+            start_line: Some(1),
+            end_line: Some(4),
+            language: Some("rust".to_string()),
+            is_exported: Some(true),
+            heuristic_label: None,
+            cohesion: None,
+            symbol_count: None,
+            keywords: None,
+            description: None,
+            enriched_by: None,
+            process_type: None,
+            step_count: None,
+            communities: None,
+            entry_point_id: None,
+            terminal_id: None,
+            entry_point_score: None,
+            entry_point_reason: None,
+        },
+    };
+    ExportRequest {
+        session_id: "session-1".to_string(),
+        project_name: "demo".to_string(),
+        source: ExportSourceDescriptor {
+            r#type: "github".to_string(),
+            base_name: "demo".to_string(),
+            display_name: "Demo".to_string(),
+            url: None,
+            branch: None,
+            original_file_name: None,
+            folder_name: None,
+        },
+        nodes: vec![node("fn:a", "alpha"), node("fn:b", "beta")],
+        relationships: vec![GraphRelationship {
+            id: "rel:1".to_string(),
+            source_id: "fn:a".to_string(),
+            target_id: "fn:b".to_string(),
+            r#type: "CALLS".to_string(),
+            confidence: 1.0,
+            reason: "direct call".to_string(),
+            step: None,
+        }],
+        file_contents: HashMap::from([(
+            "src/lib.rs".to_string(),
+            "fn alpha() { beta() }\nfn beta() {}\n".to_string(),
+        )]),
+        options: ExportOptions {
+            semantic_enabled: false,
+            max_snippet_chars: 400,
+            max_node_frames: 100,
+            max_relation_frames: 100,
+        },
+        base_export: None,
+    }
+}
+
+/// Job record in `status` with legacy backend metadata and no request.
+pub fn job_record(job_id: &str, status: JobState) -> JobRecord {
+    let now = Utc::now();
+    JobRecord {
+        job_id: job_id.to_string(),
+        created_at: now,
+        updated_at: now,
+        status,
+        progress: 0.0,
+        message: None,
+        request: None,
+        artifact: None,
+        error: None,
+        artifact_path: None,
+        events: VecDeque::new(),
+        next_seq: 1,
+        current_stage: ExportStage::Queued,
+        stage_progress: 0.0,
+        stage_started_at: None,
+        last_event_at: now,
+        metadata: Some(JobBackendMetadata {
+            backend: "legacy_vps".to_string(),
+            runpod_job_id: None,
+            payload_ref: None,
+            artifact_ref: None,
+            base_capsule_ref: None,
+            worker_metrics: None,
+        }),
+        owner_key_id: Some("ci".to_string()),
+        planned_frames: 0,
+        produced_bytes: 0,
+        trace_parent: None,
+    }
+}
+
+/// OpenAI-style `/embeddings` double answering each input with
+/// `[input length, 1]`, zero-padded to `output_dimension` when the request
+/// sets one. The first `failures` requests get a 503. Returns the
+/// base URL and the number of requests seen.
+pub async fn spawn_embeddings_mock(failures: usize) -> (String, Arc<AtomicUsize>) {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&requests);
+    let app = Router::new().route(
+        "/embeddings",
+        post(move |Json(body): Json<Value>| async move {
+            if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({})));
+            }
+            let dimension = body["output_dimension"].as_u64().unwrap_or(2) as usize;
+            let data: Vec<Value> = body["input"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .map(|(index, text)| {
+                    let mut embedding = vec![text.as_str().unwrap().len(), 1];
+                    embedding.resize(dimension, 0);
+                    json!({ "index": index, "embedding": embedding })
+                })
+                .collect();
+            (StatusCode::OK, Json(json!({ "data": data })))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{addr}"), requests)
+}
+
+/// OpenAI `text-embedding-3-small` runtime pointed at `base_url`.
+pub fn openai_embedding_runtime(
+    base_url: String,
+    batch_size: usize,
+    max_retries: u32,
+) -> EmbeddingRuntimeConfig {
+    EmbeddingRuntimeConfig::new(
+        "external_api",
+        "openai",
+        "text-embedding-3-small",
+        None,
+        Some("sk-test".to_string()),
+        None,
+        None,
+        String::new(),
+        base_url,
+        String::new(),
+        "document".to_string(),
+        None,
+        "float".to_string(),
+        true,
+        5,
+        batch_size,
+        max_retries,
+        2,
+        HttpClient::new(Duration::from_secs(5), Duration::from_secs(5), None).unwrap(),
+    )
+    .unwrap()
+}
+
+/// Voyage `voyage-code-3` runtime pointed at `base_url`.
+pub fn voyage_embedding_runtime(base_url: String, output_dimension: u16) -> EmbeddingRuntimeConfig {
+    EmbeddingRuntimeConfig::new(
+        "external_api",
+        "voyage",
+        "voyage-code-3",
+        None,
+        None,
+        Some("pa-test".to_string()),
+        None,
+        String::new(),
+        String::new(),
+        base_url,
+        "document".to_string(),
+        Some(output_dimension),
+        "float".to_string(),
+        true,
+        5,
+        16,
+        0,
+        2,
+        HttpClient::new(Duration::from_secs(5), Duration::from_secs(5), None).unwrap(),
+    )
+    .unwrap()
+}
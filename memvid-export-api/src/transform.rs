@@ -9,6 +9,14 @@ const MV2_SCHEMA_VERSION: &str = "gitnexus.mv2.schema.v1";
 const EXPORT_SCHEMA_VERSION: &str = "gitnexus.export.schema.v1";
 const AI_BIBLE_VERSION: &str = "gitnexus.ai-bible.v1";
 
+/// Node and relationship frames an export will write, before the fixed
+/// manifest and AI bible frames.
+pub fn planned_frame_count(req: &ExportRequest) -> u64 {
+    let nodes = req.options.max_node_frames.min(req.nodes.len());
+    let relations = req.options.max_relation_frames.min(req.relationships.len());
+    (nodes + relations) as u64
+}
+
 pub fn build_frame_documents(req: &ExportRequest) -> Vec<FrameDocument> {
     let mut documents = Vec::new();
     let node_limit = req.options.max_node_frames.min(req.nodes.len());
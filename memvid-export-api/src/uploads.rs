@@ -0,0 +1,822 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path as FsPath, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use tokio::fs;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    api::submit_export,
+    auth::{authorize, ApiKey, Scope},
+    models::{
+        BaseExportRef, ExportOptions, ExportRequest, ExportSourceDescriptor, GraphNode,
+        GraphRelationship,
+    },
+    AppState,
+};
+
+const UPLOAD_MANIFEST_FILE: &str = "upload.json";
+/// Extension a session directory is renamed to while it is being committed,
+/// so a second commit of the same session finds nothing to commit.
+const COMMITTING_EXTENSION: &str = "committing";
+
+#[derive(Debug)]
+enum BodyError {
+    UnsupportedEncoding(String),
+    TooLarge(usize),
+    Decode(String),
+    Json(String),
+}
+
+impl std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedEncoding(encoding) => write!(f, "unsupported encoding {encoding}"),
+            Self::TooLarge(limit) => write!(f, "body exceeds {limit} bytes"),
+            Self::Decode(message) | Self::Json(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for BodyError {}
+
+impl BodyError {
+    fn into_response(self) -> Response {
+        let (status, code, message) = match self {
+            Self::UnsupportedEncoding(encoding) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "UNSUPPORTED_CONTENT_ENCODING",
+                format!("Unsupported Content-Encoding `{encoding}`. Supported: gzip, zstd, deflate, identity."),
+            ),
+            Self::TooLarge(limit) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "PAYLOAD_TOO_LARGE",
+                format!("Decoded request body exceeds {limit} bytes."),
+            ),
+            Self::Decode(message) => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_REQUEST_BODY",
+                format!("Failed to decode request body: {message}"),
+            ),
+            Self::Json(message) => (
+                StatusCode::BAD_REQUEST,
+                "INVALID_JSON",
+                format!("Request body is not valid JSON for this endpoint: {message}"),
+            ),
+        };
+        (
+            status,
+            Json(json!({
+                "error": {
+                    "code": code,
+                    "message": message
+                }
+            })),
+        )
+            .into_response()
+    }
+}
+
+fn decode_body(encoding: &str, body: &[u8], limit: usize) -> Result<Vec<u8>, BodyError> {
+    let reader: Box<dyn Read + '_> = match encoding {
+        "" | "identity" => {
+            if body.len() > limit {
+                return Err(BodyError::TooLarge(limit));
+            }
+            return Ok(body.to_vec());
+        }
+        "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(body)),
+        "deflate" => Box::new(flate2::read::ZlibDecoder::new(body)),
+        "zstd" => Box::new(
+            zstd::stream::read::Decoder::new(body)
+                .map_err(|err| BodyError::Decode(err.to_string()))?,
+        ),
+        other => return Err(BodyError::UnsupportedEncoding(other.to_string())),
+    };
+
+    let mut decoded = Vec::new();
+    reader
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|err| BodyError::Decode(err.to_string()))?;
+    if decoded.len() > limit {
+        return Err(BodyError::TooLarge(limit));
+    }
+    Ok(decoded)
+}
+
+/// Decodes a (possibly `Content-Encoding` compressed) JSON request body off
+/// the async runtime. Decoded size is capped at `limit` bytes.
+pub async fn decode_json_body<T>(
+    headers: &HeaderMap,
+    body: Bytes,
+    limit: usize,
+) -> Result<T, Response>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let encoding = headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    let parsed = tokio::task::spawn_blocking(move || {
+        let decoded = decode_body(&encoding, &body, limit)?;
+        serde_json::from_slice::<T>(&decoded).map_err(|err| BodyError::Json(err.to_string()))
+    })
+    .await
+    .map_err(|err| BodyError::Decode(format!("decoder task join error: {err}")));
+
+    match parsed {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(err)) | Err(err) => Err(err.into_response()),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUploadRequest {
+    pub session_id: String,
+    pub project_name: String,
+    pub source: ExportSourceDescriptor,
+    pub options: ExportOptions,
+    #[serde(default)]
+    pub base_export: Option<BaseExportRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UploadManifest {
+    upload_id: String,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    #[serde(default)]
+    owner_key_id: Option<String>,
+    request: CreateUploadRequest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UploadPartKind {
+    Nodes,
+    Relationships,
+    Files,
+}
+
+impl UploadPartKind {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "nodes" => Some(Self::Nodes),
+            "relationships" => Some(Self::Relationships),
+            "files" => Some(Self::Files),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Nodes => "nodes",
+            Self::Relationships => "relationships",
+            Self::Files => "files",
+        }
+    }
+}
+
+fn uploads_root(state: &AppState) -> PathBuf {
+    state.config.staging_root.join("uploads")
+}
+
+fn upload_dir(state: &AppState, upload_id: &str) -> Option<PathBuf> {
+    Uuid::parse_str(upload_id)
+        .ok()
+        .map(|id| uploads_root(state).join(id.to_string()))
+}
+
+fn upload_error(status: StatusCode, code: &str, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": {
+                "code": code,
+                "message": message.into()
+            }
+        })),
+    )
+        .into_response()
+}
+
+fn upload_not_found() -> Response {
+    upload_error(
+        StatusCode::NOT_FOUND,
+        "UPLOAD_NOT_FOUND",
+        "Upload session not found or expired.",
+    )
+}
+
+async fn read_manifest(dir: &FsPath) -> Option<UploadManifest> {
+    let raw = fs::read(dir.join(UPLOAD_MANIFEST_FILE)).await.ok()?;
+    serde_json::from_slice(&raw).ok()
+}
+
+async fn load_manifest(dir: &FsPath) -> Option<UploadManifest> {
+    read_manifest(dir)
+        .await
+        .filter(|manifest| manifest.expires_at > Utc::now())
+}
+
+/// Like `load_manifest`, but hides sessions created by another API key.
+async fn load_owned_manifest(dir: &FsPath, key: &ApiKey) -> Option<UploadManifest> {
+    load_manifest(dir)
+        .await
+        .filter(|manifest| key.can_access(manifest.owner_key_id.as_deref()))
+}
+
+async fn list_parts(dir: &FsPath, kind: UploadPartKind) -> Result<Vec<(u32, PathBuf)>> {
+    let mut parts = Vec::new();
+    let kind_dir = dir.join(kind.as_str());
+    let mut entries = match fs::read_dir(&kind_dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(parts),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to list {}", kind_dir.display()))
+        }
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(part) = name
+            .strip_suffix(".json")
+            .and_then(|v| v.parse::<u32>().ok())
+        {
+            parts.push((part, entry.path()));
+        }
+    }
+    parts.sort_by_key(|(part, _)| *part);
+    Ok(parts)
+}
+
+pub async fn create_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+
+    let request: CreateUploadRequest =
+        match decode_json_body(&headers, body, state.config.max_decoded_body_bytes).await {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+
+    let upload_id = Uuid::new_v4().to_string();
+    let now = Utc::now();
+    let manifest = UploadManifest {
+        upload_id: upload_id.clone(),
+        created_at: now,
+        expires_at: now + ChronoDuration::seconds(state.config.upload_ttl_seconds as i64),
+        owner_key_id: Some(key.id),
+        request,
+    };
+
+    let dir = uploads_root(&state).join(&upload_id);
+    let write_result = async {
+        fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("Failed to create upload dir {}", dir.display()))?;
+        fs::write(
+            dir.join(UPLOAD_MANIFEST_FILE),
+            serde_json::to_vec(&manifest)?,
+        )
+        .await
+        .context("Failed to write upload manifest")
+    }
+    .await;
+    if let Err(err) = write_result {
+        warn!(upload_id = %upload_id, "Failed to create upload session: {err:#}");
+        return upload_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "UPLOAD_CREATE_FAILED",
+            format!("Failed to create upload session: {err}"),
+        );
+    }
+
+    info!(upload_id = %upload_id, project = %manifest.request.project_name, "Upload session created");
+
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "uploadId": upload_id,
+            "expiresAt": manifest.expires_at,
+            "partUrls": {
+                "nodes": format!("/v1/uploads/{upload_id}/nodes/{{part}}"),
+                "relationships": format!("/v1/uploads/{upload_id}/relationships/{{part}}"),
+                "files": format!("/v1/uploads/{upload_id}/files/{{part}}"),
+            },
+            "commitUrl": format!("/v1/uploads/{upload_id}/commit"),
+        })),
+    )
+        .into_response()
+}
+
+/// Stores one chunk of an upload. Re-sending the same part number replaces
+/// it, so interrupted chunks can be retried.
+pub async fn put_upload_part(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((upload_id, kind, part)): Path<(String, String, u32)>,
+    body: Bytes,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+
+    let Some(kind) = UploadPartKind::parse(&kind) else {
+        return upload_error(
+            StatusCode::NOT_FOUND,
+            "UNKNOWN_UPLOAD_PART",
+            "Upload parts must be nodes, relationships or files.",
+        );
+    };
+    let Some(dir) = upload_dir(&state, &upload_id) else {
+        return upload_not_found();
+    };
+    if load_owned_manifest(&dir, &key).await.is_none() {
+        return upload_not_found();
+    }
+
+    let limit = state.config.max_decoded_body_bytes;
+    let items = match kind {
+        UploadPartKind::Nodes => decode_json_body::<Vec<GraphNode>>(&headers, body, limit)
+            .await
+            .map(|v| (v.len(), serde_json::to_vec(&v))),
+        UploadPartKind::Relationships => {
+            decode_json_body::<Vec<GraphRelationship>>(&headers, body, limit)
+                .await
+                .map(|v| (v.len(), serde_json::to_vec(&v)))
+        }
+        UploadPartKind::Files => decode_json_body::<HashMap<String, String>>(&headers, body, limit)
+            .await
+            .map(|v| (v.len(), serde_json::to_vec(&v))),
+    };
+    let (count, encoded) = match items {
+        Ok((count, Ok(encoded))) => (count, encoded),
+        Ok((_, Err(err))) => {
+            return upload_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "UPLOAD_PART_WRITE_FAILED",
+                err.to_string(),
+            )
+        }
+        Err(response) => return response,
+    };
+
+    let kind_dir = dir.join(kind.as_str());
+    let part_path = kind_dir.join(format!("{part:08}.json"));
+    let tmp_path = kind_dir.join(format!("{part:08}.json.{}.tmp", Uuid::new_v4()));
+    let write_result = async {
+        fs::create_dir_all(&kind_dir).await?;
+        fs::write(&tmp_path, encoded).await?;
+        fs::rename(&tmp_path, &part_path).await
+    }
+    .await;
+    if let Err(err) = write_result {
+        let _ = fs::remove_file(&tmp_path).await;
+        return upload_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "UPLOAD_PART_WRITE_FAILED",
+            format!("Failed to store upload part: {err}"),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "uploadId": upload_id,
+            "kind": kind.as_str(),
+            "part": part,
+            "items": count,
+        })),
+    )
+        .into_response()
+}
+
+pub async fn get_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<String>,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportRead) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+    let Some(dir) = upload_dir(&state, &upload_id) else {
+        return upload_not_found();
+    };
+    let Some(manifest) = load_owned_manifest(&dir, &key).await else {
+        return upload_not_found();
+    };
+
+    let mut parts = serde_json::Map::new();
+    for kind in [
+        UploadPartKind::Nodes,
+        UploadPartKind::Relationships,
+        UploadPartKind::Files,
+    ] {
+        let numbers: Vec<u32> = list_parts(&dir, kind)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(part, _)| part)
+            .collect();
+        parts.insert(kind.as_str().to_string(), json!(numbers));
+    }
+
+    (
+        StatusCode::OK,
+        Json(json!({
+            "uploadId": manifest.upload_id,
+            "createdAt": manifest.created_at,
+            "expiresAt": manifest.expires_at,
+            "parts": parts,
+        })),
+    )
+        .into_response()
+}
+
+/// Bytes of stored parts a commit may still read before the assembled body
+/// is larger than a single decoded export body may be.
+struct PartBudget {
+    limit: usize,
+    used: usize,
+}
+
+impl PartBudget {
+    fn charge(&mut self, len: usize) -> Result<(), BodyError> {
+        self.used = self.used.saturating_add(len);
+        if self.used > self.limit {
+            return Err(BodyError::TooLarge(self.limit));
+        }
+        Ok(())
+    }
+}
+
+async fn read_part<T: DeserializeOwned>(path: &FsPath, budget: &mut PartBudget) -> Result<T> {
+    let raw = fs::read(path)
+        .await
+        .with_context(|| format!("Failed reading upload part {}", path.display()))?;
+    budget.charge(raw.len())?;
+    serde_json::from_slice(&raw)
+        .with_context(|| format!("Failed decoding upload part {}", path.display()))
+}
+
+async fn assemble_request(
+    dir: &FsPath,
+    manifest: UploadManifest,
+    limit: usize,
+) -> Result<ExportRequest> {
+    let mut budget = PartBudget { limit, used: 0 };
+    let mut nodes = Vec::new();
+    for (_, path) in list_parts(dir, UploadPartKind::Nodes).await? {
+        nodes.extend(read_part::<Vec<GraphNode>>(&path, &mut budget).await?);
+    }
+    let mut relationships = Vec::new();
+    for (_, path) in list_parts(dir, UploadPartKind::Relationships).await? {
+        relationships.extend(read_part::<Vec<GraphRelationship>>(&path, &mut budget).await?);
+    }
+    let mut file_contents = HashMap::new();
+    for (_, path) in list_parts(dir, UploadPartKind::Files).await? {
+        file_contents.extend(read_part::<HashMap<String, String>>(&path, &mut budget).await?);
+    }
+
+    let request = manifest.request;
+    Ok(ExportRequest {
+        session_id: request.session_id,
+        project_name: request.project_name,
+        source: request.source,
+        nodes,
+        relationships,
+        file_contents,
+        options: request.options,
+        base_export: request.base_export,
+    })
+}
+
+/// Assembles all stored parts into an `ExportRequest` and submits it as a
+/// regular export job. The session directory is moved aside first, so only
+/// one commit can run; it is removed once the job is queued and put back
+/// when the export is not accepted.
+pub async fn commit_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<String>,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+    let Some(dir) = upload_dir(&state, &upload_id) else {
+        return upload_not_found();
+    };
+    let Some(manifest) = load_owned_manifest(&dir, &key).await else {
+        return upload_not_found();
+    };
+
+    let committing = dir.with_extension(COMMITTING_EXTENSION);
+    if let Err(err) = fs::rename(&dir, &committing).await {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            if !fs::try_exists(&committing).await.unwrap_or(false) {
+                return upload_not_found();
+            }
+            return upload_error(
+                StatusCode::CONFLICT,
+                "UPLOAD_COMMIT_IN_PROGRESS",
+                "Upload session is already being committed.",
+            );
+        }
+        return upload_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "UPLOAD_COMMIT_FAILED",
+            format!("Failed to lock upload session: {err}"),
+        );
+    }
+
+    let response = commit_locked_upload(&state, &key, &upload_id, &committing, manifest).await;
+    let outcome = if response.status() == StatusCode::ACCEPTED {
+        fs::remove_dir_all(&committing).await
+    } else {
+        fs::rename(&committing, &dir).await
+    };
+    if let Err(err) = outcome {
+        warn!(upload_id = %upload_id, "Failed to release committed upload: {err}");
+    }
+    response
+}
+
+async fn commit_locked_upload(
+    state: &AppState,
+    key: &ApiKey,
+    upload_id: &str,
+    dir: &FsPath,
+    manifest: UploadManifest,
+) -> Response {
+    let limit = state.config.max_decoded_body_bytes;
+    let request = match assemble_request(dir, manifest, limit).await {
+        Ok(request) => request,
+        Err(err) if err.is::<BodyError>() => {
+            return BodyError::TooLarge(limit).into_response();
+        }
+        Err(err) => {
+            warn!(upload_id = %upload_id, "Failed to assemble upload: {err:#}");
+            return upload_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "UPLOAD_ASSEMBLY_FAILED",
+                format!("Failed to assemble upload: {err}"),
+            );
+        }
+    };
+
+    info!(
+        upload_id = %upload_id,
+        nodes = request.nodes.len(),
+        relationships = request.relationships.len(),
+        files = request.file_contents.len(),
+        "Committing upload session"
+    );
+
+    submit_export(state, request, key).await
+}
+
+pub async fn delete_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<String>,
+) -> Response {
+    let key = match authorize(&state.keys, &headers, Scope::ExportWrite) {
+        Ok(key) => key,
+        Err(err) => return err.into_response(),
+    };
+    let Some(dir) = upload_dir(&state, &upload_id) else {
+        return upload_not_found();
+    };
+    if load_owned_manifest(&dir, &key).await.is_none() {
+        return upload_not_found();
+    }
+    match fs::remove_dir_all(&dir).await {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => upload_not_found(),
+        Err(err) => upload_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "UPLOAD_DELETE_FAILED",
+            format!("Failed to delete upload session: {err}"),
+        ),
+    }
+}
+
+/// Removes upload sessions whose TTL has passed. A directory without a
+/// readable manifest is only removed once it is older than the TTL, since
+/// `create_upload` writes the manifest after creating the directory.
+pub async fn cleanup_expired_uploads(state: &AppState) -> Result<()> {
+    let ttl = std::time::Duration::from_secs(state.config.upload_ttl_seconds);
+    let root = uploads_root(state);
+    let mut entries = match fs::read_dir(&root).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("Failed to list {}", root.display())),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let dir = entry.path();
+        if !entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false) {
+            continue;
+        }
+        let expired = match read_manifest(&dir).await {
+            Some(manifest) => manifest.expires_at <= Utc::now(),
+            None => entry
+                .metadata()
+                .await
+                .ok()
+                .and_then(|meta| meta.modified().ok())
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > ttl),
+        };
+        if expired {
+            info!(upload = %dir.display(), "Removing expired upload session");
+            if let Err(err) = fs::remove_dir_all(&dir).await {
+                warn!("Failed to remove expired upload {}: {err}", dir.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{auth_headers, sample_request, test_config, test_state, TempDir};
+    use std::io::Write;
+
+    const RAW: &[u8] = br#"{"hello":"world"}"#;
+
+    fn gzip(raw: &[u8]) -> Vec<u8> {
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(raw).unwrap();
+        gz.finish().unwrap()
+    }
+
+    #[test]
+    fn decode_body_inflates_gzip_and_zstd() {
+        assert_eq!(decode_body("gzip", &gzip(RAW), 1024).unwrap(), RAW);
+        let zst = zstd::stream::encode_all(RAW, 0).unwrap();
+        assert_eq!(decode_body("zstd", &zst, 1024).unwrap(), RAW);
+        assert_eq!(decode_body("identity", RAW, 1024).unwrap(), RAW);
+    }
+
+    #[test]
+    fn decode_body_rejects_oversized_and_unknown_encodings() {
+        assert!(matches!(
+            decode_body("gzip", &gzip(RAW), 4),
+            Err(BodyError::TooLarge(4))
+        ));
+        assert!(matches!(
+            decode_body("", RAW, 4),
+            Err(BodyError::TooLarge(4))
+        ));
+        assert!(matches!(
+            decode_body("br", RAW, 1024),
+            Err(BodyError::UnsupportedEncoding(_))
+        ));
+    }
+
+    /// Creates a session holding the nodes and relationships of the sample
+    /// request and returns its id.
+    async fn upload_with_parts(state: &AppState) -> String {
+        let sample = sample_request();
+        let create = json!({
+            "sessionId": sample.session_id,
+            "projectName": sample.project_name,
+            "source": sample.source,
+            "options": sample.options,
+        });
+        let response = create_upload(
+            State(state.clone()),
+            auth_headers(),
+            Bytes::from(serde_json::to_vec(&create).unwrap()),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let upload_id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["uploadId"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        for (kind, part) in [
+            ("nodes", serde_json::to_vec(&sample.nodes).unwrap()),
+            (
+                "relationships",
+                serde_json::to_vec(&sample.relationships).unwrap(),
+            ),
+        ] {
+            let response = put_upload_part(
+                State(state.clone()),
+                auth_headers(),
+                Path((upload_id.clone(), kind.to_string(), 1)),
+                Bytes::from(part),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        upload_id
+    }
+
+    async fn commit(state: &AppState, upload_id: &str) -> Response {
+        commit_upload(
+            State(state.clone()),
+            auth_headers(),
+            Path(upload_id.to_string()),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn concurrent_commits_submit_one_export() {
+        let dir = TempDir::new("uploads-commit");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let upload_id = upload_with_parts(&state).await;
+
+        let (first, second) = tokio::join!(commit(&state, &upload_id), commit(&state, &upload_id));
+        let mut statuses = [first.status(), second.status()];
+        statuses.sort();
+        assert_eq!(statuses[0], StatusCode::ACCEPTED);
+        assert!(matches!(
+            statuses[1],
+            StatusCode::NOT_FOUND | StatusCode::CONFLICT
+        ));
+        assert_eq!(state.jobs.read().await.len(), 1);
+        assert!(!uploads_root(&state).join(&upload_id).exists());
+    }
+
+    #[tokio::test]
+    async fn commits_over_the_body_limit_are_rejected_and_keep_the_session() {
+        let dir = TempDir::new("uploads-limit");
+        let (mut state, _queue_rx) = test_state(test_config(dir.path()));
+        let upload_id = upload_with_parts(&state).await;
+        let session = uploads_root(&state).join(&upload_id);
+        let mut stored = 0;
+        for kind in [UploadPartKind::Nodes, UploadPartKind::Relationships] {
+            for (_, path) in list_parts(&session, kind).await.unwrap() {
+                stored += std::fs::metadata(path).unwrap().len() as usize;
+            }
+        }
+        // Every part fits on its own; together they do not.
+        state.config.max_decoded_body_bytes = stored - 1;
+
+        let response = commit(&state, &upload_id).await;
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(state.jobs.read().await.is_empty());
+        assert!(uploads_root(&state)
+            .join(&upload_id)
+            .join(UPLOAD_MANIFEST_FILE)
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn cleanup_spares_sessions_still_being_created() {
+        let dir = TempDir::new("uploads-cleanup");
+        let (state, _queue_rx) = test_state(test_config(dir.path()));
+        let root = uploads_root(&state);
+        let creating = root.join(Uuid::new_v4().to_string());
+        let abandoned = root.join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&creating).unwrap();
+        std::fs::create_dir_all(&abandoned).unwrap();
+        std::fs::File::open(&abandoned)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH)
+            .unwrap();
+        let live = root.join(upload_with_parts(&state).await);
+
+        cleanup_expired_uploads(&state).await.unwrap();
+        assert!(creating.exists());
+        assert!(live.exists());
+        assert!(!abandoned.exists());
+    }
+}